@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_time::Timer;
+use embassy_executor::Spawner;
+
+use sifli_hal::adc::{self, Adc, AnyAdcChannel};
+use sifli_hal::bind_interrupts;
+
+bind_interrupts!(struct Irqs {
+    GPADC => adc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut adc = Adc::new(p.GPADC, Irqs, Default::default());
+    let mut ch0 = AnyAdcChannel::new(p.PA28);
+    let mut ch1 = AnyAdcChannel::new(p.PA29);
+
+    loop {
+        let mv = adc.read_millivolts(&mut ch0).await;
+        info!("PA28: {} mV", mv);
+
+        let mut raw = [0; 2];
+        unwrap!(adc.read_scan(&mut [&mut ch0, &mut ch1], &mut raw).await);
+        info!("scan: {} {}", raw[0], raw[1]);
+
+        Timer::after_secs(1).await;
+    }
+}
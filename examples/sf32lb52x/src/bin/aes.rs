@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::aes::{self, Aes, Mode};
+use sifli_hal::bind_interrupts;
+
+bind_interrupts!(struct Irqs {
+    AES => aes::InterruptHandler;
+});
+
+// NIST SP 800-38A, F.1.1, F.2.1 and F.5.1, first block.
+const KEY: [u8; 16] = [
+    0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c,
+];
+const PLAINTEXT: [u8; 16] = [
+    0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+];
+const CBC_IV: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+const CTR_COUNTER: [u8; 16] = [
+    0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xfe, 0xff,
+];
+const ECB_CIPHERTEXT: [u8; 16] = [
+    0x3a, 0xd7, 0x7b, 0xb4, 0x0d, 0x7a, 0x36, 0x60, 0xa8, 0x9e, 0xca, 0xf3, 0x24, 0x66, 0xef, 0x97,
+];
+const CBC_CIPHERTEXT: [u8; 16] = [
+    0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9, 0x19, 0x7d,
+];
+const CTR_CIPHERTEXT: [u8; 16] = [
+    0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d, 0xb6, 0xce,
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut aes = Aes::new(p.AES, Irqs);
+    unwrap!(aes.set_key(&KEY));
+
+    for (name, mode, expected) in [
+        ("ECB", Mode::Ecb, ECB_CIPHERTEXT),
+        ("CBC", Mode::Cbc { iv: CBC_IV }, CBC_CIPHERTEXT),
+        ("CTR", Mode::Ctr { counter: CTR_COUNTER }, CTR_CIPHERTEXT),
+    ] {
+        let mut buf = PLAINTEXT;
+        unwrap!(aes.encrypt_in_place(&mut mode.clone(), &mut buf).await);
+        assert_eq!(buf, expected);
+
+        let mut decrypted = [0; 16];
+        unwrap!(aes.decrypt(&mut mode.clone(), &buf, &mut decrypted).await);
+        assert_eq!(decrypted, PLAINTEXT);
+
+        info!("AES-128 {}: ok", name);
+    }
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::peripherals;
+use sifli_hal::time::hz;
+use sifli_hal::timer::basic::{self, BasicTimer};
+
+bind_interrupts!(struct Irqs {
+    BTIM1 => basic::InterruptHandler<peripherals::BTIM1>;
+    BTIM2 => basic::InterruptHandler<peripherals::BTIM2>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut delay = BasicTimer::new(p.BTIM2, Irqs);
+    delay.wait_ms(100).await;
+    info!("delay done");
+
+    let mut ticker = BasicTimer::new(p.BTIM1, Irqs);
+    ticker.start_periodic(hz(10));
+
+    loop {
+        ticker.wait_next_tick().await;
+        info!("tick {}", ticker.ticks());
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::dma::{ExtDma, Transfer, TransferOptions};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let src: [u32; 64] = core::array::from_fn(|i| i as u32);
+    let mut dst = [0u32; 64];
+
+    // DMAC1 channel, memory to memory
+    unsafe { Transfer::new_transfer(&mut p.DMAC_CH1, &src, &mut dst, TransferOptions::default()) }.await;
+    info!("DMAC1 copy: {}", dst == src);
+
+    // EXTDMA copy engine
+    dst.fill(0);
+    let mut extdma = ExtDma::new(p.EXTDMA);
+    extdma.copy(&src, &mut dst).await;
+    info!("EXTDMA copy: {}", dst == src);
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::i2c::{self, Config, I2c};
+use sifli_hal::peripherals;
+
+bind_interrupts!(struct Irqs {
+    I2C1 => i2c::InterruptHandler<peripherals::I2C1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = Config::default();
+    config.scl_pullup = true;
+    config.sda_pullup = true;
+    let mut i2c = unwrap!(I2c::new(p.I2C1, p.PA30, p.PA33, Irqs, config));
+
+    for addr in 0x08..0x78u8 {
+        if i2c.write(addr, &[]).await.is_ok() {
+            info!("found device at 0x{:02x}", addr);
+        }
+    }
+    info!("scan done");
+}
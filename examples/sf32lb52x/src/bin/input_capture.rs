@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::gpio::Pull;
+use sifli_hal::peripherals;
+use sifli_hal::time::{khz, mhz};
+use sifli_hal::timer::input_capture::{CapturePin, InputCapture};
+use sifli_hal::timer::pwm_input::PwmInput;
+use sifli_hal::timer::{self, Channel};
+
+bind_interrupts!(struct Irqs {
+    GPTIM1 => timer::InterruptHandler<peripherals::GPTIM1>;
+    ATIM1 => timer::InterruptHandler<peripherals::ATIM1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    // Echo pulse of an ultrasonic sensor on PA10.
+    let ch1 = CapturePin::new(p.PA10, Pull::Down);
+    let mut ic = InputCapture::new(p.GPTIM1, Some(ch1), None, None, None, Irqs, mhz(1));
+
+    // Tachometer on PA11.
+    let mut pwm_input = PwmInput::new(p.ATIM1, p.PA11, Pull::Up, Irqs, khz(100));
+    pwm_input.enable();
+
+    loop {
+        let start = ic.wait_for_rising_edge(Channel::Ch1).await;
+        let end = ic.wait_for_falling_edge(Channel::Ch1).await;
+        info!("echo: {} us", end.wrapping_sub(start));
+
+        let (period, width) = pwm_input.wait_for_cycle().await;
+        info!("tacho: period {} ticks, high {} ticks", period, width);
+    }
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_time::Timer;
+use embassy_executor::Spawner;
+
+use sifli_hal::time::khz;
+use sifli_hal::timer::low_level::CountingMode;
+use sifli_hal::timer::simple_pwm::{PwmPin, SimplePwm};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    // SF32LB52-DevKit-LCD LED pin
+    let ch1 = PwmPin::new(p.PA26);
+    let mut pwm = SimplePwm::new(p.GPTIM1, Some(ch1), None, None, None, khz(10), CountingMode::EdgeAlignedUp);
+    let mut ch1 = pwm.ch1();
+    ch1.enable();
+
+    info!("PWM initialized");
+    info!("PWM max duty {}", ch1.max_duty_cycle());
+
+    loop {
+        ch1.set_duty_cycle_fully_off();
+        Timer::after_millis(300).await;
+        ch1.set_duty_cycle_fraction(1, 4);
+        Timer::after_millis(300).await;
+        ch1.set_duty_cycle_fraction(1, 2);
+        Timer::after_millis(300).await;
+        ch1.set_duty_cycle(ch1.max_duty_cycle() - 1);
+        Timer::after_millis(300).await;
+    }
+}
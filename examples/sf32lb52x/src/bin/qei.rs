@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::gpio::Pull;
+use sifli_hal::peripherals;
+use sifli_hal::timer;
+use sifli_hal::timer::qei::{Config, QeiEncoder, QeiPin};
+
+bind_interrupts!(struct Irqs {
+    GPTIM1 => timer::InterruptHandler<peripherals::GPTIM1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let a = QeiPin::new(p.PA10, Pull::Up);
+    let b = QeiPin::new(p.PA11, Pull::Up);
+    let index = QeiPin::new(p.PA12, Pull::Up);
+    let mut qei = QeiEncoder::new(p.GPTIM1, a, b, Some(index), Irqs, Config::default());
+
+    let position = qei.wait_for_index().await;
+    info!("index at {}", position);
+    qei.set_position(0);
+
+    loop {
+        qei.wait_for_position(400).await;
+        info!("reached 400, direction {}", qei.read_direction());
+        qei.wait_for_position(0).await;
+        info!("back to 0, direction {}", qei.read_direction());
+    }
+}
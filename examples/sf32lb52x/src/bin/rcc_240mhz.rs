@@ -25,7 +25,7 @@ async fn main(_spawner: Spawner) {
     config.rcc.dll1 = ConfigOption::Update(DllConfig { enable: true, stg: 9, div2: false });
     let p = sifli_hal::init(config);
 
-    rcc::test_print_clocks();
+    info!("{}", rcc::clocks());
 
     // SF32LB52-DevKit-LCD LED pin
     let mut led = gpio::Output::new(p.PA26, gpio::Level::Low);
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::rtc::{self, Alarm, AlarmTime, DateTime, DayOfWeek, Rtc};
+
+bind_interrupts!(struct Irqs {
+    RTC => rtc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut rtc = unwrap!(Rtc::new(p.RTC, Irqs, Default::default()));
+
+    // The backup registers survive resets, count the boots.
+    let boots = unwrap!(rtc.read_backup_register(0)) + 1;
+    rtc.write_backup_register(0, boots);
+    info!("boot #{}", boots);
+
+    if !rtc.is_running() {
+        let now = unwrap!(DateTime::from(2025, 1, 1, DayOfWeek::Wednesday, 12, 0, 0));
+        rtc.set_datetime(now);
+    }
+
+    let now = unwrap!(rtc.now());
+    rtc.set_alarm(
+        Alarm::A,
+        AlarmTime {
+            day: None,
+            hour: now.hour(),
+            minute: (now.minute() + 1) % 60,
+            second: now.second(),
+        },
+    );
+
+    rtc.start_wakeup_timer_ms(5000);
+    for _ in 0..3 {
+        rtc.wait_for_wakeup().await;
+        let now = unwrap!(rtc.now());
+        info!("{}-{}-{} {}:{}:{}", now.year(), now.month(), now.day(), now.hour(), now.minute(), now.second());
+    }
+    rtc.stop_wakeup_timer();
+
+    rtc.wait_for_alarm(Alarm::A).await;
+    info!("alarm A fired");
+}
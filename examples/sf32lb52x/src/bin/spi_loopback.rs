@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::spi::{Config, Spi};
+use sifli_hal::time::Hertz;
+
+// Connect PA37 (MOSI) to PA38 (MISO).
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = Config::default();
+    config.frequency = Hertz(4_000_000);
+    let mut spi = unwrap!(Spi::new(p.SPI2, p.PA39, p.PA37, p.PA38, p.DMAC_CH1, p.DMAC_CH2, config));
+
+    let tx: [u8; 16] = core::array::from_fn(|i| i as u8);
+    let mut rx = [0u8; 16];
+    unwrap!(spi.transfer(&mut rx, &tx).await);
+    info!("loopback: {}", rx == tx);
+
+    unwrap!(spi.blocking_transfer(&mut rx, &tx));
+    info!("blocking loopback: {}", rx == tx);
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_time::Timer;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::trng::{self, Rng};
+
+bind_interrupts!(struct Irqs {
+    TRNG => trng::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut rng = Rng::new(p.TRNG, Irqs);
+
+    loop {
+        let mut buf = [0u8; 16];
+        match rng.async_fill_bytes(&mut buf).await {
+            Ok(()) => info!("random: {=[u8]:x}", buf),
+            Err(e) => {
+                warn!("TRNG error: {}, reseeding", e);
+                rng.reseed().await;
+            }
+        }
+        Timer::after_secs(1).await;
+    }
+}
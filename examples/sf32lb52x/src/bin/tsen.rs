@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_time::Timer;
+use embassy_executor::Spawner;
+
+use sifli_hal::bind_interrupts;
+use sifli_hal::pmu::dvfs::{self, HpsysDvfsMode};
+use sifli_hal::tsen::{self, Tsen};
+
+bind_interrupts!(struct Irqs {
+    TSEN => tsen::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut tsen = Tsen::new(p.TSEN, Irqs);
+
+    loop {
+        let celsius = tsen.read_celsius().await;
+        info!("die temperature: {} C", celsius);
+
+        // Throttle to S0 (144 MHz) when hot, with some hysteresis.
+        if celsius > 85.0 && dvfs::mode_ceiling().is_none() {
+            unwrap!(dvfs::set_mode_ceiling(Some(HpsysDvfsMode::S0)));
+            info!("throttled");
+        } else if celsius < 75.0 && dvfs::mode_ceiling().is_some() {
+            unwrap!(dvfs::set_mode_ceiling(None));
+            info!("unthrottled");
+        }
+
+        Timer::after_secs(1).await;
+    }
+}
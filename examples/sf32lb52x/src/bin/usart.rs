@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_executor::Spawner;
+
+use sifli_hal::{bind_interrupts, peripherals, usart};
+
+bind_interrupts!(struct Irqs {
+    USART2 => usart::InterruptHandler<peripherals::USART2>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+
+    let config = usart::Config::default();
+    let mut usart = usart::Uart::new(p.USART2, p.PA27, p.PA20, Irqs, config).unwrap();
+    info!("Hello World!");
+
+    unwrap!(usart.write(b"Hello Embassy World!\r\n").await);
+    info!("wrote Hello, starting echo");
+
+    let mut buf = [0u8; 1];
+    loop {
+        unwrap!(usart.read(&mut buf).await);
+        unwrap!(usart.write(&buf).await);
+    }
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use defmt_rtt as _;
+use panic_probe as _;
+use embassy_time::Timer;
+use embassy_executor::Spawner;
+
+use sifli_hal::wdg::{self, Watchdog};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = sifli_hal::init(Default::default());
+    info!("Hello World!");
+    info!("reset reason: {}", wdg::reset_reason());
+
+    let mut config = wdg::Config::default();
+    config.timeout_us = 2_000_000;
+    let mut wdt = unwrap!(Watchdog::new(p.IWDT, config));
+    wdt.start();
+
+    // Feed a few times, then stop feeding and let it reset the chip.
+    for i in 0..5 {
+        Timer::after_secs(1).await;
+        wdt.pet();
+        info!("fed #{}", i);
+    }
+    info!("starving the watchdog");
+    loop {
+        Timer::after_secs(1).await;
+    }
+}
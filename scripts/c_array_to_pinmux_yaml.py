@@ -23,6 +23,17 @@ import re
 import yaml
 import os
 
+# Analog functions, missing from the SDK table: (function, value) per pin.
+ANALOG_FUNCTIONS = {
+    'GPIO_A28': ('GPADC_CH0', 15),
+    'GPIO_A29': ('GPADC_CH1', 15),
+    'GPIO_A30': ('GPADC_CH2', 15),
+    'GPIO_A31': ('GPADC_CH3', 15),
+    'GPIO_A32': ('GPADC_CH4', 15),
+    'GPIO_A33': ('GPADC_CH5', 15),
+    'GPIO_A34': ('GPADC_CH6', 15),
+}
+
 def parse_c_array(input_text):
     """
     Parse C array input text and extract pin configurations
@@ -58,6 +69,14 @@ def parse_c_array(input_text):
                     'function': func,
                     'value': value
                 })
+
+        if items[0] in ANALOG_FUNCTIONS:
+            func, value = ANALOG_FUNCTIONS[items[0]]
+            pin_entry['functions'].append({
+                'function': func,
+                'value': value
+            })
+            pin_entry['functions'].sort(key=lambda f: f['value'])
         
         result.append(pin_entry)
     
@@ -14,7 +14,7 @@ use serde_yaml;
 
 mod build_serde;
 // Structures imported from build_serde.rs
-use build_serde::{IR, FieldSet, Field, Interrupts, Peripherals};
+use build_serde::{IR, FieldSet, Field, Interrupts, Peripherals, Pinmux};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Retrieve all enabled features
@@ -74,6 +74,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let peripherals: Peripherals = serde_yaml::from_str(&peripherals_content)
         .map_err(|e| format!("Failed to parse peripherals.yaml: {}", e))?;
 
+    // Read and parse pinmux.yaml
+    let pinmux_path = data_dir.join("pinmux.yaml");
+    let pinmux_content = fs::read_to_string(&pinmux_path)
+        .map_err(|e| format!("Failed to read pinmux.yaml: {}", e))?;
+
+    let pinmux: Pinmux = serde_yaml::from_str(&pinmux_content)
+        .map_err(|e| format!("Failed to parse pinmux.yaml: {}", e))?;
+
     // Get output path from env
     let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
     let dest_path = out_dir.join("_generated.rs");
@@ -92,6 +100,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let implementations = generate_rcc_impl(&peripherals, &fieldsets);
     token_stream.extend(implementations);
 
+    // Generate pad functions and pin trait implementations
+    let pin_functions = generate_pin_functions(&pinmux);
+    token_stream.extend(pin_functions);
+
     // Write to file
     let mut file = File::create(&dest_path).unwrap();
     write!(file, "{}", token_stream).unwrap();
@@ -159,12 +171,12 @@ fn generate_rcc_impl(peripherals: &Peripherals, fieldsets: &BTreeMap<String, Fie
     implementations.extend(quote! {use crate::time::Hertz;});
     for peripheral in &peripherals.hcpu {
         if let Some(clock) = peripheral.clock.clone() {
-            let clock_fn_ident = format_ident!("get_{}_freq", clock);
+            let clock_ident = format_ident!("{}", clock);
             let peripheral_name_ident = format_ident!("{}", peripheral.name);
             let impl_tokens = quote! {
                 impl crate::rcc::SealedRccGetFreq for crate::peripherals::#peripheral_name_ident {
                     fn get_freq() -> Option<Hertz> {
-                        crate::rcc::#clock_fn_ident()
+                        crate::rcc::clocks().#clock_ident
                     }
                 }
                 impl crate::rcc::RccGetFreq for crate::peripherals::#peripheral_name_ident {}
@@ -176,6 +188,174 @@ fn generate_rcc_impl(peripherals: &Peripherals, fieldsets: &BTreeMap<String, Fie
     implementations
 }
 
+/// Pin traits implemented by pads carrying `function`,
+/// as `(trait path, peripheral, timer channel)`.
+///
+/// Functions of peripherals without a driver are only reachable through
+/// `Flex::set_function`.
+fn pin_signals(function: &str) -> Vec<(TokenStream, String, Option<String>)> {
+    let mut signals = Vec::new();
+
+    // `PAxx_I2C_UART`: any USART or I2C signal, routed by HPSYS_CFG.
+    if function.ends_with("_I2C_UART") {
+        for usart in ["USART1", "USART2", "USART3"] {
+            for signal in ["RxPin", "TxPin", "CtsPin", "RtsPin"] {
+                let signal = format_ident!("{}", signal);
+                signals.push((quote!(crate::usart::#signal), usart.to_string(), None));
+            }
+        }
+        for i2c in ["I2C1", "I2C2", "I2C3", "I2C4"] {
+            for signal in ["SclPin", "SdaPin"] {
+                let signal = format_ident!("{}", signal);
+                signals.push((quote!(crate::i2c::#signal), i2c.to_string(), None));
+            }
+        }
+        return signals;
+    }
+
+    // `PAxx_TIM`: any GPTIM or ATIM signal, routed by HPSYS_CFG.
+    if function.ends_with("_TIM") {
+        for tim in ["GPTIM1", "GPTIM2", "ATIM1"] {
+            for ch in ["Ch1", "Ch2", "Ch3", "Ch4"] {
+                signals.push((quote!(crate::timer::ChannelPin), tim.to_string(), Some(ch.to_string())));
+            }
+            signals.push((quote!(crate::timer::ExternalTriggerPin), tim.to_string(), None));
+        }
+        for ch in ["Ch1", "Ch2", "Ch3"] {
+            signals.push((quote!(crate::timer::ComplementaryChannelPin), "ATIM1".to_string(), Some(ch.to_string())));
+        }
+        signals.push((quote!(crate::timer::BreakInputPin), "ATIM1".to_string(), None));
+        signals.push((quote!(crate::timer::BreakInput2Pin), "ATIM1".to_string(), None));
+        return signals;
+    }
+
+    // Dedicated SPI pads.
+    if let Some((spi, signal)) = function.split_once('_') {
+        if spi.starts_with("SPI") {
+            let signal = match signal {
+                "CLK" => "SckPin",
+                "DIO" => "MosiPin",
+                "DI" => "MisoPin",
+                "CS" => "CsPin",
+                _ => panic!("Unknown SPI signal {}", function),
+            };
+            let signal = format_ident!("{}", signal);
+            signals.push((quote!(crate::spi::#signal), spi.to_string(), None));
+        }
+    }
+
+    signals
+}
+
+fn generate_pin_functions(pinmux: &Pinmux) -> TokenStream {
+    let mut functions: Vec<String> = Vec::new();
+    let mut fsel_arms = TokenStream::new();
+    let mut pin_trait_impls = TokenStream::new();
+    let mut i2c_uart_arms = TokenStream::new();
+    let mut tim_arms = TokenStream::new();
+
+    for pad in &pinmux.hcpu {
+        let pin_num: u8 = pad.pin
+            .strip_prefix("GPIO_A")
+            .and_then(|n| n.parse().ok())
+            .expect(&format!("Unknown pin name {}", pad.pin));
+        let pin_ident = format_ident!("PA{}", pin_num);
+
+        for function in &pad.functions {
+            // Function 0 is GPIO on every pad, see `Function::Gpio`.
+            if function.value == 0 {
+                continue;
+            }
+            if !functions.contains(&function.function) {
+                functions.push(function.function.clone());
+            }
+            let function_ident = format_ident!("{}", function.function);
+            let fsel = function.value;
+            fsel_arms.extend(quote! {
+                (Function::#function_ident, #pin_num) => Some(#fsel),
+            });
+            if function.function.ends_with("_I2C_UART") {
+                i2c_uart_arms.extend(quote!(#pin_num => Some(#fsel),));
+            } else if function.function.ends_with("_TIM") {
+                tim_arms.extend(quote!(#pin_num => Some(#fsel),));
+            }
+
+            // Analog pads: the GPADC channel is fixed by the pad.
+            if let Some(channel) = function.function.strip_prefix("GPADC_CH") {
+                let channel: u8 = channel.parse().expect(&format!("Unknown GPADC channel {}", function.function));
+                pin_trait_impls.extend(quote! {
+                    impl crate::adc::SealedAdcPin for crate::peripherals::#pin_ident {
+                        fn channel(&self) -> u8 {
+                            #channel
+                        }
+
+                        fn fsel(&self) -> u8 {
+                            #fsel
+                        }
+                    }
+                    impl crate::adc::AdcPin for crate::peripherals::#pin_ident {}
+                });
+                continue;
+            }
+
+            for (signal, instance, channel) in pin_signals(&function.function) {
+                let instance = format_ident!("{}", instance);
+                pin_trait_impls.extend(match channel {
+                    Some(channel) => {
+                        let channel = format_ident!("{}", channel);
+                        quote!(pin_trait_impl!(#signal, #instance, #channel, #pin_ident, #fsel);)
+                    }
+                    None => quote!(pin_trait_impl!(#signal, #instance, #pin_ident, #fsel);),
+                });
+            }
+        }
+    }
+
+    functions.sort();
+    let function_idents: Vec<_> = functions.iter().map(|f| format_ident!("{}", f)).collect();
+
+    quote! {
+        /// Pad function, see [`Flex::set_function`](crate::gpio::Flex::set_function).
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub enum Function {
+            Gpio,
+            #(#function_idents,)*
+        }
+
+        impl Function {
+            /// The pinmux `fsel` value selecting this function on `pin`,
+            /// `None` if the pad can't carry it.
+            pub(crate) fn fsel(self, pin: u8) -> Option<u8> {
+                match (self, pin) {
+                    (Function::Gpio, _) => Some(0),
+                    #fsel_arms
+                    _ => None,
+                }
+            }
+        }
+
+        /// fsel of the `PAxx_I2C_UART` function of `pin`.
+        pub(crate) fn i2c_uart_fsel(pin: u8) -> Option<u8> {
+            match pin {
+                #i2c_uart_arms
+                _ => None,
+            }
+        }
+
+        /// fsel of the `PAxx_TIM` function of `pin`.
+        pub(crate) fn tim_fsel(pin: u8) -> Option<u8> {
+            match pin {
+                #tim_arms
+                _ => None,
+            }
+        }
+
+        #pin_trait_impls
+    }
+}
+
 fn find_field_in_registers<'a>(
     registers: &[(&str, &'a FieldSet)],
     field_name: &str,
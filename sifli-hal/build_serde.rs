@@ -109,6 +109,23 @@ pub struct Peripheral {
     // pub interrupts: BTreeMap<String, String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pinmux {
+    pub hcpu: Vec<PinmuxPin>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PinmuxPin {
+    pub pin: String,
+    pub functions: Vec<PinFunction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PinFunction {
+    pub function: String,
+    pub value: u8,
+}
+
 fn default_32() -> u32 {
     32
 }
@@ -0,0 +1,496 @@
+//! General Purpose ADC (GPADC)
+//!
+//! The GPADC is a 12-bit SAR ADC with 8 conversion slots. Each slot converts
+//! one channel; a conversion runs all enabled slots in order, which is used
+//! for multi-channel scans. Channels 0..=6 are the analog functions of PA28..=PA34.
+//!
+//! Conversions can be started by software, or by the trigger output (TRGO) of
+//! a timer for a fixed sample rate, see [`Trigger`].
+//!
+//! Raw results are converted to millivolts with the factory calibration from
+//! the eFuses, see [`Adc::to_millivolts`].
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::dma::{self, ReadableRingBuffer};
+use crate::efuse::AdcCalibration;
+use crate::gpio::{Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::mode::{Async, Blocking, Mode};
+use crate::rcc::SealedRccEnableReset;
+use crate::{interrupt, pac, peripherals};
+
+pub use crate::dma::OverrunError;
+
+/// Number of conversion slots, i.e. the maximum number of channels of a scan.
+pub const SLOT_COUNT: usize = 8;
+
+/// Largest raw conversion result.
+pub const MAX_VALUE: u16 = 0x0FFF;
+
+/// DMA request of the GPADC.
+const DMA_REQUEST: dma::Request = 38;
+
+/// Nominal calibration, used when the eFuses hold none: 3.3V full scale.
+const NOMINAL_CALIBRATION: AdcCalibration = match AdcCalibration::new(1241, 3102) {
+    Some(calibration) => calibration,
+    None => panic!("invalid nominal calibration"),
+};
+
+/// ADC error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// More channels than [`SLOT_COUNT`], or no channel at all.
+    InvalidChannelCount,
+    /// The result buffer doesn't match the number of channels.
+    BufferSize,
+}
+
+/// Timer whose trigger output starts the conversions.
+///
+/// Configure the timer frequency and
+/// [`set_master_mode(MasterMode::Update)`](crate::timer::low_level::Timer::set_master_mode)
+/// for one scan per timer period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimerTrigger {
+    Atim1 = 0,
+    Gptim1 = 1,
+    Gptim2 = 2,
+    Btim1 = 3,
+    Btim2 = 4,
+}
+
+/// Conversion trigger of a continuous conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Trigger {
+    /// Back-to-back conversions, as fast as the ADC goes.
+    Continuous,
+    /// One scan per trigger output pulse of a timer.
+    Timer(TimerTrigger),
+}
+
+/// Number of conversions averaged into each result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Averaging {
+    None = 0,
+    Samples2 = 1,
+    Samples4 = 2,
+    Samples8 = 3,
+}
+
+/// ADC config.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Sample time, in `clk_peri` cycles.
+    pub sample_cycles: u8,
+    /// Averaging of each slot.
+    pub averaging: Averaging,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sample_cycles: 0x71,
+            averaging: Averaging::None,
+        }
+    }
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// GPADC interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::GPADC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = pac::GPADC;
+        if r.gpadc_irq().read().gpadc_irsr() {
+            // Masked until the next conversion, the result is read by the task.
+            r.gpadc_irq().modify(|w| w.set_gpadc_imr(true));
+            WAKER.wake();
+        }
+    }
+}
+
+/// ADC channel on an analog pin.
+pub struct AnyAdcChannel<'d> {
+    channel: u8,
+    _pin: Flex<'d>,
+}
+
+impl<'d> AnyAdcChannel<'d> {
+    /// Use `pin` as an ADC channel.
+    pub fn new(pin: impl Peripheral<P = impl AdcPin> + 'd) -> Self {
+        into_ref!(pin);
+        let (channel, fsel) = (pin.channel(), pin.fsel());
+
+        let mut pin = Flex::new(pin.map_into());
+        pin.set_pull(Pull::None);
+        unsafe { pin.set_fsel_unchecked(fsel) };
+        Self { channel, _pin: pin }
+    }
+
+    /// GPADC channel number.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+}
+
+/// ADC driver.
+pub struct Adc<'d, M: Mode> {
+    _peri: PeripheralRef<'d, peripherals::GPADC>,
+    calibration: AdcCalibration,
+    _phantom: PhantomData<M>,
+}
+
+impl<'d> Adc<'d, Blocking> {
+    /// Create a new blocking ADC driver.
+    pub fn new_blocking(peri: impl Peripheral<P = peripherals::GPADC> + 'd, config: Config) -> Self {
+        Self::new_inner(peri, config)
+    }
+}
+
+impl<'d> Adc<'d, Async> {
+    /// Create a new async ADC driver.
+    pub fn new(
+        peri: impl Peripheral<P = peripherals::GPADC> + 'd,
+        _irq: impl Binding<interrupt::typelevel::GPADC, InterruptHandler> + 'd,
+        config: Config,
+    ) -> Self {
+        let this = Self::new_inner(peri, config);
+
+        interrupt::typelevel::GPADC::unpend();
+        unsafe { interrupt::typelevel::GPADC::enable() };
+
+        this
+    }
+
+    /// Convert `channel` and return the raw result.
+    pub async fn read(&mut self, channel: &mut AnyAdcChannel<'_>) -> u16 {
+        let mut result = [0];
+        self.setup_slots(&[channel.channel]);
+        self.convert().await;
+        self.read_results(&mut result);
+        result[0]
+    }
+
+    /// Convert all `channels` in one scan and return the raw results in `results`,
+    /// in the same order.
+    pub async fn read_scan(
+        &mut self,
+        channels: &mut [&mut AnyAdcChannel<'_>],
+        results: &mut [u16],
+    ) -> Result<(), Error> {
+        let slots = slot_channels(channels.iter().map(|c| c.channel), results.len())?;
+        self.setup_slots(&slots[..channels.len()]);
+        self.convert().await;
+        self.read_results(results);
+        Ok(())
+    }
+
+    /// Convert `channel` and return the result in millivolts.
+    pub async fn read_millivolts(&mut self, channel: &mut AnyAdcChannel<'_>) -> i32 {
+        let raw = self.read(channel).await;
+        self.to_millivolts(raw)
+    }
+
+    async fn convert(&mut self) {
+        let r = pac::GPADC;
+        r.gpadc_irq().write(|w| w.set_gpadc_icr(true));
+        // Unmask
+        r.gpadc_irq().modify(|w| w.set_gpadc_imr(false));
+        r.ctrl_reg().modify(|w| w.set_adc_start(true));
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.gpadc_irq().read().gpadc_irsr() {
+                r.gpadc_irq().write(|w| w.set_gpadc_icr(true));
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
+    /// Start converting `channels` continuously, storing the raw results in
+    /// `buffer` through DMA.
+    ///
+    /// The results of a scan are stored consecutively, in the order of `channels`;
+    /// use a `buffer` length that is a multiple of the number of channels.
+    pub fn into_ring_buffered(
+        self,
+        dma: impl Peripheral<P = impl dma::Channel> + 'd,
+        buffer: &'d mut [u16],
+        channels: &mut [&mut AnyAdcChannel<'_>],
+        trigger: Trigger,
+    ) -> Result<RingBufferedAdc<'d>, Error> {
+        let slots = slot_channels(channels.iter().map(|c| c.channel), channels.len())?;
+        self.setup_slots(&slots[..channels.len()]);
+
+        let r = pac::GPADC;
+        let ring_buf = unsafe {
+            ReadableRingBuffer::new(
+                dma,
+                DMA_REQUEST,
+                r.adc_dma_rdata().as_ptr() as *mut u16,
+                buffer,
+                Default::default(),
+            )
+        };
+
+        // The trigger is armed by `RingBufferedAdc::start`.
+        r.ctrl_reg().modify(|w| w.set_dma_en(true));
+
+        Ok(RingBufferedAdc {
+            ring_buf,
+            adc: self,
+            trigger,
+        })
+    }
+}
+
+impl<'d, M: Mode> Adc<'d, M> {
+    fn new_inner(peri: impl Peripheral<P = peripherals::GPADC> + 'd, config: Config) -> Self {
+        into_ref!(peri);
+        crate::rcc::enable_and_reset::<peripherals::GPADC>();
+
+        let calibration = match AdcCalibration::read() {
+            Ok(Some(calibration)) => calibration,
+            _ => {
+                warn!("GPADC not calibrated, using nominal values");
+                NOMINAL_CALIBRATION
+            }
+        };
+
+        let r = pac::GPADC;
+        r.cfg_reg1().modify(|w| {
+            // Single-ended, on the internal LDO reference
+            w.set_anau_gpadc_se(true);
+            w.set_anau_gpadc_ldoref_en(true);
+        });
+        r.ctrl_reg2().modify(|w| w.set_samp_width(config.sample_cycles));
+        for n in 0..SLOT_COUNT {
+            r.slot(n).modify(|w| w.set_acc_num(config.averaging as u8));
+        }
+        r.ctrl_reg().modify(|w| {
+            w.set_chnl_sel_frc_en(false);
+            w.set_adc_op_mode(false);
+            w.set_timer_trig_en(false);
+            w.set_dma_en(false);
+        });
+        // Masked until a conversion is awaited
+        r.gpadc_irq().write(|w| {
+            w.set_gpadc_imr(true);
+            w.set_gpadc_icr(true);
+        });
+
+        // Reference settling time
+        crate::blocking_delay_us(200);
+
+        Self {
+            _peri: peri,
+            calibration,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Enable one slot per channel, in order, and disable the others.
+    fn setup_slots(&self, channels: &[u8]) {
+        let r = pac::GPADC;
+        for n in 0..SLOT_COUNT {
+            r.slot(n).modify(|w| match channels.get(n) {
+                Some(&channel) => {
+                    w.set_pchnl_sel(channel);
+                    w.set_slot_en(true);
+                }
+                None => w.set_slot_en(false),
+            });
+        }
+    }
+
+    /// Read the results of the first `results.len()` slots.
+    fn read_results(&self, results: &mut [u16]) {
+        let r = pac::GPADC;
+        for (n, result) in results.iter_mut().enumerate() {
+            // Two slots per RDATA register
+            *result = r.rdata(n / 2).read().slot_rdata(n % 2) & MAX_VALUE;
+        }
+    }
+
+    fn blocking_convert(&mut self) {
+        let r = pac::GPADC;
+        r.gpadc_irq().write(|w| w.set_gpadc_icr(true));
+        r.ctrl_reg().modify(|w| w.set_adc_start(true));
+        while !r.gpadc_irq().read().gpadc_irsr() {}
+        r.gpadc_irq().write(|w| w.set_gpadc_icr(true));
+    }
+
+    /// Convert `channel` and return the raw result, blocking.
+    pub fn blocking_read(&mut self, channel: &mut AnyAdcChannel<'_>) -> u16 {
+        let mut result = [0];
+        self.setup_slots(&[channel.channel]);
+        self.blocking_convert();
+        self.read_results(&mut result);
+        result[0]
+    }
+
+    /// Convert all `channels` in one scan and return the raw results in `results`,
+    /// in the same order, blocking.
+    pub fn blocking_read_scan(
+        &mut self,
+        channels: &mut [&mut AnyAdcChannel<'_>],
+        results: &mut [u16],
+    ) -> Result<(), Error> {
+        let slots = slot_channels(channels.iter().map(|c| c.channel), results.len())?;
+        self.setup_slots(&slots[..channels.len()]);
+        self.blocking_convert();
+        self.read_results(results);
+        Ok(())
+    }
+
+    /// Convert `channel` and return the result in millivolts, blocking.
+    pub fn blocking_read_millivolts(&mut self, channel: &mut AnyAdcChannel<'_>) -> i32 {
+        let raw = self.blocking_read(channel);
+        self.to_millivolts(raw)
+    }
+
+    /// Convert a raw result to millivolts, using the eFuse calibration.
+    pub fn to_millivolts(&self, raw: u16) -> i32 {
+        self.calibration.to_millivolts(raw)
+    }
+
+    /// Calibration in use, the factory one or the nominal one if the chip
+    /// was not calibrated.
+    pub fn calibration(&self) -> AdcCalibration {
+        self.calibration
+    }
+}
+
+impl<'d, M: Mode> Drop for Adc<'d, M> {
+    fn drop(&mut self) {
+        let r = pac::GPADC;
+        r.ctrl_reg().modify(|w| {
+            w.set_adc_op_mode(false);
+            w.set_timer_trig_en(false);
+            w.set_dma_en(false);
+        });
+        r.cfg_reg1().modify(|w| w.set_anau_gpadc_ldoref_en(false));
+        peripherals::GPADC::rcc_disable();
+    }
+}
+
+/// Channel numbers of a scan, checking the counts.
+fn slot_channels(channels: impl ExactSizeIterator<Item = u8>, results: usize) -> Result<[u8; SLOT_COUNT], Error> {
+    if channels.len() == 0 || channels.len() > SLOT_COUNT {
+        return Err(Error::InvalidChannelCount);
+    }
+    if results != channels.len() {
+        return Err(Error::BufferSize);
+    }
+    let mut slots = [0; SLOT_COUNT];
+    for (slot, channel) in slots.iter_mut().zip(channels) {
+        *slot = channel;
+    }
+    Ok(slots)
+}
+
+/// Continuous ADC conversions into a DMA ring buffer, see [`Adc::into_ring_buffered`].
+pub struct RingBufferedAdc<'d> {
+    // Dropped first, the DMA must stop before the ADC clock is gated.
+    ring_buf: ReadableRingBuffer<'d, u16>,
+    adc: Adc<'d, Async>,
+    trigger: Trigger,
+}
+
+impl<'d> RingBufferedAdc<'d> {
+    /// Start the conversions, also after [`stop`](Self::stop).
+    pub fn start(&mut self) {
+        self.ring_buf.start();
+        let r = pac::GPADC;
+        r.ctrl_reg().modify(|w| match self.trigger {
+            Trigger::Continuous => w.set_adc_op_mode(true),
+            Trigger::Timer(timer) => {
+                w.set_timer_trig_src_sel(timer as u8);
+                w.set_timer_trig_en(true);
+            }
+        });
+        if self.trigger == Trigger::Continuous {
+            r.ctrl_reg().modify(|w| w.set_adc_start(true));
+        }
+    }
+
+    /// Stop the conversions and clear the buffer.
+    pub async fn stop(&mut self) {
+        pac::GPADC.ctrl_reg().modify(|w| {
+            w.set_adc_op_mode(false);
+            w.set_timer_trig_en(false);
+        });
+        self.ring_buf.stop().await;
+        self.ring_buf.clear();
+    }
+
+    /// Read raw results, waiting until `buf` is full.
+    ///
+    /// Returns [`OverrunError`] if the DMA overwrote results before they were read;
+    /// the buffer is cleared and the conversions go on.
+    pub async fn read(&mut self, buf: &mut [u16]) -> Result<usize, OverrunError> {
+        let result = self.ring_buf.read_exact(buf).await;
+        if result.is_err() {
+            self.ring_buf.clear();
+        }
+        result
+    }
+
+    /// Convert a raw result to millivolts, using the eFuse calibration.
+    pub fn to_millivolts(&self, raw: u16) -> i32 {
+        self.adc.to_millivolts(raw)
+    }
+}
+
+pub(crate) trait SealedAdcPin {
+    fn channel(&self) -> u8;
+    fn fsel(&self) -> u8;
+}
+
+/// Pin that can be used as a GPADC channel.
+#[allow(private_bounds)]
+pub trait AdcPin: SealedAdcPin + crate::gpio::Pin {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_in_channel_order() {
+        assert_eq!(slot_channels([3, 0, 6].into_iter(), 3), Ok([3, 0, 6, 0, 0, 0, 0, 0]));
+        assert_eq!(slot_channels([1; SLOT_COUNT].into_iter(), SLOT_COUNT), Ok([1; SLOT_COUNT]));
+    }
+
+    #[test]
+    fn slots_check_channel_count() {
+        assert_eq!(slot_channels([].into_iter(), 0), Err(Error::InvalidChannelCount));
+        assert_eq!(
+            slot_channels([0; SLOT_COUNT + 1].into_iter(), SLOT_COUNT + 1),
+            Err(Error::InvalidChannelCount)
+        );
+    }
+
+    #[test]
+    fn slots_check_result_count() {
+        assert_eq!(slot_channels([0, 1].into_iter(), 1), Err(Error::BufferSize));
+        assert_eq!(slot_channels([0, 1].into_iter(), 3), Err(Error::BufferSize));
+    }
+}
@@ -0,0 +1,635 @@
+//! Hardware AES engine (AES)
+//!
+//! The engine encrypts and decrypts with 128, 192 or 256-bit keys in ECB, CBC
+//! or CTR mode. It reads the input and writes the output with its own DMA, so
+//! buffers are processed without the CPU, in place or not.
+//!
+//! The IV of CBC and the counter of CTR are updated after every call, so a
+//! long message can be streamed in several calls, e.g. OTA chunks as they are
+//! received. ECB and CBC require whole 16-byte blocks; CTR accepts any length,
+//! but a partial block ends the stream.
+//!
+//! With the `cipher` feature, [`BlockCipher`] implements the `cipher` crate
+//! block cipher traits, for use with the RustCrypto mode crates.
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::dma::{cache_clean_invalidate, cache_invalidate};
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::mode::{Async, Blocking, Mode as PeriMode};
+use crate::rcc::SealedRccEnableReset;
+use crate::{interrupt, pac, peripherals};
+
+/// AES block size, in bytes.
+pub const BLOCK_SIZE: usize = 16;
+
+/// Largest length processed by a single DMA run, a multiple of [`BLOCK_SIZE`].
+const MAX_RUN_LEN: usize = 0xFFF0;
+
+/// AES error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The key is not 16, 24 or 32 bytes long.
+    InvalidKeyLength,
+    /// ECB and CBC need a length that is a multiple of [`BLOCK_SIZE`].
+    InvalidLength,
+    /// The input and output buffers have different lengths.
+    BufferSize,
+    /// No key was set.
+    NoKey,
+}
+
+/// Chaining mode, with its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Electronic codebook, every block on its own.
+    Ecb,
+    /// Cipher block chaining, `iv` is the IV for the next block.
+    Cbc { iv: [u8; BLOCK_SIZE] },
+    /// Counter mode, `counter` is the big-endian counter block for the next block.
+    Ctr { counter: [u8; BLOCK_SIZE] },
+}
+
+impl Mode {
+    /// `SETTING.AES_MODE` value.
+    fn bits(&self) -> u8 {
+        match self {
+            Mode::Ecb => 0,
+            Mode::Ctr { .. } => 1,
+            Mode::Cbc { .. } => 2,
+        }
+    }
+
+    fn iv(&self) -> [u8; BLOCK_SIZE] {
+        match self {
+            Mode::Ecb => [0; BLOCK_SIZE],
+            Mode::Cbc { iv } => *iv,
+            Mode::Ctr { counter } => *counter,
+        }
+    }
+
+    /// Update the state after `blocks` blocks, `last_in` and `last_out` being the last ones.
+    fn advance(&mut self, dir: Direction, last_in: [u8; BLOCK_SIZE], last_out: [u8; BLOCK_SIZE], blocks: usize) {
+        match self {
+            Mode::Ecb => {}
+            // The ciphertext chains into the next block.
+            Mode::Cbc { iv } => {
+                *iv = match dir {
+                    Direction::Encrypt => last_out,
+                    Direction::Decrypt => last_in,
+                }
+            }
+            Mode::Ctr { counter } => {
+                *counter = u128::from_be_bytes(*counter)
+                    .wrapping_add(blocks as u128)
+                    .to_be_bytes();
+            }
+        }
+    }
+
+    /// Split `len` into the whole blocks and the CTR tail.
+    fn split(&self, len: usize) -> Result<(usize, usize), Error> {
+        let tail = len % BLOCK_SIZE;
+        match self {
+            Mode::Ctr { .. } => Ok((len - tail, tail)),
+            _ if tail != 0 => Err(Error::InvalidLength),
+            _ => Ok((len, 0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// Block buffer for the CTR tail, aligned to the cache line.
+#[repr(C, align(32))]
+struct TailBuffer([u8; 32]);
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// AES interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::AES> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = pac::AES;
+        if r.irq().read().done_stat() {
+            // Masked until the next run, the flag is cleared by the task.
+            r.irq().modify(|w| w.set_done_mask(true));
+            WAKER.wake();
+        }
+    }
+}
+
+/// AES driver.
+pub struct Aes<'d, M: PeriMode> {
+    _peri: PeripheralRef<'d, peripherals::AES>,
+    has_key: bool,
+    _phantom: PhantomData<M>,
+}
+
+impl<'d> Aes<'d, Blocking> {
+    /// Create a new blocking AES driver.
+    pub fn new_blocking(peri: impl Peripheral<P = peripherals::AES> + 'd) -> Self {
+        Self::new_inner(peri)
+    }
+}
+
+impl<'d> Aes<'d, Async> {
+    /// Create a new async AES driver.
+    pub fn new(
+        peri: impl Peripheral<P = peripherals::AES> + 'd,
+        _irq: impl Binding<interrupt::typelevel::AES, InterruptHandler> + 'd,
+    ) -> Self {
+        let this = Self::new_inner(peri);
+
+        interrupt::typelevel::AES::unpend();
+        unsafe { interrupt::typelevel::AES::enable() };
+
+        this
+    }
+
+    /// Encrypt `input` into `output`.
+    pub async fn encrypt(&mut self, mode: &mut Mode, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        check_buffers(input, output)?;
+        self.run(Direction::Encrypt, mode, input.as_ptr(), output.as_mut_ptr(), input.len())
+            .await
+    }
+
+    /// Decrypt `input` into `output`.
+    pub async fn decrypt(&mut self, mode: &mut Mode, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        check_buffers(input, output)?;
+        self.run(Direction::Decrypt, mode, input.as_ptr(), output.as_mut_ptr(), input.len())
+            .await
+    }
+
+    /// Encrypt `buf` in place.
+    pub async fn encrypt_in_place(&mut self, mode: &mut Mode, buf: &mut [u8]) -> Result<(), Error> {
+        self.run(Direction::Encrypt, mode, buf.as_ptr(), buf.as_mut_ptr(), buf.len())
+            .await
+    }
+
+    /// Decrypt `buf` in place.
+    pub async fn decrypt_in_place(&mut self, mode: &mut Mode, buf: &mut [u8]) -> Result<(), Error> {
+        self.run(Direction::Decrypt, mode, buf.as_ptr(), buf.as_mut_ptr(), buf.len())
+            .await
+    }
+
+    async fn run(
+        &mut self,
+        dir: Direction,
+        mode: &mut Mode,
+        src: *const u8,
+        dst: *mut u8,
+        len: usize,
+    ) -> Result<(), Error> {
+        let (body, tail) = self.check(mode, len)?;
+
+        // Stop the engine if the future is dropped before it completes, the
+        // DMA must not keep writing to the caller's buffers or the tail buffer.
+        let on_drop = OnDrop::new(abort);
+
+        let mut offset = 0;
+        while offset < body {
+            let n = (body - offset).min(MAX_RUN_LEN);
+            let (src, dst) = unsafe { (src.add(offset), dst.add(offset)) };
+            // Read before an in-place run overwrites it.
+            let last_in = unsafe { last_block(src, n) };
+
+            self.start(dir, mode, src, dst, n, true);
+            wait_done().await;
+            self.finish(dst, n);
+
+            mode.advance(dir, last_in, unsafe { last_block(dst, n) }, n / BLOCK_SIZE);
+            offset += n;
+        }
+
+        if tail > 0 {
+            let mut buf = TailBuffer([0; 32]);
+            unsafe { core::ptr::copy(src.add(body), buf.0.as_mut_ptr(), tail) };
+            self.start(dir, mode, buf.0.as_ptr(), buf.0.as_mut_ptr(), BLOCK_SIZE, true);
+            wait_done().await;
+            self.finish(buf.0.as_mut_ptr(), BLOCK_SIZE);
+            unsafe { core::ptr::copy(buf.0.as_ptr(), dst.add(body), tail) };
+            mode.advance(dir, [0; BLOCK_SIZE], [0; BLOCK_SIZE], 1);
+        }
+        on_drop.defuse();
+        Ok(())
+    }
+}
+
+impl<'d, M: PeriMode> Aes<'d, M> {
+    fn new_inner(peri: impl Peripheral<P = peripherals::AES> + 'd) -> Self {
+        into_ref!(peri);
+        crate::rcc::enable_and_reset::<peripherals::AES>();
+
+        // Masked until a run is awaited
+        pac::AES.irq().write(|w| {
+            w.set_done_mask(true);
+            w.set_done_clr(true);
+        });
+
+        Self {
+            _peri: peri,
+            has_key: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set the key, 16, 24 or 32 bytes for AES-128, AES-192 or AES-256.
+    pub fn set_key(&mut self, key: &[u8]) -> Result<(), Error> {
+        let length = match key.len() {
+            16 => 0,
+            24 => 1,
+            32 => 2,
+            _ => return Err(Error::InvalidKeyLength),
+        };
+
+        let r = pac::AES;
+        for (n, word) in key.chunks_exact(4).enumerate() {
+            r.ext_key(n).write(|w| w.set_val(u32::from_be_bytes([word[0], word[1], word[2], word[3]])));
+        }
+        r.setting().modify(|w| {
+            w.set_aes_length(length);
+            w.set_aes_use_efuse_key(false);
+        });
+        self.has_key = true;
+        Ok(())
+    }
+
+    /// Encrypt `input` into `output`, blocking.
+    pub fn blocking_encrypt(&mut self, mode: &mut Mode, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        check_buffers(input, output)?;
+        self.blocking_run(Direction::Encrypt, mode, input.as_ptr(), output.as_mut_ptr(), input.len())
+    }
+
+    /// Decrypt `input` into `output`, blocking.
+    pub fn blocking_decrypt(&mut self, mode: &mut Mode, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        check_buffers(input, output)?;
+        self.blocking_run(Direction::Decrypt, mode, input.as_ptr(), output.as_mut_ptr(), input.len())
+    }
+
+    /// Encrypt `buf` in place, blocking.
+    pub fn blocking_encrypt_in_place(&mut self, mode: &mut Mode, buf: &mut [u8]) -> Result<(), Error> {
+        self.blocking_run(Direction::Encrypt, mode, buf.as_ptr(), buf.as_mut_ptr(), buf.len())
+    }
+
+    /// Decrypt `buf` in place, blocking.
+    pub fn blocking_decrypt_in_place(&mut self, mode: &mut Mode, buf: &mut [u8]) -> Result<(), Error> {
+        self.blocking_run(Direction::Decrypt, mode, buf.as_ptr(), buf.as_mut_ptr(), buf.len())
+    }
+
+    fn blocking_run(
+        &mut self,
+        dir: Direction,
+        mode: &mut Mode,
+        src: *const u8,
+        dst: *mut u8,
+        len: usize,
+    ) -> Result<(), Error> {
+        let (body, tail) = self.check(mode, len)?;
+
+        let mut offset = 0;
+        while offset < body {
+            let n = (body - offset).min(MAX_RUN_LEN);
+            let (src, dst) = unsafe { (src.add(offset), dst.add(offset)) };
+            // Read before an in-place run overwrites it.
+            let last_in = unsafe { last_block(src, n) };
+
+            self.start(dir, mode, src, dst, n, false);
+            blocking_wait_done();
+            self.finish(dst, n);
+
+            mode.advance(dir, last_in, unsafe { last_block(dst, n) }, n / BLOCK_SIZE);
+            offset += n;
+        }
+
+        if tail > 0 {
+            let mut buf = TailBuffer([0; 32]);
+            unsafe { core::ptr::copy(src.add(body), buf.0.as_mut_ptr(), tail) };
+            self.start(dir, mode, buf.0.as_ptr(), buf.0.as_mut_ptr(), BLOCK_SIZE, false);
+            blocking_wait_done();
+            self.finish(buf.0.as_mut_ptr(), BLOCK_SIZE);
+            unsafe { core::ptr::copy(buf.0.as_ptr(), dst.add(body), tail) };
+            mode.advance(dir, [0; BLOCK_SIZE], [0; BLOCK_SIZE], 1);
+        }
+        Ok(())
+    }
+
+    fn check(&self, mode: &Mode, len: usize) -> Result<(usize, usize), Error> {
+        if !self.has_key {
+            return Err(Error::NoKey);
+        }
+        mode.split(len)
+    }
+
+    /// Program a DMA run of `len` bytes and start it.
+    fn start(&self, dir: Direction, mode: &Mode, src: *const u8, dst: *mut u8, len: usize, irq: bool) {
+        let r = pac::AES;
+
+        cache_clean_invalidate(core::ptr::slice_from_raw_parts(src, len));
+        cache_clean_invalidate(core::ptr::slice_from_raw_parts(dst as *const u8, len));
+
+        r.setting().modify(|w| {
+            w.set_aes_mode(mode.bits());
+            // CTR decrypts by encrypting the counter.
+            w.set_aes_op_mode(dir == Direction::Decrypt && !matches!(mode, Mode::Ctr { .. }));
+        });
+        let iv = mode.iv();
+        for (n, word) in iv.chunks_exact(4).enumerate() {
+            r.aes_iv(n).write(|w| w.set_val(u32::from_be_bytes([word[0], word[1], word[2], word[3]])));
+        }
+
+        r.dma_in().write(|w| w.set_addr(src as u32));
+        r.dma_out().write(|w| w.set_addr(dst as u32));
+        r.dma_data().write(|w| w.set_size(len as u32));
+
+        r.irq().write(|w| w.set_done_clr(true));
+        r.irq().modify(|w| w.set_done_mask(!irq));
+        r.command().write(|w| w.set_start(true));
+    }
+
+    /// Make the output of a finished run visible to the CPU.
+    fn finish(&self, dst: *mut u8, len: usize) {
+        cache_invalidate(core::ptr::slice_from_raw_parts_mut(dst, len));
+    }
+}
+
+impl<'d, M: PeriMode> Drop for Aes<'d, M> {
+    fn drop(&mut self) {
+        let r = pac::AES;
+        // Don't leave the key behind.
+        for n in 0..8 {
+            r.ext_key(n).write(|w| w.set_val(0));
+        }
+        interrupt::typelevel::AES::disable();
+        peripherals::AES::rcc_disable();
+    }
+}
+
+fn check_buffers(input: &[u8], output: &[u8]) -> Result<(), Error> {
+    if input.len() == output.len() {
+        Ok(())
+    } else {
+        Err(Error::BufferSize)
+    }
+}
+
+/// Last block of the `len` bytes at `p`.
+unsafe fn last_block(p: *const u8, len: usize) -> [u8; BLOCK_SIZE] {
+    let mut block = [0; BLOCK_SIZE];
+    core::ptr::copy(p.add(len - BLOCK_SIZE), block.as_mut_ptr(), BLOCK_SIZE);
+    block
+}
+
+/// Stop a run in progress and wait for the engine to be idle.
+///
+/// The key and setting registers are kept.
+fn abort() {
+    let r = pac::AES;
+    r.irq().modify(|w| w.set_done_mask(true));
+    r.command().write(|w| w.set_aes_acc_reset(true));
+    while r.status().read().busy() {}
+    r.irq().write(|w| w.set_done_clr(true));
+}
+
+fn blocking_wait_done() {
+    let r = pac::AES;
+    while !r.irq().read().done_stat() {}
+    r.irq().write(|w| w.set_done_clr(true));
+}
+
+async fn wait_done() {
+    let r = pac::AES;
+    poll_fn(|cx| {
+        WAKER.register(cx.waker());
+        if r.irq().read().done_stat() {
+            r.irq().write(|w| w.set_done_clr(true));
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+}
+
+#[cfg(feature = "cipher")]
+pub use self::block_cipher::BlockCipher;
+
+#[cfg(feature = "cipher")]
+mod block_cipher {
+    use core::cell::RefCell;
+
+    use cipher::consts::{U1, U16};
+    use cipher::inout::InOut;
+    use cipher::{Block, BlockBackend, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser, ParBlocksSizeUser};
+
+    use super::*;
+
+    /// The AES engine as a `cipher` block cipher, one block per run in ECB mode.
+    ///
+    /// Chaining is left to the RustCrypto mode crates (`cbc`, `ctr`, ...). For
+    /// long messages, the DMA modes of [`Aes`] are much faster.
+    pub struct BlockCipher<'a, 'd, M: PeriMode> {
+        aes: RefCell<&'a mut Aes<'d, M>>,
+    }
+
+    impl<'a, 'd, M: PeriMode> BlockCipher<'a, 'd, M> {
+        /// Use `aes`, with the key set by [`Aes::set_key`].
+        pub fn new(aes: &'a mut Aes<'d, M>) -> Result<Self, Error> {
+            if !aes.has_key {
+                return Err(Error::NoKey);
+            }
+            Ok(Self { aes: RefCell::new(aes) })
+        }
+    }
+
+    impl<'a, 'd, M: PeriMode> BlockSizeUser for BlockCipher<'a, 'd, M> {
+        type BlockSize = U16;
+    }
+
+    impl<'a, 'd, M: PeriMode> cipher::BlockCipher for BlockCipher<'a, 'd, M> {}
+
+    impl<'a, 'd, M: PeriMode> BlockEncrypt for BlockCipher<'a, 'd, M> {
+        fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+            let mut aes = self.aes.borrow_mut();
+            f.call(&mut Backend {
+                aes: &mut aes,
+                dir: Direction::Encrypt,
+            });
+        }
+    }
+
+    impl<'a, 'd, M: PeriMode> BlockDecrypt for BlockCipher<'a, 'd, M> {
+        fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+            let mut aes = self.aes.borrow_mut();
+            f.call(&mut Backend {
+                aes: &mut aes,
+                dir: Direction::Decrypt,
+            });
+        }
+    }
+
+    struct Backend<'b, 'd, M: PeriMode> {
+        aes: &'b mut Aes<'d, M>,
+        dir: Direction,
+    }
+
+    impl<'b, 'd, M: PeriMode> BlockSizeUser for Backend<'b, 'd, M> {
+        type BlockSize = U16;
+    }
+
+    impl<'b, 'd, M: PeriMode> ParBlocksSizeUser for Backend<'b, 'd, M> {
+        type ParBlocksSize = U1;
+    }
+
+    impl<'b, 'd, M: PeriMode> BlockBackend for Backend<'b, 'd, M> {
+        fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+            let mut buf = TailBuffer([0; 32]);
+            buf.0[..BLOCK_SIZE].copy_from_slice(block.get_in());
+            let p = buf.0.as_mut_ptr();
+            // The key was checked in `BlockCipher::new`.
+            unwrap!(self.aes.blocking_run(self.dir, &mut Mode::Ecb, p, p, BLOCK_SIZE));
+            block.get_out().copy_from_slice(&buf.0[..BLOCK_SIZE]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NIST SP 800-38A, F.1.1, F.2.1 and F.5.1 (AES-128).
+    const PLAINTEXT: [[u8; BLOCK_SIZE]; 4] = [
+        hex("6bc1bee22e409f96e93d7e117393172a"),
+        hex("ae2d8a571e03ac9c9eb76fac45af8e51"),
+        hex("30c81c46a35ce411e5fbc1191a0a52ef"),
+        hex("f69f2445df4f9b17ad2b417be66c3710"),
+    ];
+    const ECB_CIPHERTEXT: [[u8; BLOCK_SIZE]; 4] = [
+        hex("3ad77bb40d7a3660a89ecaf32466ef97"),
+        hex("f5d3d58503b9699de785895a96fdbaaf"),
+        hex("43b1cd7f598ece23881b00e3ed030688"),
+        hex("7b0c785e27e8ad3f8223207104725dd4"),
+    ];
+    const CBC_IV: [u8; BLOCK_SIZE] = hex("000102030405060708090a0b0c0d0e0f");
+    const CBC_CIPHERTEXT: [[u8; BLOCK_SIZE]; 4] = [
+        hex("7649abac8119b246cee98e9b12e9197d"),
+        hex("5086cb9b507219ee95db113a917678b2"),
+        hex("73bed6b8e3c1743b7116e69e22229516"),
+        hex("3ff1caa1681fac09120eca307586e1a7"),
+    ];
+    const CTR_COUNTERS: [[u8; BLOCK_SIZE]; 4] = [
+        hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"),
+        hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdff00"),
+        hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdff01"),
+        hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdff02"),
+    ];
+    const CTR_CIPHERTEXT: [[u8; BLOCK_SIZE]; 4] = [
+        hex("874d6191b620e3261bef6864990db6ce"),
+        hex("9806f66b7970fdff8617187bb9fffdff"),
+        hex("5ae4df3edbd5d35e5b4f09020db03eab"),
+        hex("1e031dda2fbe03d1792170a0f3009cee"),
+    ];
+
+    const fn hex(s: &str) -> [u8; BLOCK_SIZE] {
+        const fn nibble(c: u8) -> u8 {
+            match c {
+                b'0'..=b'9' => c - b'0',
+                _ => c - b'a' + 10,
+            }
+        }
+        let s = s.as_bytes();
+        let mut out = [0; BLOCK_SIZE];
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            out[i] = nibble(s[2 * i]) << 4 | nibble(s[2 * i + 1]);
+            i += 1;
+        }
+        out
+    }
+
+    #[test]
+    fn split_ecb_cbc_need_whole_blocks() {
+        for mode in [Mode::Ecb, Mode::Cbc { iv: CBC_IV }] {
+            assert_eq!(mode.split(0), Ok((0, 0)));
+            assert_eq!(mode.split(64), Ok((64, 0)));
+            assert_eq!(mode.split(65), Err(Error::InvalidLength));
+            assert_eq!(mode.split(15), Err(Error::InvalidLength));
+        }
+    }
+
+    #[test]
+    fn split_ctr_keeps_tail() {
+        let mode = Mode::Ctr { counter: CTR_COUNTERS[0] };
+        assert_eq!(mode.split(64), Ok((64, 0)));
+        assert_eq!(mode.split(60), Ok((48, 12)));
+        assert_eq!(mode.split(5), Ok((0, 5)));
+    }
+
+    #[test]
+    fn ecb_has_no_state() {
+        let mut mode = Mode::Ecb;
+        mode.advance(Direction::Encrypt, PLAINTEXT[0], ECB_CIPHERTEXT[0], 1);
+        assert_eq!(mode, Mode::Ecb);
+        assert_eq!(mode.iv(), [0; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn cbc_encrypt_chains_ciphertext() {
+        let mut mode = Mode::Cbc { iv: CBC_IV };
+        // One block per call, then the rest in one call.
+        mode.advance(Direction::Encrypt, PLAINTEXT[0], CBC_CIPHERTEXT[0], 1);
+        assert_eq!(mode.iv(), CBC_CIPHERTEXT[0]);
+        mode.advance(Direction::Encrypt, PLAINTEXT[3], CBC_CIPHERTEXT[3], 3);
+        assert_eq!(mode.iv(), CBC_CIPHERTEXT[3]);
+    }
+
+    #[test]
+    fn cbc_decrypt_chains_ciphertext() {
+        let mut mode = Mode::Cbc { iv: CBC_IV };
+        mode.advance(Direction::Decrypt, CBC_CIPHERTEXT[1], PLAINTEXT[1], 2);
+        assert_eq!(mode.iv(), CBC_CIPHERTEXT[1]);
+        mode.advance(Direction::Decrypt, CBC_CIPHERTEXT[2], PLAINTEXT[2], 1);
+        assert_eq!(mode.iv(), CBC_CIPHERTEXT[2]);
+    }
+
+    #[test]
+    fn ctr_counter_increments_per_block() {
+        for dir in [Direction::Encrypt, Direction::Decrypt] {
+            let mut mode = Mode::Ctr { counter: CTR_COUNTERS[0] };
+            mode.advance(dir, PLAINTEXT[0], CTR_CIPHERTEXT[0], 1);
+            assert_eq!(mode.iv(), CTR_COUNTERS[1]);
+            mode.advance(dir, PLAINTEXT[2], CTR_CIPHERTEXT[2], 2);
+            assert_eq!(mode.iv(), CTR_COUNTERS[3]);
+        }
+    }
+
+    #[test]
+    fn ctr_counter_wraps() {
+        let mut mode = Mode::Ctr { counter: [0xFF; BLOCK_SIZE] };
+        mode.advance(Direction::Encrypt, [0; BLOCK_SIZE], [0; BLOCK_SIZE], 2);
+        let mut expected = [0; BLOCK_SIZE];
+        expected[BLOCK_SIZE - 1] = 1;
+        assert_eq!(mode.iv(), expected);
+    }
+
+    #[test]
+    fn mode_bits() {
+        assert_eq!(Mode::Ecb.bits(), 0);
+        assert_eq!(Mode::Ctr { counter: CTR_COUNTERS[0] }.bits(), 1);
+        assert_eq!(Mode::Cbc { iv: CBC_IV }.bits(), 2);
+    }
+}
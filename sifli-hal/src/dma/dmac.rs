@@ -0,0 +1,466 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::{AnyChannel, Channel, Request, Word, WordSize, CHANNEL_COUNT};
+use crate::pac;
+
+/// DMA transfer direction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum Dir {
+    /// Memory to peripheral
+    MemoryToPeripheral,
+    /// Peripheral to memory
+    PeripheralToMemory,
+    /// Memory to memory, the "peripheral" address is the source.
+    MemoryToMemory,
+}
+
+/// DMA channel priority.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Priority {
+    /// Low priority.
+    Low,
+    /// Medium priority.
+    Medium,
+    /// High priority.
+    High,
+    /// Very high priority.
+    VeryHigh,
+}
+
+impl Priority {
+    fn bits(&self) -> u8 {
+        match self {
+            Priority::Low => 0b00,
+            Priority::Medium => 0b01,
+            Priority::High => 0b10,
+            Priority::VeryHigh => 0b11,
+        }
+    }
+}
+
+/// DMA transfer options.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TransferOptions {
+    /// Request priority level
+    pub priority: Priority,
+    /// Enable half transfer interrupt
+    pub half_transfer_ir: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            priority: Priority::VeryHigh,
+            half_transfer_ir: false,
+        }
+    }
+}
+
+/// Maximum number of data items of a single DMAC1 transfer (CNDTR is 16 bits).
+pub const MAX_TRANSFER_COUNT: usize = 0xFFFF;
+
+pub(crate) struct ChannelState {
+    pub(crate) waker: AtomicWaker,
+    /// Number of completed passes over the buffer, used by the ring buffers.
+    pub(crate) complete_count: AtomicUsize,
+}
+
+impl ChannelState {
+    const fn new() -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+            complete_count: AtomicUsize::new(0),
+        }
+    }
+}
+
+pub(crate) static STATE: [ChannelState; CHANNEL_COUNT] = [const { ChannelState::new() }; CHANNEL_COUNT];
+
+#[inline]
+fn regs() -> pac::dmac::Dmac {
+    pac::DMAC1
+}
+
+pub(crate) fn on_irq(id: usize) {
+    let r = regs();
+    let isr = r.isr().read();
+    let ccr = r.ch(id).ccr().read();
+
+    if isr.teif(id) && ccr.teie() {
+        panic!("DMA: error on DMAC1 channel {}", id + 1);
+    }
+
+    if isr.htif(id) && ccr.htie() {
+        // Acknowledge half transfer complete interrupt
+        r.ifcr().write(|w| w.set_chtif(id, true));
+    } else if isr.tcif(id) && ccr.tcie() {
+        // Acknowledge transfer complete interrupt
+        r.ifcr().write(|w| w.set_ctcif(id, true));
+        STATE[id].complete_count.fetch_add(1, Ordering::Release);
+    } else {
+        return;
+    }
+
+    STATE[id].waker.wake();
+}
+
+impl AnyChannel {
+    #[inline]
+    pub(crate) fn index(&self) -> usize {
+        self.id as usize
+    }
+
+    pub(crate) unsafe fn configure(
+        &self,
+        request: Request,
+        dir: Dir,
+        peri_addr: *const u32,
+        mem_addr: *mut u32,
+        mem_len: usize,
+        incr_mem: bool,
+        data_size: WordSize,
+        options: TransferOptions,
+        circular: bool,
+    ) {
+        assert!(mem_len > 0 && mem_len <= MAX_TRANSFER_COUNT);
+
+        let r = regs();
+        let id = self.index();
+
+        // "Preceding reads and writes cannot be moved past subsequent writes."
+        fence(Ordering::SeqCst);
+
+        let ch = r.ch(id);
+        ch.ccr().write(|_| {});
+        r.ifcr().write(|w| w.set_cgif(id, true));
+        STATE[id].complete_count.store(0, Ordering::Release);
+
+        // Four request selectors per CSELR register.
+        r.cselr(id / 4).modify(|w| w.set_cs(id % 4, request));
+
+        ch.cpar().write_value(peri_addr as u32);
+        ch.cm0ar().write_value(mem_addr as u32);
+        ch.cndtr().write(|w| w.set_ndt(mem_len as u16));
+
+        ch.ccr().write(|w| {
+            // DIR = 1 reads from memory (CM0AR) and writes to the peripheral (CPAR).
+            // For memory-to-memory, CPAR is the source and DIR stays 0.
+            w.set_dir(dir == Dir::MemoryToPeripheral);
+            w.set_mem2mem(dir == Dir::MemoryToMemory);
+            w.set_msize(data_size.bits());
+            w.set_psize(data_size.bits());
+            w.set_pl(options.priority.bits());
+            w.set_minc(incr_mem);
+            w.set_pinc(dir == Dir::MemoryToMemory);
+            w.set_teie(true);
+            w.set_htie(options.half_transfer_ir);
+            w.set_tcie(true);
+            w.set_circ(circular);
+            w.set_en(false); // don't start yet
+        });
+    }
+
+    pub(crate) fn start(&self) {
+        regs().ch(self.index()).ccr().modify(|w| w.set_en(true));
+    }
+
+    pub(crate) fn clear_irqs(&self) {
+        regs().ifcr().write(|w| w.set_cgif(self.index(), true));
+    }
+
+    pub(crate) fn request_stop(&self) {
+        // Disable the channel. Keep the IEs enabled so the irqs still fire.
+        regs().ch(self.index()).ccr().modify(|w| w.set_en(false));
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        let id = self.index();
+        let ccr = regs().ch(id).ccr().read();
+        // The interrupt handler acknowledges TCIF, so use the completion count instead.
+        let complete = STATE[id].complete_count.load(Ordering::Acquire) != 0;
+        ccr.en() && (ccr.circ() || !complete)
+    }
+
+    pub(crate) fn get_remaining_transfers(&self) -> u16 {
+        regs().ch(self.index()).cndtr().read().ndt()
+    }
+
+    /// Whether a transfer complete is flagged but not handled by the interrupt yet.
+    pub(crate) fn is_complete_pending(&self) -> bool {
+        regs().isr().read().tcif(self.index())
+    }
+
+    /// Returns the number of passes completed so far, which is restarted from 0.
+    pub(crate) fn disable_circular_mode(&self) -> usize {
+        // Restart the completion count, so `is_running` reports the end of the current pass.
+        let passes = STATE[self.index()].complete_count.swap(0, Ordering::AcqRel);
+        regs().ch(self.index()).ccr().modify(|w| w.set_circ(false));
+        passes
+    }
+
+    pub(crate) fn poll_stop(&self) -> Poll<()> {
+        if self.is_running() {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Clean (and invalidate) the data cache for `buf`, so that the DMA sees what the CPU
+/// wrote and the CPU does not later write back stale lines over data written by the DMA.
+///
+/// CPU writes to memory sharing a cache line with `buf` during the transfer may be lost.
+pub(crate) fn cache_clean_invalidate<W>(buf: *const [W]) {
+    let len = core::mem::size_of::<W>() * buf.len();
+    if len == 0 {
+        return;
+    }
+    unsafe {
+        let mut cp = cortex_m::Peripherals::steal();
+        cp.SCB.clean_invalidate_dcache_by_address(buf as *const W as usize, len);
+    }
+}
+
+/// Invalidate the data cache for `buf` after the DMA wrote to it.
+pub(crate) fn cache_invalidate<W>(buf: *mut [W]) {
+    let len = core::mem::size_of::<W>() * buf.len();
+    if len == 0 {
+        return;
+    }
+    unsafe {
+        let mut cp = cortex_m::Peripherals::steal();
+        cp.SCB.invalidate_dcache_by_address(buf as *mut W as usize, len);
+    }
+}
+
+/// DMA transfer.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Transfer<'a> {
+    channel: PeripheralRef<'a, AnyChannel>,
+    /// Memory written by the DMA, invalidated from the cache when the transfer ends.
+    dst: Option<(*mut u8, usize)>,
+}
+
+impl<'a> Transfer<'a> {
+    /// Create a new read DMA transfer (peripheral to memory).
+    pub unsafe fn new_read<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buf: &'a mut [W],
+        options: TransferOptions,
+    ) -> Self {
+        Self::new_read_raw(channel, request, peri_addr, buf, options)
+    }
+
+    /// Create a new read DMA transfer (peripheral to memory), using raw pointers.
+    pub unsafe fn new_read_raw<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buf: *mut [W],
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+        let channel: PeripheralRef<'a, AnyChannel> = channel.map_into();
+
+        cache_clean_invalidate(buf);
+        channel.configure(
+            request,
+            Dir::PeripheralToMemory,
+            peri_addr as *const u32,
+            buf as *mut W as *mut u32,
+            buf.len(),
+            true,
+            W::size(),
+            options,
+            false,
+        );
+        channel.start();
+
+        Self {
+            channel,
+            dst: Some((buf as *mut W as *mut u8, core::mem::size_of::<W>() * buf.len())),
+        }
+    }
+
+    /// Create a new write DMA transfer (memory to peripheral).
+    pub unsafe fn new_write<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        buf: &'a [W],
+        peri_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        Self::new_write_raw(channel, request, buf, peri_addr, options)
+    }
+
+    /// Create a new write DMA transfer (memory to peripheral), using raw pointers.
+    pub unsafe fn new_write_raw<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        buf: *const [W],
+        peri_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+        let channel: PeripheralRef<'a, AnyChannel> = channel.map_into();
+
+        cache_clean_invalidate(buf);
+        channel.configure(
+            request,
+            Dir::MemoryToPeripheral,
+            peri_addr as *const u32,
+            buf as *const W as *mut u32,
+            buf.len(),
+            true,
+            W::size(),
+            options,
+            false,
+        );
+        channel.start();
+
+        Self { channel, dst: None }
+    }
+
+    /// Create a new write DMA transfer (memory to peripheral), writing the same value repeatedly.
+    pub unsafe fn new_write_repeated<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        repeated: &'a W,
+        count: usize,
+        peri_addr: *mut W,
+        options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+        let channel: PeripheralRef<'a, AnyChannel> = channel.map_into();
+
+        cache_clean_invalidate(core::ptr::slice_from_raw_parts(repeated as *const W, 1));
+        channel.configure(
+            request,
+            Dir::MemoryToPeripheral,
+            peri_addr as *const u32,
+            repeated as *const W as *mut u32,
+            count,
+            false,
+            W::size(),
+            options,
+            false,
+        );
+        channel.start();
+
+        Self { channel, dst: None }
+    }
+
+    /// Create a new memory-to-memory DMA transfer, copying `src` into `dst`.
+    ///
+    /// `src` and `dst` must have the same length.
+    pub unsafe fn new_transfer<W: Word>(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        src: &'a [W],
+        dst: &'a mut [W],
+        options: TransferOptions,
+    ) -> Self {
+        assert_eq!(src.len(), dst.len());
+        into_ref!(channel);
+        let channel: PeripheralRef<'a, AnyChannel> = channel.map_into();
+
+        cache_clean_invalidate(src as *const [W]);
+        cache_clean_invalidate(dst as *const [W]);
+        channel.configure(
+            0,
+            Dir::MemoryToMemory,
+            src.as_ptr() as *const u32,
+            dst.as_mut_ptr() as *mut u32,
+            dst.len(),
+            true,
+            W::size(),
+            options,
+            false,
+        );
+        channel.start();
+
+        Self {
+            channel,
+            dst: Some((dst.as_mut_ptr() as *mut u8, core::mem::size_of_val(dst))),
+        }
+    }
+
+    /// Request the transfer to stop.
+    ///
+    /// This doesn't immediately stop the transfer, you have to wait until [`is_running`](Self::is_running) returns false.
+    pub fn request_stop(&mut self) {
+        self.channel.request_stop()
+    }
+
+    /// Return whether this transfer is still running.
+    ///
+    /// If this returns `false`, it can be because either the transfer finished, or
+    /// it was requested to stop early with [`request_stop`](Self::request_stop).
+    pub fn is_running(&mut self) -> bool {
+        self.channel.is_running()
+    }
+
+    /// Gets the total remaining transfers for the channel
+    /// Note: this will be zero for transfers that completed without cancellation.
+    pub fn get_remaining_transfers(&self) -> u16 {
+        self.channel.get_remaining_transfers()
+    }
+
+    /// Blocking wait until the transfer finishes.
+    pub fn blocking_wait(mut self) {
+        while self.is_running() {}
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+
+        self.finish();
+        core::mem::forget(self);
+    }
+
+    fn finish(&mut self) {
+        self.channel.request_stop();
+        self.channel.clear_irqs();
+        if let Some((ptr, len)) = self.dst.take() {
+            cache_invalidate(core::ptr::slice_from_raw_parts_mut(ptr, len));
+        }
+    }
+}
+
+impl<'a> Drop for Transfer<'a> {
+    fn drop(&mut self) {
+        self.request_stop();
+        while self.is_running() {}
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+
+        self.finish();
+    }
+}
+
+impl<'a> Unpin for Transfer<'a> {}
+impl<'a> Future for Transfer<'a> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let state = &STATE[self.channel.index()];
+        state.waker.register(cx.waker());
+
+        if self.channel.is_running() {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
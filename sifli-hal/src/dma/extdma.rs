@@ -0,0 +1,134 @@
+use core::future::poll_fn;
+use core::sync::atomic::{fence, AtomicBool, Ordering};
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::dmac::{cache_clean_invalidate, cache_invalidate};
+use crate::pac;
+use crate::peripherals;
+
+/// Maximum number of words of a single EXTDMA copy (CNDTR is 20 bits).
+pub const EXTDMA_MAX_TRANSFER_COUNT: usize = 0xF_FFFF;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+static DONE: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn on_irq() {
+    let r = pac::EXTDMA;
+    let isr = r.isr().read();
+    let ccr = r.ccr().read();
+
+    if isr.teif() && ccr.teie() {
+        panic!("DMA: error on EXTDMA");
+    }
+
+    if isr.tcif() && ccr.tcie() {
+        r.ifcr().write(|w| w.set_ctcif(true));
+        r.ccr().modify(|w| w.set_tcie(false));
+        DONE.store(true, Ordering::Release);
+        WAKER.wake();
+    }
+}
+
+/// EXTDMA memory-to-memory copy engine.
+///
+/// Copies word-aligned memory, e.g. framebuffers from PSRAM to SRAM, without
+/// occupying a DMAC1 channel.
+pub struct ExtDma<'d> {
+    _peri: PeripheralRef<'d, peripherals::EXTDMA>,
+}
+
+impl<'d> ExtDma<'d> {
+    /// Create a new EXTDMA driver.
+    pub fn new(peri: impl Peripheral<P = peripherals::EXTDMA> + 'd) -> Self {
+        into_ref!(peri);
+        Self { _peri: peri }
+    }
+
+    /// Copy `src` into `dst`, waiting for completion via the EXTDMA interrupt.
+    ///
+    /// `src` and `dst` must have the same length.
+    pub async fn copy(&mut self, src: &[u32], dst: &mut [u32]) {
+        // Stop the transfer if the future is dropped before it completes.
+        let on_drop = OnDrop::new(|| {
+            pac::EXTDMA.ccr().modify(|w| w.set_en(false));
+        });
+
+        self.start(src, dst, true);
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if DONE.load(Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        on_drop.defuse();
+        self.finish(dst);
+    }
+
+    /// Copy `src` into `dst`, busy waiting for completion.
+    ///
+    /// `src` and `dst` must have the same length.
+    pub fn blocking_copy(&mut self, src: &[u32], dst: &mut [u32]) {
+        // Without TCIE, the interrupt handler leaves TCIF for us to poll.
+        self.start(src, dst, false);
+        let r = pac::EXTDMA;
+        while !r.isr().read().tcif() {
+            if r.isr().read().teif() {
+                panic!("DMA: error on EXTDMA");
+            }
+        }
+        r.ifcr().write(|w| w.set_ctcif(true));
+        self.finish(dst);
+    }
+
+    fn start(&mut self, src: &[u32], dst: &mut [u32], irq: bool) {
+        assert_eq!(src.len(), dst.len());
+        assert!(!src.is_empty() && src.len() <= EXTDMA_MAX_TRANSFER_COUNT);
+
+        let r = pac::EXTDMA;
+        cache_clean_invalidate(src as *const [u32]);
+        cache_clean_invalidate(dst as *const [u32]);
+
+        // "Preceding reads and writes cannot be moved past subsequent writes."
+        fence(Ordering::SeqCst);
+
+        r.ccr().write(|_| {});
+        r.ifcr().write(|w| {
+            w.set_cgif(true);
+            w.set_ctcif(true);
+            w.set_chtif(true);
+            w.set_cteif(true);
+        });
+        DONE.store(false, Ordering::Release);
+
+        r.srcar().write_value(src.as_ptr() as u32);
+        r.dstar().write_value(dst.as_mut_ptr() as u32);
+        r.cndtr().write(|w| w.set_ndt(src.len() as u32));
+        r.ccr().write(|w| {
+            w.set_srcinc(true);
+            w.set_dstinc(true);
+            w.set_teie(true);
+            w.set_tcie(irq);
+        });
+        r.ccr().modify(|w| w.set_en(true));
+    }
+
+    fn finish(&mut self, dst: &mut [u32]) {
+        pac::EXTDMA.ccr().modify(|w| w.set_en(false));
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+        cache_invalidate(dst as *mut [u32]);
+    }
+}
+
+impl<'d> Drop for ExtDma<'d> {
+    fn drop(&mut self) {
+        pac::EXTDMA.ccr().modify(|w| w.set_en(false));
+    }
+}
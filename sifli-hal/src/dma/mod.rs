@@ -0,0 +1,217 @@
+//! Direct Memory Access (DMA)
+//!
+//! DMAC1 has 8 channels, each of which can serve any peripheral request.
+//! [`Transfer`] and [`ReadableRingBuffer`]/[`WritableRingBuffer`] are built on
+//! top of those channels. EXTDMA is a separate memory-to-memory copy engine,
+//! see [`ExtDma`].
+
+mod dmac;
+pub use dmac::*;
+
+mod ringbuffer;
+pub use ringbuffer::*;
+
+mod extdma;
+pub use extdma::*;
+
+use embassy_hal_internal::{impl_peripheral, Peripheral};
+
+use crate::interrupt;
+use crate::interrupt::InterruptExt;
+use crate::peripherals;
+
+/// DMA request line (DMAC1 `CSELR` value).
+pub type Request = u8;
+
+/// Number of DMAC1 channels.
+pub(crate) const CHANNEL_COUNT: usize = 8;
+
+/// Size of a single DMA data item.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WordSize {
+    /// Byte
+    OneByte,
+    /// Half-word
+    TwoBytes,
+    /// Word
+    FourBytes,
+}
+
+impl WordSize {
+    /// Amount of bytes of this word size.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::OneByte => 1,
+            Self::TwoBytes => 2,
+            Self::FourBytes => 4,
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self {
+            Self::OneByte => 0b00,
+            Self::TwoBytes => 0b01,
+            Self::FourBytes => 0b10,
+        }
+    }
+}
+
+trait SealedWord {}
+
+/// Word that can be transferred by the DMA.
+#[allow(private_bounds)]
+pub trait Word: SealedWord + Copy + 'static {
+    /// Word size
+    fn size() -> WordSize;
+}
+
+macro_rules! impl_word {
+    ($t:ty, $size:ident) => {
+        impl SealedWord for $t {}
+        impl Word for $t {
+            fn size() -> WordSize {
+                WordSize::$size
+            }
+        }
+    };
+}
+
+impl_word!(u8, OneByte);
+impl_word!(i8, OneByte);
+impl_word!(u16, TwoBytes);
+impl_word!(i16, TwoBytes);
+impl_word!(u32, FourBytes);
+impl_word!(i32, FourBytes);
+
+pub(crate) trait SealedChannel {
+    fn id(&self) -> u8;
+}
+
+/// DMAC1 channel.
+#[allow(private_bounds)]
+pub trait Channel: SealedChannel + Peripheral<P = Self> + Into<AnyChannel> + 'static {
+    /// Type-erase (degrade) this pin into an `AnyChannel`.
+    ///
+    /// This converts DMA channel singletons (`DMAC_CH1`, `DMAC_CH2`, ...), which
+    /// are all different types, into the same type. It is useful for
+    /// creating arrays of channels, or avoiding generics.
+    #[inline]
+    fn degrade(self) -> AnyChannel {
+        AnyChannel { id: self.id() }
+    }
+}
+
+/// Type-erased DMA channel.
+pub struct AnyChannel {
+    pub(crate) id: u8,
+}
+impl_peripheral!(AnyChannel);
+
+impl SealedChannel for AnyChannel {
+    fn id(&self) -> u8 {
+        self.id
+    }
+}
+impl Channel for AnyChannel {}
+
+macro_rules! channel_impl {
+    ($name:ident, $num:expr) => {
+        impl SealedChannel for peripherals::$name {
+            fn id(&self) -> u8 {
+                $num
+            }
+        }
+        impl Channel for peripherals::$name {}
+
+        impl From<peripherals::$name> for crate::dma::AnyChannel {
+            fn from(val: peripherals::$name) -> Self {
+                crate::dma::Channel::degrade(val)
+            }
+        }
+    };
+}
+
+channel_impl!(DMAC_CH1, 0);
+channel_impl!(DMAC_CH2, 1);
+channel_impl!(DMAC_CH3, 2);
+channel_impl!(DMAC_CH4, 3);
+channel_impl!(DMAC_CH5, 4);
+channel_impl!(DMAC_CH6, 5);
+channel_impl!(DMAC_CH7, 6);
+channel_impl!(DMAC_CH8, 7);
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH1() {
+    dmac::on_irq(0);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH2() {
+    dmac::on_irq(1);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH3() {
+    dmac::on_irq(2);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH4() {
+    dmac::on_irq(3);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH5() {
+    dmac::on_irq(4);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH6() {
+    dmac::on_irq(5);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH7() {
+    dmac::on_irq(6);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn DMAC1_CH8() {
+    dmac::on_irq(7);
+}
+
+#[cfg(feature = "rt")]
+#[interrupt]
+fn EXTDMA() {
+    extdma::on_irq();
+}
+
+pub(crate) unsafe fn init(dma_it_priority: interrupt::Priority) {
+    crate::rcc::enable_and_reset::<peripherals::DMAC1>();
+    crate::rcc::enable_and_reset::<peripherals::EXTDMA>();
+
+    for irq in [
+        interrupt::DMAC1_CH1,
+        interrupt::DMAC1_CH2,
+        interrupt::DMAC1_CH3,
+        interrupt::DMAC1_CH4,
+        interrupt::DMAC1_CH5,
+        interrupt::DMAC1_CH6,
+        interrupt::DMAC1_CH7,
+        interrupt::DMAC1_CH8,
+        interrupt::EXTDMA,
+    ] {
+        irq.disable();
+        irq.set_priority(dma_it_priority);
+        irq.enable();
+    }
+}
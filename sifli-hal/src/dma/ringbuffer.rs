@@ -0,0 +1,448 @@
+use core::future::poll_fn;
+use core::sync::atomic::{fence, Ordering};
+use core::task::{Poll, Waker};
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::dmac::{cache_clean_invalidate, cache_invalidate, Dir, TransferOptions, STATE};
+use super::{AnyChannel, Channel, Request, Word};
+
+/// The DMA overran the ring buffer: data was lost (readable) or
+/// stale data was sent (writable).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OverrunError;
+
+/// Position of the DMA in a circular buffer, counted since the channel was started.
+struct DmaPosition<'a> {
+    channel: &'a AnyChannel,
+    cap: usize,
+}
+
+impl<'a> DmaPosition<'a> {
+    /// Absolute index of the next item the DMA will access.
+    fn get(&self) -> usize {
+        let state = &STATE[self.channel.index()];
+        loop {
+            let count = state.complete_count.load(Ordering::Acquire);
+            let pending = self.channel.is_complete_pending();
+            let ndtr = self.channel.get_remaining_transfers() as usize;
+            // The interrupt may run between the reads, read again to detect the race.
+            if self.channel.is_complete_pending() == pending && state.complete_count.load(Ordering::Acquire) == count
+            {
+                return position(self.cap, count, pending, ndtr);
+            }
+        }
+    }
+}
+
+/// Absolute position from the completed passes `count` and the remaining transfers `ndtr`.
+///
+/// NDTR reloads at the end of a pass, before the interrupt counts it: while TCIF
+/// is `pending`, that pass is not in `count` yet. An NDTR of 0 is the end of the
+/// last pass once circular mode is disabled, there is no reload then.
+fn position(cap: usize, count: usize, pending: bool, ndtr: usize) -> usize {
+    let count = if pending && ndtr != 0 { count + 1 } else { count };
+    count * cap + (cap - ndtr)
+}
+
+/// Number of items between `index` and the DMA position `dma`, which must be at most `cap`.
+fn distance(dma: usize, index: usize, cap: usize) -> Result<usize, OverrunError> {
+    match dma.checked_sub(index) {
+        Some(n) if n <= cap => Ok(n),
+        _ => Err(OverrunError),
+    }
+}
+
+/// Split `n` items from absolute `index` into the parts before and after the wrap.
+fn split(index: usize, n: usize, cap: usize) -> (core::ops::Range<usize>, core::ops::Range<usize>) {
+    let start = index % cap;
+    let first = n.min(cap - start);
+    (start..start + first, 0..n - first)
+}
+
+/// Ringbuffer for receiving data using DMA circular mode.
+pub struct ReadableRingBuffer<'a, W: Word> {
+    channel: PeripheralRef<'a, AnyChannel>,
+    buffer: &'a mut [W],
+    request: Request,
+    peri_addr: *mut W,
+    options: TransferOptions,
+    /// Absolute index of the next item to be read by the CPU.
+    read_index: usize,
+}
+
+impl<'a, W: Word> ReadableRingBuffer<'a, W> {
+    /// Create a new ring buffer, the transfer is not started until [`start`](Self::start) is called.
+    pub unsafe fn new(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buffer: &'a mut [W],
+        mut options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+        // The half transfer interrupt is needed to wake readers in the middle of a pass.
+        options.half_transfer_ir = true;
+        Self {
+            channel: channel.map_into(),
+            buffer,
+            request,
+            peri_addr,
+            options,
+            read_index: 0,
+        }
+    }
+
+    /// Start the ring buffer operation.
+    pub fn start(&mut self) {
+        cache_clean_invalidate(&*self.buffer as *const [W]);
+        unsafe {
+            self.channel.configure(
+                self.request,
+                Dir::PeripheralToMemory,
+                self.peri_addr as *const u32,
+                self.buffer.as_mut_ptr() as *mut u32,
+                self.buffer.len(),
+                true,
+                W::size(),
+                self.options,
+                true,
+            );
+        }
+        self.read_index = 0;
+        self.channel.start();
+    }
+
+    /// Clear all data in the ring buffer.
+    pub fn clear(&mut self) {
+        self.read_index = self.dma_position();
+    }
+
+    fn dma_position(&self) -> usize {
+        DmaPosition {
+            channel: &self.channel,
+            cap: self.buffer.len(),
+        }
+        .get()
+    }
+
+    /// The capacity of the ringbuffer
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The current number of items in the buffer.
+    pub fn len(&self) -> Result<usize, OverrunError> {
+        distance(self.dma_position(), self.read_index, self.buffer.len())
+    }
+
+    /// Read items from the ring buffer.
+    ///
+    /// Returns a tuple of the length read and the length remaining in the buffer.
+    /// If not all of the items were read, then there will be some items in the buffer remaining.
+    /// The length remaining is the capacity, ring_buf.len(), less the items remaining after the read.
+    /// OverrunError is returned if the portion to be read was overwritten by the DMA controller.
+    pub fn read(&mut self, buf: &mut [W]) -> Result<(usize, usize), OverrunError> {
+        let cap = self.buffer.len();
+        let available = self.len()?;
+        let n = available.min(buf.len());
+
+        let (first, second) = split(self.read_index, n, cap);
+        let (head, tail) = buf[..n].split_at_mut(first.len());
+
+        // Drop stale cache lines before looking at what the DMA wrote.
+        cache_invalidate(&mut self.buffer[first.clone()] as *mut [W]);
+        cache_invalidate(&mut self.buffer[second.clone()] as *mut [W]);
+        fence(Ordering::SeqCst);
+        head.copy_from_slice(&self.buffer[first]);
+        tail.copy_from_slice(&self.buffer[second]);
+
+        // Check that the DMA did not overwrite what we just copied.
+        let available_after = distance(self.dma_position(), self.read_index, cap)?;
+
+        self.read_index += n;
+        Ok((n, available_after - n))
+    }
+
+    /// Read an exact number of items from the ringbuffer.
+    ///
+    /// Returns the remaining number of items available in the buffer.
+    pub async fn read_exact(&mut self, buffer: &mut [W]) -> Result<usize, OverrunError> {
+        let mut read_data = 0;
+        let buffer_len = buffer.len();
+
+        poll_fn(|cx| {
+            self.set_waker(cx.waker());
+
+            match self.read(&mut buffer[read_data..buffer_len]) {
+                Ok((len, remaining)) => {
+                    read_data += len;
+                    if read_data == buffer_len {
+                        Poll::Ready(Ok(remaining))
+                    } else {
+                        Poll::Pending
+                    }
+                }
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+
+    /// Set a waker to be woken when at least one item has been received.
+    pub fn set_waker(&mut self, waker: &Waker) {
+        STATE[self.channel.index()].waker.register(waker);
+    }
+
+    /// Request the DMA to stop.
+    pub fn request_stop(&mut self) {
+        self.channel.request_stop();
+    }
+
+    /// Stop the DMA after the current pass over the buffer completes.
+    pub async fn stop(&mut self) {
+        // The position restarts with the completion count, keep the read index in step.
+        let passes = self.channel.disable_circular_mode();
+        self.read_index = self.read_index.saturating_sub(passes * self.buffer.len());
+        poll_fn(|cx| {
+            self.set_waker(cx.waker());
+            self.channel.poll_stop()
+        })
+        .await
+    }
+
+    /// Return whether DMA is still running.
+    pub fn is_running(&mut self) -> bool {
+        self.channel.is_running()
+    }
+}
+
+impl<'a, W: Word> Drop for ReadableRingBuffer<'a, W> {
+    fn drop(&mut self) {
+        self.request_stop();
+        while self.is_running() {}
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+        self.channel.clear_irqs();
+    }
+}
+
+/// Ringbuffer for writing data using DMA circular mode.
+pub struct WritableRingBuffer<'a, W: Word> {
+    channel: PeripheralRef<'a, AnyChannel>,
+    buffer: &'a mut [W],
+    request: Request,
+    peri_addr: *mut W,
+    options: TransferOptions,
+    /// Absolute index of the next item to be written by the CPU.
+    write_index: usize,
+}
+
+impl<'a, W: Word> WritableRingBuffer<'a, W> {
+    /// Create a new ring buffer, the transfer is not started until [`start`](Self::start) is called.
+    ///
+    /// The initial content of `buffer` is sent first, use [`write_immediate`](Self::write_immediate)
+    /// before starting to fill it.
+    pub unsafe fn new(
+        channel: impl Peripheral<P = impl Channel> + 'a,
+        request: Request,
+        peri_addr: *mut W,
+        buffer: &'a mut [W],
+        mut options: TransferOptions,
+    ) -> Self {
+        into_ref!(channel);
+        options.half_transfer_ir = true;
+        let write_index = buffer.len();
+        Self {
+            channel: channel.map_into(),
+            buffer,
+            request,
+            peri_addr,
+            options,
+            write_index,
+        }
+    }
+
+    /// Start the ring buffer operation.
+    pub fn start(&mut self) {
+        cache_clean_invalidate(&*self.buffer as *const [W]);
+        unsafe {
+            self.channel.configure(
+                self.request,
+                Dir::MemoryToPeripheral,
+                self.peri_addr as *const u32,
+                self.buffer.as_mut_ptr() as *mut u32,
+                self.buffer.len(),
+                true,
+                W::size(),
+                self.options,
+                true,
+            );
+        }
+        self.write_index = self.buffer.len();
+        self.channel.start();
+    }
+
+    /// Overwrite the whole buffer before the transfer is started.
+    ///
+    /// Returns the number of items written.
+    pub fn write_immediate(&mut self, buf: &[W]) -> usize {
+        let n = buf.len().min(self.buffer.len());
+        self.buffer[..n].copy_from_slice(&buf[..n]);
+        n
+    }
+
+    fn dma_position(&self) -> usize {
+        DmaPosition {
+            channel: &self.channel,
+            cap: self.buffer.len(),
+        }
+        .get()
+    }
+
+    /// The capacity of the ringbuffer
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The number of items that can be written without waiting.
+    pub fn free(&self) -> Result<usize, OverrunError> {
+        // The DMA caught up with us and sent stale data if it is past the write index.
+        let pending = distance(self.write_index, self.dma_position(), self.buffer.len())?;
+        Ok(self.buffer.len() - pending)
+    }
+
+    /// Write items to the ring buffer.
+    ///
+    /// Returns a tuple of the length written and the free space remaining in the buffer.
+    pub fn write(&mut self, buf: &[W]) -> Result<(usize, usize), OverrunError> {
+        let cap = self.buffer.len();
+        let free = self.free()?;
+        let n = free.min(buf.len());
+
+        let (first, second) = split(self.write_index, n, cap);
+        let (head, tail) = buf[..n].split_at(first.len());
+        self.buffer[first.clone()].copy_from_slice(head);
+        self.buffer[second.clone()].copy_from_slice(tail);
+
+        // Push the new items out of the cache before the DMA reads them.
+        cache_clean_invalidate(&self.buffer[first] as *const [W]);
+        cache_clean_invalidate(&self.buffer[second] as *const [W]);
+        fence(Ordering::SeqCst);
+
+        self.write_index += n;
+        Ok((n, free - n))
+    }
+
+    /// Write an exact number of items to the ringbuffer.
+    ///
+    /// Returns the remaining free space in the buffer.
+    pub async fn write_exact(&mut self, buffer: &[W]) -> Result<usize, OverrunError> {
+        let mut written_data = 0;
+        let buffer_len = buffer.len();
+
+        poll_fn(|cx| {
+            self.set_waker(cx.waker());
+
+            match self.write(&buffer[written_data..buffer_len]) {
+                Ok((len, remaining)) => {
+                    written_data += len;
+                    if written_data == buffer_len {
+                        Poll::Ready(Ok(remaining))
+                    } else {
+                        Poll::Pending
+                    }
+                }
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+
+    /// Set a waker to be woken when at least one item has been sent.
+    pub fn set_waker(&mut self, waker: &Waker) {
+        STATE[self.channel.index()].waker.register(waker);
+    }
+
+    /// Request the DMA to stop.
+    pub fn request_stop(&mut self) {
+        self.channel.request_stop();
+    }
+
+    /// Stop the DMA after the current pass over the buffer completes.
+    pub async fn stop(&mut self) {
+        // The position restarts with the completion count, keep the write index in step.
+        let passes = self.channel.disable_circular_mode();
+        self.write_index = self.write_index.saturating_sub(passes * self.buffer.len());
+        poll_fn(|cx| {
+            self.set_waker(cx.waker());
+            self.channel.poll_stop()
+        })
+        .await
+    }
+
+    /// Return whether DMA is still running.
+    pub fn is_running(&mut self) -> bool {
+        self.channel.is_running()
+    }
+}
+
+impl<'a, W: Word> Drop for WritableRingBuffer<'a, W> {
+    fn drop(&mut self) {
+        self.request_stop();
+        while self.is_running() {}
+
+        // "Subsequent reads and writes cannot be moved ahead of preceding reads."
+        fence(Ordering::SeqCst);
+        self.channel.clear_irqs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_counts_passes() {
+        assert_eq!(position(8, 0, false, 8), 0);
+        assert_eq!(position(8, 0, false, 3), 5);
+        assert_eq!(position(8, 2, false, 8), 16);
+        assert_eq!(position(8, 2, false, 1), 23);
+    }
+
+    #[test]
+    fn position_with_pending_completion() {
+        // NDTR reloaded, the interrupt did not count the pass yet.
+        assert_eq!(position(8, 0, true, 8), 8);
+        assert_eq!(position(8, 1, true, 6), 18);
+        // Same position once the interrupt ran.
+        assert_eq!(position(8, 2, false, 6), 18);
+    }
+
+    #[test]
+    fn position_at_end_of_last_pass() {
+        // Circular mode disabled, NDTR stays at 0.
+        assert_eq!(position(8, 0, true, 0), 8);
+        assert_eq!(position(8, 1, false, 0), 16);
+    }
+
+    #[test]
+    fn distance_checks_overrun() {
+        assert_eq!(distance(10, 10, 8), Ok(0));
+        assert_eq!(distance(18, 10, 8), Ok(8));
+        assert_eq!(distance(19, 10, 8), Err(OverrunError));
+        // Index ahead of the DMA, e.g. a stale index after a restart.
+        assert_eq!(distance(3, 10, 8), Err(OverrunError));
+    }
+
+    #[test]
+    fn split_wraps() {
+        assert_eq!(split(0, 5, 8), (0..5, 0..0));
+        assert_eq!(split(13, 3, 8), (5..8, 0..0));
+        assert_eq!(split(14, 5, 8), (6..8, 0..3));
+        assert_eq!(split(16, 8, 8), (0..8, 0..0));
+        assert_eq!(split(7, 0, 8), (7..7, 0..0));
+    }
+}
@@ -0,0 +1,343 @@
+//! eFuse controller (EFUSEC).
+//!
+//! The eFuses hold per-chip data programmed at factory test: the chip
+//! identity ([`ChipInfo`]), the voltage trims used by [`crate::pmu::dvfs`]
+//! ([`VoltageTrims`]), and the ADC and temperature sensor calibrations
+//! ([`AdcCalibration`], [`TsenCalibration`]). They are organized in
+//! [`BANK_COUNT`] banks of 256 bits.
+//!
+//! Bits can only be programmed from 0 to 1, once. [`program_bank`] is
+//! `unsafe` and meant for production tooling only.
+
+use crate::pac::{EFUSEC, HPSYS_CFG};
+use crate::peripherals;
+use crate::rcc::{self, SealedRccEnableReset};
+use crate::time::Hertz;
+
+/// Number of banks.
+pub const BANK_COUNT: usize = 4;
+/// Number of 32-bit words in a bank.
+pub const BANK_WORDS: usize = 8;
+
+/// Bank holding the chip identity.
+const IDENTITY_BANK: usize = 0;
+/// Bank holding the factory calibration data.
+const CALIBRATION_BANK: usize = 1;
+
+/// Read timeout, in microseconds.
+const READ_TIMEOUT_US: u32 = 1_000;
+/// Program timeout, in microseconds. Each bit takes a ~10us strobe.
+const PROGRAM_TIMEOUT_US: u32 = 20_000;
+
+/// Minimum read strobe width, in nanoseconds.
+const READ_STROBE_NS: u32 = 100;
+/// Program strobe width, in nanoseconds (9us to 11us).
+const PROGRAM_STROBE_NS: u32 = 10_000;
+
+/// eFuse error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The bank number is out of range.
+    InvalidBank,
+    /// The controller didn't complete the operation in time.
+    Timeout,
+    /// The bits read back after programming don't match.
+    VerifyFailed,
+}
+
+/// Read bank `bank`, least significant word first.
+pub fn read_bank(bank: usize) -> Result<[u32; BANK_WORDS], Error> {
+    if bank >= BANK_COUNT {
+        return Err(Error::InvalidBank);
+    }
+
+    // Left enabled after reading. Not reset, the bootloader may have set the timings.
+    peripherals::EFUSEC::rcc_enable();
+
+    critical_section::with(|_| {
+        set_timing();
+        run(bank, false, READ_TIMEOUT_US)?;
+
+        let mut data = [0; BANK_WORDS];
+        for (word, d) in data.iter_mut().enumerate() {
+            *d = EFUSEC.bank_data(bank * BANK_WORDS + word).read().data();
+        }
+        Ok(data)
+    })
+}
+
+/// Program the bits set in `data` into bank `bank`, then read it back.
+///
+/// Bits already programmed stay programmed, bits cleared in `data` are left
+/// untouched.
+///
+/// # Safety
+///
+/// Programming is irreversible. Wrong identity or calibration bits can make
+/// the chip unusable, e.g. by setting a voltage trim out of range. The eFuse
+/// programming supply must be present.
+pub unsafe fn program_bank(bank: usize, data: &[u32; BANK_WORDS]) -> Result<(), Error> {
+    let current = read_bank(bank)?;
+    if current.iter().zip(data).all(|(c, d)| d & !c == 0) {
+        return Ok(());
+    }
+
+    critical_section::with(|_| {
+        for (word, (c, d)) in current.iter().zip(data).enumerate() {
+            // Only strobe the bits that still need it.
+            EFUSEC.pgm_data(word).write(|w| w.set_data(d & !c));
+        }
+        run(bank, true, PROGRAM_TIMEOUT_US)
+    })?;
+
+    let programmed = read_bank(bank)?;
+    if programmed.iter().zip(current.iter().zip(data)).all(|(p, (c, d))| *p == c | d) {
+        Ok(())
+    } else {
+        Err(Error::VerifyFailed)
+    }
+}
+
+/// Set the strobe timings from the current clk_peri frequency.
+fn set_timing() {
+    let freq = rcc::get_clk_peri_freq().unwrap_or(Hertz::mhz(48));
+    let cycles = |ns: u32| ((freq.0 as u64 * ns as u64).div_ceil(1_000_000_000)) as u32;
+
+    EFUSEC.timr().write(|w| {
+        w.set_thrd(cycles(READ_STROBE_NS).clamp(1, 0x7f) as u8);
+        w.set_thpck(cycles(PROGRAM_STROBE_NS).clamp(1, 0x7ff) as u16);
+    });
+}
+
+/// Start a read or program operation on `bank` and wait for it.
+fn run(bank: usize, program: bool, timeout_us: u32) -> Result<(), Error> {
+    EFUSEC.cr().write(|w| {
+        w.set_banksel(bank as u8);
+        w.set_mode(program);
+        w.set_en(true);
+    });
+
+    let mut remaining = timeout_us;
+    while !EFUSEC.sr().read().done() {
+        if remaining == 0 {
+            EFUSEC.cr().modify(|w| w.set_en(false));
+            return Err(Error::Timeout);
+        }
+        remaining -= 1;
+        crate::cortex_m_blocking_delay_us(1);
+    }
+    // Write 1 to clear
+    EFUSEC.sr().write(|w| w.set_done(true));
+    Ok(())
+}
+
+/// Extract `len` bits at bit `offset` of a bank.
+fn bits(data: &[u32; BANK_WORDS], offset: usize, len: usize) -> u32 {
+    let word = data[offset / 32] >> (offset % 32);
+    // Fields never straddle two words.
+    word & ((1 << len) - 1)
+}
+
+/// Factory voltage trims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VoltageTrims {
+    /// BUCK comparator offset trim.
+    pub buck_vos_trim: u8,
+    /// Polarity of `buck_vos_trim`.
+    pub buck_vos_polar: bool,
+    /// HPSYS LDO code giving 1.1 V.
+    pub hpsys_ldo_vout: u8,
+    /// LPSYS LDO code giving 1.1 V.
+    pub lpsys_ldo_vout: u8,
+    /// Retention voltage trim.
+    pub vret_trim: u8,
+}
+
+impl VoltageTrims {
+    /// Read the trims, `None` if the chip was not calibrated.
+    pub fn read() -> Result<Option<Self>, Error> {
+        let data = read_bank(CALIBRATION_BANK)?;
+
+        // Layout of the calibration bank, word 0.
+        let trims = Self {
+            buck_vos_trim: bits(&data, 0, 3) as u8,
+            buck_vos_polar: bits(&data, 3, 1) != 0,
+            hpsys_ldo_vout: bits(&data, 4, 4) as u8,
+            lpsys_ldo_vout: bits(&data, 8, 4) as u8,
+            vret_trim: bits(&data, 12, 4) as u8,
+        };
+
+        // An unprogrammed LDO code reads 0, which is never a valid 1.1 V code.
+        if trims.hpsys_ldo_vout == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(trims))
+        }
+    }
+}
+
+/// Chip package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Package {
+    /// QFN68, external flash
+    Sf32lb52aQfn68,
+    /// QFN68, external flash, internal PSRAM
+    Sf32lb52bQfn68,
+    /// QFN68, internal NOR flash
+    Sf32lb52dQfn68,
+    /// QFN68, internal NOR flash and PSRAM
+    Sf32lb52eQfn68,
+    /// QFN68, internal PSRAM
+    Sf32lb52jQfn68,
+    /// Unknown package id
+    Unknown(u8),
+}
+
+impl Package {
+    fn from_id(id: u8) -> Self {
+        match id {
+            0x01 => Self::Sf32lb52aQfn68,
+            0x02 => Self::Sf32lb52bQfn68,
+            0x03 => Self::Sf32lb52dQfn68,
+            0x04 => Self::Sf32lb52eQfn68,
+            0x05 => Self::Sf32lb52jQfn68,
+            id => Self::Unknown(id),
+        }
+    }
+}
+
+/// Chip identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChipInfo {
+    /// Unique id.
+    pub uid: [u8; 16],
+    /// Package and SiP variant.
+    pub package: Package,
+    /// Size of the SiP flash in bytes, 0 if none.
+    pub flash_size: u32,
+    /// Size of the SiP PSRAM in bytes, 0 if none.
+    pub psram_size: u32,
+    /// Silicon revision.
+    pub revision: u8,
+}
+
+impl ChipInfo {
+    /// Read the chip identity.
+    pub fn read() -> Result<Self, Error> {
+        let data = read_bank(IDENTITY_BANK)?;
+
+        // Layout of the identity bank: words 0..4 UID, word 4 package and SiP.
+        let mut uid = [0; 16];
+        for (chunk, word) in uid.chunks_exact_mut(4).zip(&data[..4]) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        // Sizes are stored as log2 of the size in MiB, 0 if none.
+        let size = |code: u32| if code == 0 { 0 } else { (1u32 << (code - 1)) * 1024 * 1024 };
+
+        Ok(Self {
+            uid,
+            package: Package::from_id(bits(&data, 128, 8) as u8),
+            flash_size: size(bits(&data, 136, 4)),
+            psram_size: size(bits(&data, 140, 4)),
+            revision: HPSYS_CFG.idr().read().revid(),
+        })
+    }
+}
+
+/// Factory GPADC calibration.
+///
+/// The ADC was measured with 1.0V and 2.5V applied to a channel, the
+/// conversion is linear between the two points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdcCalibration {
+    code_1v0: u16,
+    code_2v5: u16,
+}
+
+impl AdcCalibration {
+    /// Calibration from the raw results at 1.0V and 2.5V, `None` unless
+    /// `code_2v5` is above `code_1v0`.
+    pub const fn new(code_1v0: u16, code_2v5: u16) -> Option<Self> {
+        if code_2v5 > code_1v0 {
+            Some(Self { code_1v0, code_2v5 })
+        } else {
+            None
+        }
+    }
+
+    /// Read the calibration, `None` if the chip was not calibrated.
+    pub fn read() -> Result<Option<Self>, Error> {
+        let data = read_bank(CALIBRATION_BANK)?;
+
+        // Layout of the calibration bank, word 1.
+        Ok(Self::new(bits(&data, 32, 12) as u16, bits(&data, 44, 12) as u16))
+    }
+
+    /// Raw conversion result at 1.0V.
+    pub const fn code_1v0(&self) -> u16 {
+        self.code_1v0
+    }
+
+    /// Raw conversion result at 2.5V.
+    pub const fn code_2v5(&self) -> u16 {
+        self.code_2v5
+    }
+
+    /// Convert a raw conversion result to millivolts.
+    pub fn to_millivolts(&self, code: u16) -> i32 {
+        // Positive, checked by `new`.
+        let span = self.code_2v5 as i32 - self.code_1v0 as i32;
+        1000 + (code as i32 - self.code_1v0 as i32) * 1500 / span
+    }
+}
+
+/// Factory temperature sensor calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TsenCalibration {
+    /// Correction added to the nominal conversion, in 0.1 °C.
+    pub offset_decicelsius: i16,
+}
+
+impl TsenCalibration {
+    /// Read the calibration, `None` if the chip was not calibrated.
+    pub fn read() -> Result<Option<Self>, Error> {
+        let data = read_bank(CALIBRATION_BANK)?;
+
+        // Layout of the calibration bank, word 2: 10-bit two's complement.
+        let raw = bits(&data, 64, 10) as u16;
+        if raw == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            offset_decicelsius: ((raw << 6) as i16) >> 6,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adc_calibration_needs_increasing_codes() {
+        assert_eq!(AdcCalibration::new(0, 0), None);
+        assert_eq!(AdcCalibration::new(3102, 1241), None);
+        assert!(AdcCalibration::new(1241, 3102).is_some());
+    }
+
+    #[test]
+    fn adc_calibration_to_millivolts() {
+        let cal = AdcCalibration::new(1241, 3102).unwrap();
+        assert_eq!(cal.to_millivolts(1241), 1000);
+        assert_eq!(cal.to_millivolts(3102), 2500);
+        // Extrapolated outside of the calibration points.
+        assert_eq!(cal.to_millivolts(0), 0);
+        assert_eq!(cal.to_millivolts(4095), 3300);
+    }
+}
@@ -13,12 +13,20 @@ use crate::pac::hpsys_gpio::regs;
 use crate::pac::hpsys_pinmux::vals;
 use crate::{interrupt, pac, peripherals, Peripheral};
 
+pub use crate::_generated::Function;
+pub use crate::hpsys_cfg::{RouteError, Signal};
+
 // TODO: move this const to _generated.rs
 #[cfg(any(feature = "sf32lb52x"))]
-pub(crate) const PA_PIN_COUNT: usize = 30;
+pub(crate) const PA_PIN_COUNT: usize = 45;
 
 static PA_WAKERS: [AtomicWaker; PA_PIN_COUNT] = [const { AtomicWaker::new() }; PA_PIN_COUNT];
 
+/// The pad can't carry the requested [`Function`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidFunction;
+
 /// Represents a digital input or output level.
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum Level {
@@ -188,35 +196,38 @@ pub(crate) unsafe fn init(gpio1_it_priority: interrupt::Priority) {
 
 #[cfg(feature = "rt")]
 fn irq_handler<const N: usize>(wakers: &[AtomicWaker; N]) {
-    // let cpu = SIO.cpuid().read() as usize;
-    // // There are two sets of interrupt registers, one for cpu0 and one for cpu1
-    // // and here we are selecting the set that belongs to the currently executing
-    // // cpu.
-    // let proc_intx: pac::io::Int = bank.int_proc(cpu);
-    // for pin in 0..N {
-    //     // There are 4 raw interrupt status registers, PROCx_INTS0, PROCx_INTS1,
-    //     // PROCx_INTS2, and PROCx_INTS3, and we are selecting the one that the
-    //     // current pin belongs to.
-    //     let intsx = proc_intx.ints(pin / 8);
-    //     // The status register is divided into groups of four, one group for
-    //     // each pin. Each group consists of four trigger levels LEVEL_LOW,
-    //     // LEVEL_HIGH, EDGE_LOW, and EDGE_HIGH for each pin.
-    //     let pin_group = pin % 8;
-    //     let event = (intsx.read().0 >> (pin_group * 4)) & 0xf;
-
-    //     // no more than one event can be awaited per pin at any given time, so
-    //     // we can just clear all interrupt enables for that pin without having
-    //     // to check which event was signalled.
-    //     if event != 0 {
-    //         proc_intx.inte(pin / 8).write_clear(|w| {
-    //             w.set_edge_high(pin_group, true);
-    //             w.set_edge_low(pin_group, true);
-    //             w.set_level_high(pin_group, true);
-    //             w.set_level_low(pin_group, true);
-    //         });
-    //         wakers[pin].wake();
-    //     }
-    // }
+    let gpio = pac::HPSYS_GPIO;
+    for bank in 0..(N + 31) / 32 {
+        // Only look at pins whose interrupt is currently enabled, the raw
+        // status bits of disabled pins may still be set.
+        let pending = if bank == 0 {
+            gpio.isr0().read().0 & gpio.ier0().read().0
+        } else {
+            gpio.isr1().read().0 & gpio.ier1().read().0
+        };
+        if pending == 0 {
+            continue;
+        }
+
+        // No more than one event can be awaited per pin at any given time, so
+        // we can just disable the interrupt of the pin without having to check
+        // which event was signalled. This also stops level triggers from
+        // firing again until the next `InputFuture` re-arms the pin.
+        if bank == 0 {
+            gpio.iecr0().write_value(regs::Iecr0(pending));
+            gpio.isr0().write_value(regs::Isr0(pending));
+        } else {
+            gpio.iecr1().write_value(regs::Iecr1(pending));
+            gpio.isr1().write_value(regs::Isr1(pending));
+        }
+
+        for bit in 0..32 {
+            let pin = bank * 32 + bit;
+            if pin < N && pending & (1 << bit) != 0 {
+                wakers[pin].wake();
+            }
+        }
+    }
 }
 
 #[cfg(feature = "rt")]
@@ -229,12 +240,127 @@ fn GPIO1() {
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 struct InputFuture<'d> {
     pin: PeripheralRef<'d, AnyPin>,
+    /// IPH/IPL bits before the wait, restored on drop: they also select
+    /// open-drain for an output pin.
+    polarity: (bool, bool),
 }
 
 impl<'d> InputFuture<'d> {
     fn new(pin: PeripheralRef<'d, AnyPin>, level: InterruptTrigger) -> Self {
-        todo!();
-        Self { pin }
+        let gpio = pin.gpio();
+        let bit = 1 << (pin.pin() % 32);
+
+        // (edge triggered, active high / rising, active low / falling)
+        let (edge, high, low) = match level {
+            InterruptTrigger::LevelLow => (false, false, true),
+            InterruptTrigger::LevelHigh => (false, true, false),
+            InterruptTrigger::EdgeLow => (true, false, true),
+            InterruptTrigger::EdgeHigh => (true, true, false),
+            InterruptTrigger::AnyEdge => (true, true, true),
+        };
+
+        let polarity = critical_section::with(|_| {
+            if pin.pin() / 32 == 0 {
+                // Disable the interrupt while reconfiguring it, so that a stale
+                // trigger setting cannot fire in between.
+                gpio.iecr0().write_value(regs::Iecr0(bit));
+
+                if edge {
+                    gpio.itsr0().write_value(regs::Itsr0(bit));
+                } else {
+                    gpio.itcr0().write_value(regs::Itcr0(bit));
+                }
+            } else {
+                gpio.iecr1().write_value(regs::Iecr1(bit));
+
+                if edge {
+                    gpio.itsr1().write_value(regs::Itsr1(bit));
+                } else {
+                    gpio.itcr1().write_value(regs::Itcr1(bit));
+                }
+            }
+
+            let polarity = get_polarity(&pin);
+            set_polarity(&pin, high, low);
+
+            // Clear any stale event and arm the pin.
+            if pin.pin() / 32 == 0 {
+                gpio.isr0().write_value(regs::Isr0(bit));
+                gpio.iesr0().write_value(regs::Iesr0(bit));
+            } else {
+                gpio.isr1().write_value(regs::Isr1(bit));
+                gpio.iesr1().write_value(regs::Iesr1(bit));
+            }
+            polarity
+        });
+
+        Self { pin, polarity }
+    }
+
+    #[inline]
+    fn is_armed(&self) -> bool {
+        let bit = 1 << (self.pin.pin() % 32);
+        let ier = if self.pin.pin() / 32 == 0 {
+            self.pin.gpio().ier0().read().0
+        } else {
+            self.pin.gpio().ier1().read().0
+        };
+        ier & bit != 0
+    }
+}
+
+impl<'d> Drop for InputFuture<'d> {
+    fn drop(&mut self) {
+        // Disarm the pin if the future is dropped before the event happened.
+        let bit = 1 << (self.pin.pin() % 32);
+        if self.pin.pin() / 32 == 0 {
+            self.pin.gpio().iecr0().write_value(regs::Iecr0(bit));
+        } else {
+            self.pin.gpio().iecr1().write_value(regs::Iecr1(bit));
+        }
+        let (high, low) = self.polarity;
+        critical_section::with(|_| set_polarity(&self.pin, high, low));
+    }
+}
+
+/// Read the IPH/IPL bits of `pin`.
+fn get_polarity(pin: &AnyPin) -> (bool, bool) {
+    let bit = 1 << (pin.pin() % 32);
+    let gpio = pin.gpio();
+    let (iph, ipl) = if pin.pin() / 32 == 0 {
+        (gpio.iphr0().read().0, gpio.iplr0().read().0)
+    } else {
+        (gpio.iphr1().read().0, gpio.iplr1().read().0)
+    };
+    (iph & bit != 0, ipl & bit != 0)
+}
+
+/// Write the IPH/IPL bits of `pin`.
+fn set_polarity(pin: &AnyPin, high: bool, low: bool) {
+    let bit = 1 << (pin.pin() % 32);
+    let gpio = pin.gpio();
+    if pin.pin() / 32 == 0 {
+        if high {
+            gpio.iphsr0().write_value(regs::Iphsr0(bit));
+        } else {
+            gpio.iphcr0().write_value(regs::Iphcr0(bit));
+        }
+        if low {
+            gpio.iplsr0().write_value(regs::Iplsr0(bit));
+        } else {
+            gpio.iplcr0().write_value(regs::Iplcr0(bit));
+        }
+    } else {
+        if high {
+            gpio.iphsr1().write_value(regs::Iphsr1(bit));
+        } else {
+            gpio.iphcr1().write_value(regs::Iphcr1(bit));
+        }
+        if low {
+            gpio.iplsr1().write_value(regs::Iplsr1(bit));
+        } else {
+            gpio.iplcr1().write_value(regs::Iplcr1(bit));
+        }
     }
 }
 
@@ -242,36 +368,17 @@ impl<'d> Future for InputFuture<'d> {
     type Output = ();
 
     fn poll(self: FuturePin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // // We need to register/re-register the waker for each poll because any
-        // // calls to wake will deregister the waker.
-        // let waker = match self.pin.bank() {
-        //     Bank::Bank0 => &BANK0_WAKERS[self.pin.pin() as usize],
-        //     #[cfg(feature = "qspi-as-gpio")]
-        //     Bank::Qspi => &QSPI_WAKERS[self.pin.pin() as usize],
-        // };
-        // waker.register(cx.waker());
-
-        // // self.int_proc() will get the register offset for the current cpu,
-        // // then we want to access the interrupt enable register for our
-        // // pin (there are 4 of these PROC0_INTE0, PROC0_INTE1, PROC0_INTE2, and
-        // // PROC0_INTE3 per cpu).
-        // let inte: pac::io::regs::Int = self.pin.int_proc().inte((self.pin.pin() / 8) as usize).read();
-        // // The register is divided into groups of four, one group for
-        // // each pin. Each group consists of four trigger levels LEVEL_LOW,
-        // // LEVEL_HIGH, EDGE_LOW, and EDGE_HIGH for each pin.
-        // let pin_group = (self.pin.pin() % 8) as usize;
-
-        // // since the interrupt handler clears all INTE flags we'll check that
-        // // all have been cleared and unconditionally return Ready(()) if so.
-        // // we don't need further handshaking since only a single event wait
-        // // is possible for any given pin at any given time.
-        // if !inte.edge_high(pin_group)
-        //     && !inte.edge_low(pin_group)
-        //     && !inte.level_high(pin_group)
-        //     && !inte.level_low(pin_group)
-        // {
-        //     return Poll::Ready(());
-        // }
+        // We need to register/re-register the waker for each poll because any
+        // calls to wake will deregister the waker.
+        PA_WAKERS[self.pin.pin() as usize].register(cx.waker());
+
+        // The interrupt handler disables the pin's interrupt when the event
+        // fires, so once it is disabled we are done. We don't need further
+        // handshaking since only a single event wait is possible for any
+        // given pin at any given time.
+        if !self.is_armed() {
+            return Poll::Ready(());
+        }
         Poll::Pending
     }
 }
@@ -507,7 +614,7 @@ impl<'d> Flex<'d> {
         let mut pin = Self { pin: pin.map_into() };
         
         // DISABLE_ISR
-        if pin.pin.pin() % 32 == 0 {
+        if pin.pin.pin() / 32 == 0 {
             pin.pin.gpio().iecr0().write_value(regs::Iecr0(pin.bit()));
         } else {
             pin.pin.gpio().iecr1().write_value(regs::Iecr1(pin.bit()));
@@ -516,7 +623,7 @@ impl<'d> Flex<'d> {
         // WAIT_ISR_DISABLED
         crate::cortex_m_blocking_delay_us(1);
 
-        if pin.pin.pin() % 32 == 0 {
+        if pin.pin.pin() / 32 == 0 {
             pin.pin.gpio().isr0().write_value(sifli_pac::hpsys_gpio::regs::Isr0(pin.bit()));
 
             // CLEAR_OPEN_DRAIN_FLAG
@@ -831,6 +938,28 @@ impl<'d> Flex<'d> {
         InputFuture::new(self.pin.reborrow(), InterruptTrigger::AnyEdge).await;
     }
 
+    /// Select the pad function.
+    ///
+    /// Returns [`InvalidFunction`] if this pad can't carry `function`.
+    /// The `PAxx_I2C_UART` and `PAxx_TIM` functions only connect the pad to
+    /// the pin-select logic, the peripheral signal itself is chosen in HPSYS_CFG.
+    pub fn set_function(&mut self, function: Function) -> Result<(), InvalidFunction> {
+        let fsel = function.fsel(self.pin.pin()).ok_or(InvalidFunction)?;
+        unsafe { self.set_fsel_unchecked(fsel) };
+        Ok(())
+    }
+
+    /// Route a USART, I2C or timer signal to this pad.
+    ///
+    /// This selects the pad's `PAxx_I2C_UART` or `PAxx_TIM` function and
+    /// programs the HPSYS_CFG pin-select register of `signal`. The route is
+    /// released when this `Flex` is dropped.
+    pub fn route(&mut self, signal: Signal) -> Result<(), RouteError> {
+        let fsel = crate::hpsys_cfg::route(self.pin.pin(), signal)?;
+        unsafe { self.set_fsel_unchecked(fsel) };
+        Ok(())
+    }
+
     /// set pinmux fsel
     pub unsafe fn set_fsel_unchecked(&mut self, fsel: u8) {
         let pin_id = self.pin.pin();
@@ -852,6 +981,7 @@ impl<'d> Flex<'d> {
 impl<'d> Drop for Flex<'d> {
     #[inline]
     fn drop(&mut self) {
+        crate::hpsys_cfg::release(self.pin.pin());
         // let idx = self.pin._pin() as usize;
         // self.pin.pad_ctrl().write(|_| {});
         // self.pin.gpio().ctrl().write(|w| {
@@ -980,6 +1110,21 @@ impl_pin!(PA26, 0, 26);
 impl_pin!(PA27, 0, 27);
 impl_pin!(PA28, 0, 28);
 impl_pin!(PA29, 0, 29);
+impl_pin!(PA30, 0, 30);
+impl_pin!(PA31, 0, 31);
+impl_pin!(PA32, 0, 32);
+impl_pin!(PA33, 0, 33);
+impl_pin!(PA34, 0, 34);
+impl_pin!(PA35, 0, 35);
+impl_pin!(PA36, 0, 36);
+impl_pin!(PA37, 0, 37);
+impl_pin!(PA38, 0, 38);
+impl_pin!(PA39, 0, 39);
+impl_pin!(PA40, 0, 40);
+impl_pin!(PA41, 0, 41);
+impl_pin!(PA42, 0, 42);
+impl_pin!(PA43, 0, 43);
+impl_pin!(PA44, 0, 44);
 
 // ====================
 
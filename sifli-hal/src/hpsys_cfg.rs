@@ -0,0 +1,204 @@
+//! HPSYS_CFG pin routing.
+//!
+//! On SF32LB52x every pad has a generic `PAxx_I2C_UART` and `PAxx_TIM`
+//! function. Selecting one of them only connects the pad to the pin-select
+//! logic, the USART/I2C/timer signal actually driving (or sampling) the pad
+//! is chosen by the HPSYS_CFG `*_PINR` registers, which hold a pad number per
+//! signal.
+//!
+//! [`Flex::route`](crate::gpio::Flex::route) programs both, and keeps track of
+//! which signal uses which pad so that two signals are never routed to the same
+//! pad. Routes are released when the `Flex` owning the pad is dropped.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::gpio::PA_PIN_COUNT;
+use crate::pac;
+
+/// Signal routed through a `PAxx_I2C_UART` or `PAxx_TIM` pad function.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Signal {
+    Usart1Tx,
+    Usart1Rx,
+    Usart1Rts,
+    Usart1Cts,
+    Usart2Tx,
+    Usart2Rx,
+    Usart2Rts,
+    Usart2Cts,
+    Usart3Tx,
+    Usart3Rx,
+    Usart3Rts,
+    Usart3Cts,
+    I2c1Scl,
+    I2c1Sda,
+    I2c2Scl,
+    I2c2Sda,
+    I2c3Scl,
+    I2c3Sda,
+    I2c4Scl,
+    I2c4Sda,
+    Gptim1Ch1,
+    Gptim1Ch2,
+    Gptim1Ch3,
+    Gptim1Ch4,
+    Gptim1Etr,
+    Gptim2Ch1,
+    Gptim2Ch2,
+    Gptim2Ch3,
+    Gptim2Ch4,
+    Gptim2Etr,
+    Atim1Ch1,
+    Atim1Ch2,
+    Atim1Ch3,
+    Atim1Ch4,
+    Atim1Ch1n,
+    Atim1Ch2n,
+    Atim1Ch3n,
+    Atim1Etr,
+    Atim1Bk,
+    Atim1Bk2,
+    Lptim1In,
+    Lptim1Out,
+    Lptim1Etr,
+    Lptim2In,
+    Lptim2Out,
+    Lptim2Etr,
+}
+
+/// Pad function group a [`Signal`] is routed through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    I2cUart,
+    Tim,
+}
+
+impl Signal {
+    fn kind(self) -> Kind {
+        use Signal::*;
+        match self {
+            Usart1Tx | Usart1Rx | Usart1Rts | Usart1Cts | Usart2Tx | Usart2Rx | Usart2Rts | Usart2Cts
+            | Usart3Tx | Usart3Rx | Usart3Rts | Usart3Cts | I2c1Scl | I2c1Sda | I2c2Scl | I2c2Sda
+            | I2c3Scl | I2c3Sda | I2c4Scl | I2c4Sda => Kind::I2cUart,
+            _ => Kind::Tim,
+        }
+    }
+
+    /// Write `pad` into the pin-select field of this signal.
+    fn select(self, pad: u8) {
+        let cfg = pac::HPSYS_CFG;
+        use Signal::*;
+        match self {
+            Usart1Tx => cfg.usart1_pinr().modify(|w| w.set_txd_pin(pad)),
+            Usart1Rx => cfg.usart1_pinr().modify(|w| w.set_rxd_pin(pad)),
+            Usart1Rts => cfg.usart1_pinr().modify(|w| w.set_rts_pin(pad)),
+            Usart1Cts => cfg.usart1_pinr().modify(|w| w.set_cts_pin(pad)),
+            Usart2Tx => cfg.usart2_pinr().modify(|w| w.set_txd_pin(pad)),
+            Usart2Rx => cfg.usart2_pinr().modify(|w| w.set_rxd_pin(pad)),
+            Usart2Rts => cfg.usart2_pinr().modify(|w| w.set_rts_pin(pad)),
+            Usart2Cts => cfg.usart2_pinr().modify(|w| w.set_cts_pin(pad)),
+            Usart3Tx => cfg.usart3_pinr().modify(|w| w.set_txd_pin(pad)),
+            Usart3Rx => cfg.usart3_pinr().modify(|w| w.set_rxd_pin(pad)),
+            Usart3Rts => cfg.usart3_pinr().modify(|w| w.set_rts_pin(pad)),
+            Usart3Cts => cfg.usart3_pinr().modify(|w| w.set_cts_pin(pad)),
+            I2c1Scl => cfg.i2c1_pinr().modify(|w| w.set_scl_pin(pad)),
+            I2c1Sda => cfg.i2c1_pinr().modify(|w| w.set_sda_pin(pad)),
+            I2c2Scl => cfg.i2c2_pinr().modify(|w| w.set_scl_pin(pad)),
+            I2c2Sda => cfg.i2c2_pinr().modify(|w| w.set_sda_pin(pad)),
+            I2c3Scl => cfg.i2c3_pinr().modify(|w| w.set_scl_pin(pad)),
+            I2c3Sda => cfg.i2c3_pinr().modify(|w| w.set_sda_pin(pad)),
+            I2c4Scl => cfg.i2c4_pinr().modify(|w| w.set_scl_pin(pad)),
+            I2c4Sda => cfg.i2c4_pinr().modify(|w| w.set_sda_pin(pad)),
+            Gptim1Ch1 => cfg.gptim1_pinr().modify(|w| w.set_ch1_pin(pad)),
+            Gptim1Ch2 => cfg.gptim1_pinr().modify(|w| w.set_ch2_pin(pad)),
+            Gptim1Ch3 => cfg.gptim1_pinr().modify(|w| w.set_ch3_pin(pad)),
+            Gptim1Ch4 => cfg.gptim1_pinr().modify(|w| w.set_ch4_pin(pad)),
+            Gptim1Etr => cfg.etr_pinr().modify(|w| w.set_etr1_pin(pad)),
+            Gptim2Ch1 => cfg.gptim2_pinr().modify(|w| w.set_ch1_pin(pad)),
+            Gptim2Ch2 => cfg.gptim2_pinr().modify(|w| w.set_ch2_pin(pad)),
+            Gptim2Ch3 => cfg.gptim2_pinr().modify(|w| w.set_ch3_pin(pad)),
+            Gptim2Ch4 => cfg.gptim2_pinr().modify(|w| w.set_ch4_pin(pad)),
+            Gptim2Etr => cfg.etr_pinr().modify(|w| w.set_etr2_pin(pad)),
+            Atim1Ch1 => cfg.atim1_pinr1().modify(|w| w.set_ch1_pin(pad)),
+            Atim1Ch2 => cfg.atim1_pinr1().modify(|w| w.set_ch2_pin(pad)),
+            Atim1Ch3 => cfg.atim1_pinr1().modify(|w| w.set_ch3_pin(pad)),
+            Atim1Ch4 => cfg.atim1_pinr1().modify(|w| w.set_ch4_pin(pad)),
+            Atim1Ch1n => cfg.atim1_pinr2().modify(|w| w.set_ch1n_pin(pad)),
+            Atim1Ch2n => cfg.atim1_pinr2().modify(|w| w.set_ch2n_pin(pad)),
+            Atim1Ch3n => cfg.atim1_pinr2().modify(|w| w.set_ch3n_pin(pad)),
+            Atim1Etr => cfg.atim1_pinr3().modify(|w| w.set_etr_pin(pad)),
+            Atim1Bk => cfg.atim1_pinr3().modify(|w| w.set_bk_pin(pad)),
+            Atim1Bk2 => cfg.atim1_pinr3().modify(|w| w.set_bk2_pin(pad)),
+            Lptim1In => cfg.lptim1_pinr().modify(|w| w.set_in_pin(pad)),
+            Lptim1Out => cfg.lptim1_pinr().modify(|w| w.set_out_pin(pad)),
+            Lptim1Etr => cfg.lptim1_pinr().modify(|w| w.set_etr_pin(pad)),
+            Lptim2In => cfg.lptim2_pinr().modify(|w| w.set_in_pin(pad)),
+            Lptim2Out => cfg.lptim2_pinr().modify(|w| w.set_out_pin(pad)),
+            Lptim2Etr => cfg.lptim2_pinr().modify(|w| w.set_etr_pin(pad)),
+        }
+    }
+}
+
+/// Routing error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RouteError {
+    /// Another signal is already routed to this pad.
+    PadInUse(Signal),
+    /// The signal is already routed to another pad.
+    SignalInUse(u8),
+    /// The pad has no function for this signal in `pinmux.yaml`.
+    NoFunction,
+}
+
+/// Signal routed to each pad.
+static ROUTES: Mutex<RefCell<[Option<Signal>; PA_PIN_COUNT]>> = Mutex::new(RefCell::new([None; PA_PIN_COUNT]));
+
+/// Route `signal` to `pad`, returning the fsel of the `PAxx_I2C_UART` or
+/// `PAxx_TIM` function the pad must be switched to.
+///
+/// Routing the signal already routed to the pad again is a no-op.
+pub(crate) fn route(pad: u8, signal: Signal) -> Result<u8, RouteError> {
+    let fsel = match signal.kind() {
+        Kind::I2cUart => crate::_generated::i2c_uart_fsel(pad),
+        Kind::Tim => crate::_generated::tim_fsel(pad),
+    }
+    .ok_or(RouteError::NoFunction)?;
+
+    critical_section::with(|cs| {
+        let mut routes = ROUTES.borrow_ref_mut(cs);
+        match routes[pad as usize] {
+            Some(s) if s == signal => return Ok(fsel),
+            Some(s) => return Err(RouteError::PadInUse(s)),
+            None => {}
+        }
+        if let Some(other) = routes.iter().position(|s| *s == Some(signal)) {
+            return Err(RouteError::SignalInUse(other as u8));
+        }
+
+        signal.select(pad);
+        routes[pad as usize] = Some(signal);
+        Ok(fsel)
+    })
+}
+
+/// Pin-select value not matching any pad, used to disconnect a signal.
+const NO_PAD: u8 = 0xFF;
+
+/// Disconnect the signal routed to `pad`, if any.
+pub(crate) fn release(pad: u8) {
+    critical_section::with(|cs| {
+        if let Some(signal) = ROUTES.borrow_ref_mut(cs)[pad as usize].take() {
+            signal.select(NO_PAD);
+        }
+    });
+}
+
+/// Signal currently routed to `pad`.
+pub fn routed_signal(pad: u8) -> Option<Signal> {
+    critical_section::with(|cs| ROUTES.borrow_ref(cs).get(pad as usize).copied().flatten())
+}
@@ -0,0 +1,805 @@
+//! Inter-Integrated-Circuit (I2C) master driver.
+#![macro_use]
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+#[cfg(feature = "time")]
+use embassy_time::{Duration, Instant};
+use embedded_hal_1::i2c::Operation;
+
+use crate::gpio::{AnyPin, Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::mode::{Async, Blocking, Mode};
+use crate::pac::i2c::I2c as Regs;
+use crate::rcc::{RccEnableReset, RccGetFreq};
+use crate::time::Hertz;
+use crate::{interrupt, pac};
+
+/// I2C error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Bus error
+    Bus,
+    /// Arbitration lost
+    Arbitration,
+    /// The address was not acknowledged.
+    NackAddress,
+    /// A data byte was not acknowledged.
+    NackData,
+    /// Timeout
+    Timeout,
+    /// The address does not fit in 7 or 10 bits.
+    AddressOutOfRange(u16),
+}
+
+/// I2C config error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The requested frequency is above 1 MHz (fast-mode plus).
+    FrequencyTooHigh,
+    /// The requested frequency is too low to be reached from the peripheral clock.
+    FrequencyTooLow,
+    /// The peripheral clock is disabled.
+    ClockDisabled,
+}
+
+/// I2C address.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    /// 7-bit address
+    SevenBit(u8),
+    /// 10-bit address
+    TenBit(u16),
+}
+
+/// I2C config.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// SCL frequency.
+    ///
+    /// Up to 100 kHz uses standard mode, up to 400 kHz fast mode and up to
+    /// 1 MHz fast-mode plus.
+    pub frequency: Hertz,
+    /// Enable the internal pull-up on SCL.
+    pub scl_pullup: bool,
+    /// Enable the internal pull-up on SDA.
+    pub sda_pullup: bool,
+    /// Timeout of a whole transaction.
+    #[cfg(feature = "time")]
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            frequency: Hertz(100_000),
+            scl_pullup: false,
+            sda_pullup: false,
+            #[cfg(feature = "time")]
+            timeout: Duration::from_millis(1000),
+        }
+    }
+}
+
+/// Standard mode speed limit.
+const STANDARD_MODE_MAX: u32 = 100_000;
+/// Fast-mode plus speed limit.
+const FAST_MODE_PLUS_MAX: u32 = 1_000_000;
+/// Fixed SCL overhead in clk_peri cycles, on top of twice the load value.
+const SCL_OVERHEAD: u32 = 7;
+/// Time for the stop of an abort to free the bus, in microseconds.
+const ABORT_TIMEOUT_US: u32 = 1_000;
+
+#[derive(Copy, Clone)]
+struct Timeout {
+    #[cfg(feature = "time")]
+    deadline: Instant,
+}
+
+impl Timeout {
+    #[inline]
+    fn check(self) -> Result<(), Error> {
+        #[cfg(feature = "time")]
+        if Instant::now() > self.deadline {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn with<R>(
+        self,
+        fut: impl core::future::Future<Output = Result<R, Error>>,
+    ) -> Result<R, Error> {
+        #[cfg(feature = "time")]
+        {
+            use embassy_futures::select::{select, Either};
+
+            match select(fut, embassy_time::Timer::at(self.deadline)).await {
+                Either::First(r) => r,
+                Either::Second(_) => Err(Error::Timeout),
+            }
+        }
+        #[cfg(not(feature = "time"))]
+        fut.await
+    }
+}
+
+/// Status flag a transfer step waits for.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Flag {
+    /// The data buffer register was shifted out.
+    TxEmpty,
+    /// The data buffer register holds a received byte.
+    RxFull,
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // Disable the interrupts, the future re-enables them on the next poll.
+        T::regs().ier().write(|_| {});
+        T::state().waker.wake();
+    }
+}
+
+/// I2C driver.
+pub struct I2c<'d, T: Instance, M: Mode> {
+    _peri: PeripheralRef<'d, T>,
+    _scl: Flex<'d>,
+    _sda: Flex<'d>,
+    #[cfg(feature = "time")]
+    timeout: Duration,
+    _phantom: PhantomData<M>,
+}
+
+impl<'d, T: Instance> I2c<'d, T, Blocking> {
+    /// Create a new blocking I2C driver.
+    pub fn new_blocking(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        Self::new_inner(peri, scl, sda, config)
+    }
+}
+
+impl<'d, T: Instance> I2c<'d, T, Async> {
+    /// Create a new async I2C driver.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let this = Self::new_inner(peri, scl, sda, config)?;
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        Ok(this)
+    }
+
+    /// Write to address from buffer.
+    pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Write(write)],
+            false,
+        )
+        .await
+    }
+
+    /// Read from address into buffer.
+    pub async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
+        self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Read(read)],
+            false,
+        )
+        .await
+    }
+
+    /// Write to address from `write` and then read from address into `read`,
+    /// with a repeated start in between.
+    pub async fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Error> {
+        self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Write(write), Operation::Read(read)],
+            false,
+        )
+        .await
+    }
+
+    /// Execute the provided operations on the I2C bus.
+    ///
+    /// See [`embedded_hal_1::i2c::I2c::transaction`] for the semantics.
+    pub async fn transaction(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        self.transaction_inner(address, operations, false).await
+    }
+}
+
+impl<'d, T: Instance, M: Mode> I2c<'d, T, M> {
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        scl: impl Peripheral<P = impl SclPin<T>> + 'd,
+        sda: impl Peripheral<P = impl SdaPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(peri, scl, sda);
+
+        let scl_pull = if config.scl_pullup {
+            Pull::Up
+        } else {
+            Pull::None
+        };
+        let sda_pull = if config.sda_pullup {
+            Pull::Up
+        } else {
+            Pull::None
+        };
+        let scl = new_pin::<T>(scl.map_into(), Signal::Scl, scl_pull);
+        let sda = new_pin::<T>(sda.map_into(), Signal::Sda, sda_pull);
+
+        crate::rcc::enable_and_reset::<T>();
+
+        let mut this = Self {
+            _peri: peri,
+            _scl: scl,
+            _sda: sda,
+            #[cfg(feature = "time")]
+            timeout: config.timeout,
+            _phantom: PhantomData,
+        };
+        this.set_config(&config)?;
+        Ok(this)
+    }
+
+    /// Reconfigure the driver.
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let freq = config.frequency.0;
+        if freq > FAST_MODE_PLUS_MAX {
+            return Err(ConfigError::FrequencyTooHigh);
+        }
+        let clk = T::frequency().ok_or(ConfigError::ClockDisabled)?.0;
+        let period = clk / freq.max(1);
+        if period <= SCL_OVERHEAD + 2 {
+            return Err(ConfigError::FrequencyTooHigh);
+        }
+        // SCL period = 2 * LV + overhead, in clk_peri cycles.
+        let lv = (period - SCL_OVERHEAD).div_ceil(2);
+        if lv > 0x1FF {
+            return Err(ConfigError::FrequencyTooLow);
+        }
+        let standard = freq <= STANDARD_MODE_MAX;
+
+        let r = T::regs();
+        r.cr().modify(|w| w.set_iue(false));
+        r.lcr().modify(|w| {
+            if standard {
+                w.set_slv(lv as u16);
+            } else {
+                w.set_flv(lv as u16);
+            }
+        });
+        r.cr().modify(|w| {
+            w.set_mode(if standard { 0 } else { 1 });
+            w.set_scle(true);
+            w.set_iue(true);
+        });
+        #[cfg(feature = "time")]
+        {
+            self.timeout = config.timeout;
+        }
+        Ok(())
+    }
+
+    fn timeout(&self) -> Timeout {
+        Timeout {
+            #[cfg(feature = "time")]
+            deadline: Instant::now() + self.timeout,
+        }
+    }
+
+    /// Blocking write.
+    pub fn blocking_write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
+        embassy_futures::block_on(self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Write(write)],
+            true,
+        ))
+    }
+
+    /// Blocking read.
+    pub fn blocking_read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Error> {
+        embassy_futures::block_on(self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Read(read)],
+            true,
+        ))
+    }
+
+    /// Blocking write, restart, read.
+    pub fn blocking_write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Error> {
+        embassy_futures::block_on(self.transaction_inner(
+            Address::SevenBit(address),
+            &mut [Operation::Write(write), Operation::Read(read)],
+            true,
+        ))
+    }
+
+    /// Blocking transaction.
+    ///
+    /// See [`embedded_hal_1::i2c::I2c::transaction`] for the semantics.
+    pub fn blocking_transaction(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        embassy_futures::block_on(self.transaction_inner(address, operations, true))
+    }
+
+    /// Run a transaction: consecutive operations of the same direction are
+    /// merged, a repeated start is sent on every direction change and a stop
+    /// after the last byte.
+    async fn transaction_inner(
+        &mut self,
+        address: Address,
+        ops: &mut [Operation<'_>],
+        blocking: bool,
+    ) -> Result<(), Error> {
+        match address {
+            Address::SevenBit(a) if a > 0x7F => return Err(Error::AddressOutOfRange(a as u16)),
+            Address::TenBit(a) if a > 0x3FF => return Err(Error::AddressOutOfRange(a)),
+            _ => {}
+        }
+
+        // Release the bus if the future is dropped in the middle of a transaction.
+        let on_drop = OnDrop::new(|| abort::<T>());
+        let timeout = self.timeout();
+        let result = self.run_ops(address, ops, blocking, timeout).await;
+        on_drop.defuse();
+        if result.is_err() {
+            abort::<T>();
+        }
+        result
+    }
+
+    async fn run_ops(
+        &mut self,
+        address: Address,
+        ops: &mut [Operation<'_>],
+        blocking: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        // Empty operations don't put anything on the bus, except for a lone
+        // address probe.
+        let last = match ops.iter().rposition(|op| !is_empty(op)) {
+            Some(last) => last,
+            None => {
+                let read = matches!(ops.first(), Some(Operation::Read(_)));
+                return self
+                    .send_address(address, read, true, blocking, timeout)
+                    .await;
+            }
+        };
+
+        let mut prev_read = None;
+        for i in 0..=last {
+            if is_empty(&ops[i]) {
+                continue;
+            }
+            let is_last_op = i == last;
+            // Reads are merged with the following read, any other next
+            // operation needs a repeated start, so the final byte is nacked.
+            let next_is_read = matches!(
+                ops[i + 1..].iter().find(|op| !is_empty(op)),
+                Some(Operation::Read(_))
+            );
+
+            match &mut ops[i] {
+                Operation::Write(write) => {
+                    if prev_read != Some(false) {
+                        self.send_address(address, false, false, blocking, timeout)
+                            .await?;
+                    }
+                    let n = write.len();
+                    for (j, byte) in write.iter().enumerate() {
+                        let stop = is_last_op && j == n - 1;
+                        T::regs().dbr().write(|w| w.set_data(*byte));
+                        T::regs().tcr().write(|w| {
+                            w.set_tb(true);
+                            w.set_stop(stop);
+                        });
+                        wait_flag::<T>(Flag::TxEmpty, blocking, timeout).await?;
+                        if T::regs().sr().read().acknak() {
+                            return Err(Error::NackData);
+                        }
+                    }
+                    prev_read = Some(false);
+                }
+                Operation::Read(read) => {
+                    if prev_read != Some(true) {
+                        self.send_address(address, true, false, blocking, timeout)
+                            .await?;
+                    }
+                    let n = read.len();
+                    for (j, byte) in read.iter_mut().enumerate() {
+                        let last_byte = j == n - 1;
+                        let stop = is_last_op && last_byte;
+                        let nack = last_byte && !next_is_read;
+                        T::regs().tcr().write(|w| {
+                            w.set_tb(true);
+                            w.set_nack(nack);
+                            w.set_stop(stop);
+                        });
+                        wait_flag::<T>(Flag::RxFull, blocking, timeout).await?;
+                        *byte = T::regs().dbr().read().data();
+                    }
+                    prev_read = Some(true);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a (repeated) start and the address, with a stop right after it if `stop` is set.
+    async fn send_address(
+        &mut self,
+        address: Address,
+        read: bool,
+        stop: bool,
+        blocking: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        match address {
+            Address::SevenBit(a) => {
+                self.send_address_byte((a << 1) | read as u8, true, stop, blocking, timeout)
+                    .await
+            }
+            Address::TenBit(a) => {
+                let header = 0xF0 | ((a >> 7) as u8 & 0x06);
+                // The full address is always sent in write direction first.
+                self.send_address_byte(header, true, false, blocking, timeout)
+                    .await?;
+                self.send_address_byte(a as u8, false, stop && !read, blocking, timeout)
+                    .await?;
+                if read {
+                    // Repeated start with only the header in read direction.
+                    self.send_address_byte(header | 1, true, stop, blocking, timeout)
+                        .await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn send_address_byte(
+        &mut self,
+        byte: u8,
+        start: bool,
+        stop: bool,
+        blocking: bool,
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        let r = T::regs();
+        r.dbr().write(|w| w.set_data(byte));
+        r.tcr().write(|w| {
+            w.set_start(start);
+            w.set_stop(stop);
+            w.set_tb(true);
+        });
+        wait_flag::<T>(Flag::TxEmpty, blocking, timeout).await?;
+        if r.sr().read().acknak() {
+            return Err(Error::NackAddress);
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, M: Mode> Drop for I2c<'d, T, M> {
+    fn drop(&mut self) {
+        T::regs().cr().modify(|w| w.set_iue(false));
+        crate::rcc::disable::<T>();
+    }
+}
+
+fn is_empty(op: &Operation<'_>) -> bool {
+    match op {
+        Operation::Write(w) => w.is_empty(),
+        Operation::Read(r) => r.is_empty(),
+    }
+}
+
+/// Wait until `flag` is set, returning bus errors and arbitration loss.
+async fn wait_flag<T: Instance>(flag: Flag, blocking: bool, timeout: Timeout) -> Result<(), Error> {
+    if blocking {
+        loop {
+            if let Some(res) = check_flag::<T>(flag) {
+                return res;
+            }
+            timeout.check()?;
+        }
+    }
+
+    timeout
+        .with(poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+            match check_flag::<T>(flag) {
+                Some(res) => Poll::Ready(res),
+                None => {
+                    T::regs().ier().write(|w| {
+                        match flag {
+                            Flag::TxEmpty => w.set_teie(true),
+                            Flag::RxFull => w.set_rfie(true),
+                        }
+                        w.set_aldie(true);
+                        w.set_bedie(true);
+                    });
+                    Poll::Pending
+                }
+            }
+        }))
+        .await
+}
+
+fn check_flag<T: Instance>(flag: Flag) -> Option<Result<(), Error>> {
+    let r = T::regs();
+    let sr = r.sr().read();
+    if sr.ald() {
+        r.sr().write(|w| w.set_ald(true));
+        return Some(Err(Error::Arbitration));
+    }
+    let set = match flag {
+        Flag::TxEmpty => sr.te(),
+        Flag::RxFull => sr.rf(),
+    };
+    // BED is also raised when the target nacks, which is reported by the
+    // caller from ACKNAK once the byte is done.
+    if sr.bed() && !sr.acknak() {
+        r.sr().write(|w| w.set_bed(true));
+        return Some(Err(Error::Bus));
+    }
+    if !set {
+        return None;
+    }
+    r.sr().write(|w| {
+        w.set_te(true);
+        w.set_rf(true);
+        w.set_bed(true);
+    });
+    Some(Ok(()))
+}
+
+/// Release the bus after an error: send a stop and drop the pending flags.
+///
+/// Also runs from the drop of a transaction future, so it must not hang: if
+/// the bus is still busy after [`ABORT_TIMEOUT_US`], e.g. a target holding
+/// SDA low, the controller is reset.
+fn abort<T: Instance>() {
+    let r = T::regs();
+    r.ier().write(|_| {});
+    r.tcr().write(|w| w.set_ma(true));
+    let mut waited = 0;
+    while r.sr().read().ub() {
+        if waited == ABORT_TIMEOUT_US {
+            warn!("I2C: bus still busy after abort, resetting the controller");
+            // Unit reset, then restore the configuration.
+            let cr = r.cr().read();
+            r.cr().write(|w| w.set_ur(true));
+            r.cr().write_value(cr);
+            break;
+        }
+        crate::blocking_delay_us(1);
+        waited += 1;
+    }
+    r.sr().write(|w| {
+        w.set_te(true);
+        w.set_rf(true);
+        w.set_ald(true);
+        w.set_bed(true);
+    });
+}
+
+/// I2C signal, used to route a `PAxx_I2C_UART` pad to the right input/output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Signal {
+    Scl,
+    Sda,
+}
+
+fn new_pin<'d, T: Instance>(pin: PeripheralRef<'d, AnyPin>, signal: Signal, pull: Pull) -> Flex<'d> {
+    let mut pin = Flex::new(pin);
+    pin.set_pull(pull);
+    unwrap!(pin.route(T::route_signal(signal)));
+    pin
+}
+
+pub(crate) struct State {
+    waker: AtomicWaker,
+}
+
+impl State {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+trait SealedInstance: RccEnableReset + RccGetFreq {
+    fn regs() -> Regs;
+    fn state() -> &'static State;
+    fn route_signal(signal: Signal) -> crate::gpio::Signal;
+}
+
+/// I2C peripheral instance.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + Peripheral<P = Self> + 'static {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pin_trait!(SclPin, Instance);
+pin_trait!(SdaPin, Instance);
+
+// TODO: move to _generated.rs
+macro_rules! impl_i2c {
+    ($inst:ident, $scl:ident, $sda:ident) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            fn regs() -> Regs {
+                pac::$inst
+            }
+
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
+
+            fn route_signal(signal: Signal) -> crate::gpio::Signal {
+                match signal {
+                    Signal::Scl => crate::gpio::Signal::$scl,
+                    Signal::Sda => crate::gpio::Signal::$sda,
+                }
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$inst;
+        }
+    };
+}
+
+impl_i2c!(I2C1, I2c1Scl, I2c1Sda);
+impl_i2c!(I2C2, I2c2Scl, I2c2Sda);
+impl_i2c!(I2C3, I2c3Scl, I2c3Sda);
+impl_i2c!(I2C4, I2c4Scl, I2c4Sda);
+
+// ====================
+
+mod eh02 {
+    use super::*;
+
+    impl<'d, T: Instance, M: Mode> embedded_hal_02::blocking::i2c::Read for I2c<'d, T, M> {
+        type Error = Error;
+
+        fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+            self.blocking_read(address, buffer)
+        }
+    }
+
+    impl<'d, T: Instance, M: Mode> embedded_hal_02::blocking::i2c::Write for I2c<'d, T, M> {
+        type Error = Error;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.blocking_write(address, bytes)
+        }
+    }
+
+    impl<'d, T: Instance, M: Mode> embedded_hal_02::blocking::i2c::WriteRead for I2c<'d, T, M> {
+        type Error = Error;
+
+        fn write_read(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), Self::Error> {
+            self.blocking_write_read(address, bytes, buffer)
+        }
+    }
+}
+
+impl embedded_hal_1::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal_1::i2c::ErrorKind {
+        use embedded_hal_1::i2c::{ErrorKind, NoAcknowledgeSource};
+
+        match *self {
+            Self::Bus => ErrorKind::Bus,
+            Self::Arbitration => ErrorKind::ArbitrationLoss,
+            Self::NackAddress => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            Self::NackData => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+            Self::Timeout => ErrorKind::Other,
+            Self::AddressOutOfRange(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_1::i2c::ErrorType for I2c<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_1::i2c::I2c<embedded_hal_1::i2c::SevenBitAddress>
+    for I2c<'d, T, M>
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.blocking_transaction(Address::SevenBit(address), operations)
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_1::i2c::I2c<embedded_hal_1::i2c::TenBitAddress>
+    for I2c<'d, T, M>
+{
+    fn transaction(
+        &mut self,
+        address: u16,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.blocking_transaction(Address::TenBit(address), operations)
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_async::i2c::I2c<embedded_hal_async::i2c::SevenBitAddress>
+    for I2c<'d, T, Async>
+{
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction(Address::SevenBit(address), operations)
+            .await
+    }
+}
+
+impl<'d, T: Instance> embedded_hal_async::i2c::I2c<embedded_hal_async::i2c::TenBitAddress>
+    for I2c<'d, T, Async>
+{
+    async fn transaction(
+        &mut self,
+        address: u16,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction(Address::TenBit(address), operations).await
+    }
+}
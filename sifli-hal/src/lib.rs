@@ -6,14 +6,29 @@ use core::arch::global_asm;
 
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
+mod macros;
 
 pub mod rcc;
 pub mod gpio;
+pub mod hpsys_cfg;
 pub mod timer;
 pub mod time;
 pub mod pmu;
+pub mod efuse;
+pub mod usart;
+pub mod dma;
+pub mod spi;
+pub mod i2c;
+pub mod rtc;
+pub mod adc;
+pub mod tsen;
+pub mod trng;
+pub mod aes;
+pub mod wdg;
 #[cfg(feature = "_time-driver")]
 pub mod time_driver;
+#[cfg(feature = "low-power")]
+pub mod low_power;
 
 // Reexports
 pub use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
@@ -22,6 +37,30 @@ pub use sifli_pac as pac;
 #[cfg(not(feature = "unstable-pac"))]
 pub(crate) use sifli_pac as pac;
 
+/// Operating modes for peripherals.
+pub mod mode {
+    trait SealedMode {}
+
+    /// Operating mode for a peripheral.
+    #[allow(private_bounds)]
+    pub trait Mode: SealedMode {}
+
+    macro_rules! impl_mode {
+        ($name:ident) => {
+            impl SealedMode for $name {}
+            impl Mode for $name {}
+        };
+    }
+
+    /// Blocking mode.
+    pub struct Blocking;
+    /// Async mode.
+    pub struct Async;
+
+    impl_mode!(Blocking);
+    impl_mode!(Async);
+}
+
 /// HAL configuration for SiFli
 pub mod config {
     use crate::rcc;
@@ -32,6 +71,7 @@ pub mod config {
     pub struct Config {
         pub rcc: rcc::Config,
         pub gpio1_it_priority: interrupt::Priority,
+        pub dma_it_priority: interrupt::Priority,
     }
 
     impl Default for Config {
@@ -39,6 +79,7 @@ pub mod config {
             Self {
                 rcc: rcc::Config::new_keep(),
                 gpio1_it_priority: interrupt::Priority::P3,
+                dma_it_priority: interrupt::Priority::P3,
             }
         }
     }
@@ -57,16 +98,26 @@ pub fn init(config: Config) -> Peripherals {
     // before doing anything important.
     let p = Peripherals::take();
 
+    // Before anything can reset again
+    wdg::init();
+
     unsafe {
+        // Before any DVFS switch
+        pmu::dvfs::load_calibration();
+
         // rcc::Config::apply()
-        config.rcc.apply();
+        unwrap!(config.rcc.apply(), "invalid clock configuration");
+
+        // Restored by the low-power executor on wakeup.
+        #[cfg(feature = "low-power")]
+        low_power::set_rcc_config(config.rcc);
 
         #[cfg(feature = "_time-driver")]
         time_driver::init();
         
         gpio::init(config.gpio1_it_priority);
 
-        // dma::init();
+        dma::init(config.dma_it_priority);
     }
     p
 }
@@ -0,0 +1,345 @@
+//! Low-power support.
+//!
+//! The [`Executor`] in this module is a drop-in replacement for the default
+//! `embassy_executor::Executor`. When there is nothing to poll, it asks the
+//! time driver how long until the next alarm and picks the deepest state it
+//! can use:
+//!
+//! - the next alarm is less than 250 ms away: plain `WFE`.
+//! - a [`DeepSleepGuard`] is alive: light sleep.
+//! - otherwise: deep sleep.
+//!
+//! In light and deep sleep the HPSYS clocks are stopped. With the
+//! `time-driver-lptim1`/`time-driver-lptim2` features the time driver runs
+//! from `clk_rtc` and keeps counting, and wakes the CPU up by itself. With a
+//! GPTIM or ATIM time driver, an LPTIM must be given to [`stop_with_lptim`] to
+//! wake the CPU up, and the elapsed time is added to the tick counter on wakeup.
+//!
+//! The clock configuration passed to [`crate::init`] is applied again on
+//! wakeup, as the system clock falls back to HRC48 in deep sleep. Use explicit
+//! values rather than `keep` for the clocks that must be restored.
+//!
+//! ```rust,no_run
+//! use embassy_executor::Spawner;
+//!
+//! #[embassy_executor::main(executor = "sifli_hal::low_power::Executor")]
+//! async fn main(_spawner: Spawner) {
+//!     let p = sifli_hal::init(Default::default());
+//!     // ...
+//! }
+//! ```
+
+use core::arch::asm;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, AtomicU32, Ordering};
+
+use cortex_m::peripheral::SCB;
+use critical_section::{CriticalSection, Mutex};
+use embassy_executor::*;
+
+use crate::pac::{self, HPSYS_AON};
+use crate::rcc::{self, RccGetFreq};
+use crate::time_driver::get_driver;
+
+const THREAD_PENDER: usize = usize::MAX;
+
+static mut EXECUTOR: Option<Executor> = None;
+
+/// Number of alive [`DeepSleepGuard`]s.
+static DEEP_SLEEP_BLOCKERS: AtomicU32 = AtomicU32::new(0);
+
+/// Clock configuration restored on wakeup.
+static RCC_CONFIG: Mutex<RefCell<Option<rcc::Config>>> = Mutex::new(RefCell::new(None));
+
+pub(crate) fn set_rcc_config(config: rcc::Config) {
+    critical_section::with(|cs| {
+        RCC_CONFIG.borrow_ref_mut(cs).replace(config);
+    });
+}
+
+/// Update the clock configuration restored on wakeup, after a runtime change
+/// such as [`pmu::dvfs::set_hclk`](crate::pmu::dvfs::set_hclk).
+pub(crate) fn update_rcc_config(f: impl FnOnce(&mut rcc::Config)) {
+    critical_section::with(|cs| {
+        if let Some(config) = RCC_CONFIG.borrow_ref_mut(cs).as_mut() {
+            f(config);
+        }
+    });
+}
+
+/// Prevents the executor from entering deep sleep while alive.
+///
+/// Hold one while a peripheral that doesn't survive deep sleep is in use,
+/// e.g. during a USART reception or a DMA transfer. The executor falls back
+/// to light sleep.
+pub struct DeepSleepGuard {
+    _private: (),
+}
+
+impl DeepSleepGuard {
+    /// Block deep sleep until the returned guard is dropped.
+    pub fn new() -> Self {
+        DEEP_SLEEP_BLOCKERS.fetch_add(1, Ordering::Relaxed);
+        Self { _private: () }
+    }
+}
+
+impl Default for DeepSleepGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for DeepSleepGuard {
+    fn drop(&mut self) {
+        DEEP_SLEEP_BLOCKERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// HPSYS power mode entered on `WFE`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepMode {
+    /// Core clock stopped, peripherals keep running.
+    Idle = 0,
+    /// HPSYS clocks stopped, fast wakeup.
+    LightSleep = 1,
+    /// HPSYS clocks stopped and most of HPSYS powered down, RAM retained.
+    DeepSleep = 2,
+}
+
+fn set_sleep_mode(mode: SleepMode) {
+    HPSYS_AON.pmr().modify(|w| w.set_mode(mode as u8));
+}
+
+trait SealedWakeupInstance {
+    fn regs() -> pac::lptim::Lptim;
+    fn set_wakeup(enable: bool);
+    fn unpend();
+}
+
+/// LPTIM that can wake HPSYS up from deep sleep.
+#[allow(private_bounds)]
+pub trait WakeupInstance: SealedWakeupInstance + RccGetFreq + 'static {}
+
+macro_rules! impl_wakeup {
+    ($inst:ident, $field:ident) => {
+        impl SealedWakeupInstance for crate::peripherals::$inst {
+            fn regs() -> pac::lptim::Lptim {
+                pac::$inst
+            }
+
+            fn set_wakeup(enable: bool) {
+                HPSYS_AON.wer().modify(|w| w.$field(enable));
+            }
+
+            fn unpend() {
+                use crate::interrupt::typelevel::Interrupt;
+                crate::interrupt::typelevel::$inst::unpend();
+            }
+        }
+
+        impl WakeupInstance for crate::peripherals::$inst {}
+    };
+}
+
+impl_wakeup!(LPTIM1, set_lptim1);
+impl_wakeup!(LPTIM2, set_lptim2);
+
+/// Enable waking up from `T` in light and deep sleep.
+#[cfg(feature = "_time-driver-lptim")]
+pub(crate) fn enable_wakeup<T: WakeupInstance>() {
+    T::set_wakeup(true);
+}
+
+/// LPTIM counting the time spent in sleep for a GPTIM or ATIM time driver.
+#[cfg(not(feature = "_time-driver-lptim"))]
+#[derive(Clone, Copy)]
+pub(crate) struct WakeupTimer {
+    regs: pac::lptim::Lptim,
+    clk_rtc: u32,
+    set_wakeup: fn(bool),
+    unpend: fn(),
+}
+
+#[cfg(not(feature = "_time-driver-lptim"))]
+impl WakeupTimer {
+    /// Start counting, and wake up after `requested` at the latest.
+    pub(crate) fn start_wakeup_alarm(&self, requested: embassy_time::Duration, _cs: CriticalSection) {
+        let r = self.regs;
+
+        // Smallest prescaler for which the duration fits the 16-bit counter.
+        let ticks = requested.as_micros() * self.clk_rtc as u64 / 1_000_000;
+        let presc = (0..=7u8).find(|p| ticks >> p <= 0xFFFF).unwrap_or(7);
+        let arr = (ticks >> presc).clamp(1, 0xFFFF) as u32;
+
+        // CFGR and IER can only be written while disabled, ARR only while enabled.
+        r.cr().modify(|w| w.set_enable(false));
+        r.cfgr().write(|w| w.set_presc(presc));
+        r.icr().write(|w| w.set_arrmcf(true));
+        r.ier().write(|w| w.set_arrmie(true));
+        r.cr().modify(|w| w.set_enable(true));
+        r.arr().write(|w| w.set_arr(arr as _));
+
+        (self.set_wakeup)(true);
+        r.cr().modify(|w| w.set_sngstrt(true));
+    }
+
+    /// Stop counting and return the time elapsed since `start_wakeup_alarm`, if it was running.
+    pub(crate) fn stop_wakeup_alarm(&self, _cs: CriticalSection) -> Option<embassy_time::Duration> {
+        let r = self.regs;
+        if !r.cr().read().enable() {
+            return None;
+        }
+
+        let ticks = if r.isr().read().arrm() {
+            r.arr().read().arr() as u64
+        } else {
+            r.cnt().read().cnt() as u64
+        };
+        let presc = r.cfgr().read().presc();
+
+        r.cr().modify(|w| w.set_enable(false));
+        r.icr().write(|w| w.set_arrmcf(true));
+        (self.set_wakeup)(false);
+        // The interrupt is never enabled, it only generates the wakeup event.
+        (self.unpend)();
+
+        Some(embassy_time::Duration::from_micros(
+            (ticks << presc) * 1_000_000 / self.clk_rtc as u64,
+        ))
+    }
+}
+
+/// Use `lptim` to wake up from sleep when the time driver is a GPTIM or ATIM.
+///
+/// Until this is called, the executor doesn't enter light or deep sleep with such
+/// a time driver.
+#[cfg(not(feature = "_time-driver-lptim"))]
+pub fn stop_with_lptim<T: WakeupInstance>(_lptim: T) {
+    let timer = WakeupTimer {
+        regs: T::regs(),
+        clk_rtc: unwrap!(T::frequency(), "clk_rtc is disabled").0,
+        set_wakeup: T::set_wakeup,
+        unpend: T::unpend,
+    };
+    get_driver().set_wakeup_timer(timer);
+}
+
+/// Thread mode executor, using WFE.
+///
+/// This executor allows for low power by entering light or deep sleep
+/// when there is nothing to do and no alarm is due soon.
+///
+/// This will use the LPTIM time driver, or the LPTIM given to [`stop_with_lptim`],
+/// to wake up from sleep.
+pub struct Executor {
+    inner: raw::Executor,
+    not_send: PhantomData<*mut ()>,
+    scb: SCB,
+}
+
+impl Executor {
+    /// Create a new Executor.
+    pub fn take() -> &'static mut Self {
+        critical_section::with(|_| unsafe {
+            assert!(EXECUTOR.is_none());
+
+            EXECUTOR = Some(Self {
+                inner: raw::Executor::new(THREAD_PENDER as *mut ()),
+                not_send: PhantomData,
+                scb: cortex_m::Peripherals::steal().SCB,
+            });
+
+            let executor = EXECUTOR.as_mut().unwrap();
+
+            // Wake up from WFE on pending interrupts, including the wakeup LPTIM
+            // whose interrupt stays disabled.
+            executor.scb.set_sevonpend();
+
+            executor
+        })
+    }
+
+    unsafe fn on_wakeup(&mut self, mode: SleepMode) {
+        set_sleep_mode(SleepMode::Idle);
+        self.scb.clear_sleepdeep();
+
+        // hclk as last seen by the DVFS listeners.
+        let hclk = rcc::clocks().hclk;
+
+        if mode != SleepMode::Idle {
+            crate::wdg::resume_after_sleep();
+            critical_section::with(|cs| {
+                if let Some(config) = RCC_CONFIG.borrow_ref(cs).as_ref() {
+                    // Already applied by `init` or `set_hclk`, so only a ready-wait timeout can fail.
+                    unwrap!(config.apply());
+                }
+            });
+        }
+
+        get_driver().resume_time();
+
+        let clocks = rcc::clocks();
+        if clocks.hclk != hclk {
+            crate::pmu::dvfs::notify(&clocks);
+        }
+    }
+
+    fn configure_pwr(&mut self) -> SleepMode {
+        self.scb.clear_sleepdeep();
+        set_sleep_mode(SleepMode::Idle);
+
+        compiler_fence(Ordering::SeqCst);
+
+        if get_driver().pause_time().is_err() {
+            trace!("low power: not ready to stop");
+            return SleepMode::Idle;
+        }
+
+        let mode = if DEEP_SLEEP_BLOCKERS.load(Ordering::Relaxed) > 0 {
+            SleepMode::LightSleep
+        } else {
+            SleepMode::DeepSleep
+        };
+
+        trace!("low power: enter {}", mode as u8);
+        crate::wdg::pause_for_sleep();
+        set_sleep_mode(mode);
+        self.scb.set_sleepdeep();
+        mode
+    }
+
+    /// Run the executor.
+    ///
+    /// The `init` closure is called with a [`Spawner`] that spawns tasks on
+    /// this executor. Use it to spawn the initial task(s). After `init` returns,
+    /// the executor starts running the tasks.
+    ///
+    /// To spawn more tasks later, you may keep copies of the [`Spawner`] (it is `Copy`),
+    /// for example by passing it as an argument to the initial tasks.
+    ///
+    /// This function requires `&'static mut self`. This means you have to store the
+    /// Executor instance in a place where it'll live forever and grants you mutable
+    /// access. There's a few ways to do this:
+    ///
+    /// - a [StaticCell](https://docs.rs/static_cell/latest/static_cell/) (safe)
+    /// - a `static mut` (unsafe)
+    /// - a local variable in a function you know never returns (like `fn main() -> !`), upgrading its lifetime with `transmute`. (unsafe)
+    ///
+    /// This function never returns.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        let executor = unsafe { EXECUTOR.as_mut().unwrap() };
+        init(executor.inner.spawner());
+
+        loop {
+            unsafe {
+                executor.inner.poll();
+                let mode = self.configure_pwr();
+                asm!("wfe");
+                self.on_wakeup(mode);
+            };
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![macro_use]
+
+macro_rules! pin_trait {
+    ($signal:ident, $instance:path) => {
+        #[doc = concat!(stringify!($signal), " pin trait")]
+        pub trait $signal<T: $instance>: crate::gpio::Pin {
+            #[doc = concat!("Get the pinmux function (fsel) needed to use this pin as ", stringify!($signal))]
+            fn fsel(&self) -> u8;
+        }
+    };
+    ($signal:ident, $instance:path, $channel:path) => {
+        #[doc = concat!(stringify!($signal), " pin trait")]
+        pub trait $signal<T: $instance, C: $channel>: crate::gpio::Pin {
+            #[doc = concat!("Get the pinmux function (fsel) needed to use this pin as ", stringify!($signal))]
+            fn fsel(&self) -> u8;
+        }
+    };
+}
+
+macro_rules! pin_trait_impl {
+    (crate::$mod:ident::$trait:ident, $instance:ident, $channel:ident, $pin:ident, $fsel:expr) => {
+        impl crate::$mod::$trait<crate::peripherals::$instance, crate::$mod::$channel> for crate::peripherals::$pin {
+            fn fsel(&self) -> u8 {
+                $fsel
+            }
+        }
+    };
+    (crate::$mod:ident::$trait:ident, $instance:ident, $pin:ident, $fsel:expr) => {
+        impl crate::$mod::$trait<crate::peripherals::$instance> for crate::peripherals::$pin {
+            fn fsel(&self) -> u8 {
+                $fsel
+            }
+        }
+    };
+}
@@ -1,5 +1,27 @@
+//! Dynamic voltage and frequency scaling of HPSYS.
+//!
+//! hclk can be changed at runtime with [`set_hclk`] or [`set_mode`], e.g. to
+//! boost to 240 MHz for a burst of work and drop to 24 MHz when idle. The
+//! core voltage follows the DVFS mode required by the new frequency.
+//!
+//! Only the clocks derived from hclk change: hclk, pclk1 and pclk2, and the
+//! peripherals clocked by them (GPTIM1, BTIM1). USART, SPI and I2C run from
+//! clk_peri and are not affected. Code depending on hclk can register a
+//! listener with [`add_listener`] to reprogram itself after a change; the
+//! time driver does so when its timer runs from pclk1.
+//!
+//! Thermal management can cap the mode with [`set_mode_ceiling`], e.g. from
+//! the die temperature read by [`crate::tsen`].
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use critical_section::Mutex;
+
 use crate::pac::hpsys_cfg::regs::Ulpmcr;
 use crate::pac::{HPSYS_CFG, PMUC};
+use crate::efuse::VoltageTrims;
+use crate::rcc::{self, ClkSysSel, ClockError, Clocks, ConfigOption, DllConfig};
 use crate::time::Hertz;
 
 // Constants for DVFS mode limits
@@ -8,6 +30,8 @@ pub const HPSYS_DVFS_MODE_D1_LIMIT: u32 = 48;
 pub const HPSYS_DVFS_MODE_S0_LIMIT: u32 = 144;
 pub const HPSYS_DVFS_MODE_S1_LIMIT: u32 = 240;
 
+/// `ldo` codes of `HPSYS_DVFS_CONFIG` are for an uncalibrated chip, `ldo_offset`
+/// is relative to the 1.1 V code, read from the eFuses by [`load_calibration`].
 pub const HPSYS_DVFS_CONFIG: [HpsysDvfsConfig; 4] = [
     // LDO: 0.9V, BUCK: 1.0V
     HpsysDvfsConfig { ldo_offset: -5, ldo: 0x6, buck: 0x9, ulpmcr: 0x00100330 },
@@ -74,7 +98,7 @@ impl HpsysDvfsMode {
     }
 
     pub fn get_frequency_limit(self) -> Hertz {
-        Hertz(match self {
+        Hertz::mhz(match self {
             HpsysDvfsMode::D0 => HPSYS_DVFS_MODE_D0_LIMIT,
             HpsysDvfsMode::D1 => HPSYS_DVFS_MODE_D1_LIMIT,
             HpsysDvfsMode::S0 => HPSYS_DVFS_MODE_S0_LIMIT,
@@ -91,16 +115,14 @@ pub struct HpsysDvfsConfig {
     pub ulpmcr: u32,
 }
 
-pub(crate) fn config_hcpu_dvfs<F>(
-    current_hclk_freq: Hertz,
-    target_hclk_freq: Hertz,
+pub(crate) fn config_hcpu_dvfs<F, R>(
+    current_dvfs_mode: HpsysDvfsMode,
+    target_dvfs_mode: HpsysDvfsMode,
     config_clock: F,
-) where
-    F: FnOnce(),
+) -> R
+where
+    F: FnOnce() -> R,
 { 
-    let current_dvfs_mode = HpsysDvfsMode::from_hertz(current_hclk_freq).unwrap();
-    let target_dvfs_mode = HpsysDvfsMode::from_hertz(target_hclk_freq).unwrap();
-
     use HpsysDvfsMode::*;
     match (current_dvfs_mode, target_dvfs_mode) {
         (D0, D0) | (D1, D1) | (S0, S0) | (S1, S1) => config_clock(),
@@ -113,18 +135,48 @@ pub(crate) fn config_hcpu_dvfs<F>(
             config_hcpu_sx_mode_volt(target_dvfs_mode);
             // buck need 250us to settle
             crate::cortex_m_blocking_delay_us(250);
-            config_clock();
-        },
-        (D0, D1) | (D1, D0) => {
-            // TODO:
-            // switch between different D mode
-            // Why?
-            // switch_hcpu_dvfs_d2s(target_dvfs_mode, todo_set_to_144);
-            switch_hcpu_dvfs_s2d(target_dvfs_mode, config_clock);
+            config_clock()
         },
+        (D0, D1) | (D1, D0) => switch_hcpu_dvfs_d2d(current_dvfs_mode, target_dvfs_mode, config_clock),
     }
 }
 
+/// HPSYS LDO code giving 1.1 V.
+static LDO_VOUT_REF: AtomicU8 = AtomicU8::new(HPSYS_DVFS_CONFIG[HpsysDvfsMode::S0 as usize].ldo);
+
+/// Load the per-chip voltage trims from the eFuses.
+///
+/// Called by `init`, before configuring the clocks. Uncalibrated chips keep the
+/// nominal codes of [`HPSYS_DVFS_CONFIG`].
+pub(crate) fn load_calibration() {
+    match VoltageTrims::read() {
+        Ok(Some(trims)) => {
+            LDO_VOUT_REF.store(trims.hpsys_ldo_vout, Ordering::Relaxed);
+            PMUC.buck_cr1().modify(|w| {
+                w.set_vos_trim(trims.buck_vos_trim);
+                w.set_vos_polar(trims.buck_vos_polar);
+            });
+
+            // The bootloader may have left an S mode with nominal voltages, which
+            // isn't reprogrammed if the mode doesn't change.
+            let mode = rcc::get_hclk_freq().and_then(|f| HpsysDvfsMode::from_hertz(f).ok());
+            if let (true, Some(mode @ (HpsysDvfsMode::S0 | HpsysDvfsMode::S1))) = (is_hpsys_dvfs_mode_s(), mode) {
+                config_hcpu_sx_mode_volt(mode);
+                // buck need 250us to settle
+                crate::cortex_m_blocking_delay_us(250);
+            }
+        }
+        Ok(None) => warn!("no voltage trims in eFuse, using nominal DVFS voltages"),
+        Err(e) => warn!("failed to read voltage trims: {:?}, using nominal DVFS voltages", e),
+    }
+}
+
+/// Calibrated LDO code of `mode`, i.e. `HAL_PMU_GetHpsysVoutRef` plus the mode offset.
+fn ldo_vout(mode: HpsysDvfsMode) -> u8 {
+    let vout = LDO_VOUT_REF.load(Ordering::Relaxed) as i8 + mode.get_config().ldo_offset;
+    vout.clamp(0, 0xF) as u8
+}
+
 fn config_hcpu_sx_mode_volt(target_dvfs_mode: HpsysDvfsMode) {
     let dvfs_config = target_dvfs_mode.get_config();
 
@@ -134,18 +186,17 @@ fn config_hcpu_sx_mode_volt(target_dvfs_mode: HpsysDvfsMode) {
     });
 
     // configure LDO voltage
-    // TODO: use efuse value (HAL_PMU_GetHpsysVoutRef[2])
-    let vout_ref = dvfs_config.ldo;
     PMUC.hpsys_vout().modify(| w| {
-        w.set_vout(vout_ref);
+        w.set_vout(ldo_vout(target_dvfs_mode));
     });
 }
 
-fn switch_hcpu_dvfs_d2s<F>(
+fn switch_hcpu_dvfs_d2s<F, R>(
     target_dvfs_mode: HpsysDvfsMode,
     config_clock: F,
-) where
-    F: FnOnce(),
+) -> R
+where
+    F: FnOnce() -> R,
 { 
     config_hcpu_sx_mode_volt(target_dvfs_mode);
     // Switch to S mode
@@ -156,14 +207,15 @@ fn switch_hcpu_dvfs_d2s<F>(
     // buck need 250us to settle
     crate::cortex_m_blocking_delay_us(250);
 
-    config_clock();
+    config_clock()
 }
 
-fn switch_hcpu_dvfs_s2d<F>(
+fn switch_hcpu_dvfs_s2d<F, R>(
     target_dvfs_mode: HpsysDvfsMode,
     config_clock: F,
-) where
-    F: FnOnce(),
+) -> R
+where
+    F: FnOnce() -> R,
 {
     let dvfs_config = target_dvfs_mode.get_config();
     
@@ -173,13 +225,11 @@ fn switch_hcpu_dvfs_s2d<F>(
     });
 
     // configure LDO voltage
-    // TODO: use efuse value (HAL_PMU_GetHpsysVoutRef[2])
-    let vout_ref = dvfs_config.ldo;
     PMUC.hpsys_ldo().modify(| w| {
-        w.set_vref(vout_ref + dvfs_config.ldo_offset as u8);
+        w.set_vref(ldo_vout(target_dvfs_mode));
     });
 
-    config_clock();
+    let result = config_clock();
 
     // configure memory param
     HPSYS_CFG.ulpmcr().write_value(Ulpmcr(dvfs_config.ulpmcr));
@@ -187,4 +237,235 @@ fn switch_hcpu_dvfs_s2d<F>(
     HPSYS_CFG.syscr().modify(|w| {
         w.set_ldo_vsel(true);
     });
+
+    result
+}
+
+/// Switch between D0 and D1, both running from the LDO.
+///
+/// The voltage is raised before the clock, and lowered after it.
+fn switch_hcpu_dvfs_d2d<F, R>(
+    current_dvfs_mode: HpsysDvfsMode,
+    target_dvfs_mode: HpsysDvfsMode,
+    config_clock: F,
+) -> R
+where
+    F: FnOnce() -> R,
+{
+    let dvfs_config = target_dvfs_mode.get_config();
+
+    if target_dvfs_mode as u8 > current_dvfs_mode as u8 {
+        PMUC.hpsys_ldo().modify(|w| {
+            w.set_vref(ldo_vout(target_dvfs_mode));
+        });
+        // LDO settling time
+        crate::cortex_m_blocking_delay_us(20);
+
+        // configure memory param
+        HPSYS_CFG.ulpmcr().write_value(Ulpmcr(dvfs_config.ulpmcr));
+        config_clock()
+    } else {
+        let result = config_clock();
+
+        // configure memory param
+        HPSYS_CFG.ulpmcr().write_value(Ulpmcr(dvfs_config.ulpmcr));
+        PMUC.hpsys_ldo().modify(|w| {
+            w.set_vref(ldo_vout(target_dvfs_mode));
+        });
+        result
+    }
+}
+
+/// DVFS error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DvfsError {
+    /// No clock configuration gives this hclk frequency.
+    UnsupportedFrequency(Hertz),
+    /// The clock configuration is invalid or failed to apply.
+    Clock(ClockError),
+    /// All listener slots are taken.
+    TooManyListeners,
+    /// The frequency is above the limit of the mode ceiling.
+    AboveCeiling(HpsysDvfsMode),
+}
+
+impl From<ClockError> for DvfsError {
+    fn from(e: ClockError) -> Self {
+        DvfsError::Clock(e)
+    }
+}
+
+/// Called after hclk changed, with the new clocks.
+pub type Listener = fn(&Clocks);
+
+const MAX_LISTENERS: usize = 8;
+
+static LISTENERS: Mutex<RefCell<[Option<Listener>; MAX_LISTENERS]>> =
+    Mutex::new(RefCell::new([None; MAX_LISTENERS]));
+
+/// Handle of a registered listener, to unregister it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ListenerId(usize);
+
+/// Register `listener` to be called after every hclk change made by [`set_hclk`] or [`set_mode`].
+///
+/// Listeners run in the context of the caller of `set_hclk`.
+pub fn add_listener(listener: Listener) -> Result<ListenerId, DvfsError> {
+    critical_section::with(|cs| {
+        let mut listeners = LISTENERS.borrow_ref_mut(cs);
+        let n = listeners
+            .iter()
+            .position(|l| l.is_none())
+            .ok_or(DvfsError::TooManyListeners)?;
+        listeners[n] = Some(listener);
+        Ok(ListenerId(n))
+    })
+}
+
+/// Unregister a listener added with [`add_listener`].
+pub fn remove_listener(id: ListenerId) {
+    critical_section::with(|cs| {
+        LISTENERS.borrow_ref_mut(cs)[id.0] = None;
+    })
+}
+
+pub(crate) fn notify(clocks: &Clocks) {
+    // Copied out, so that a listener can add or remove listeners.
+    let listeners = critical_section::with(|cs| *LISTENERS.borrow_ref(cs));
+    for listener in listeners.iter().flatten() {
+        listener(clocks);
+    }
+}
+
+/// Switch hclk to `freq`, changing the DVFS mode as needed, then notify the listeners.
+///
+/// Frequencies up to 48 MHz divide hxt48, so they must divide 48 MHz by at
+/// most 255. Higher frequencies use DLL1 and must be multiples of 24 MHz.
+/// pclk1 and pclk2 keep their dividers.
+///
+/// DLL2, which usually clocks the flash and PSRAM, must fit the limit of the
+/// new mode (see [`HpsysDvfsMode::get_dll2_limit`]), otherwise
+/// [`ClockError::Dll2ExceedsDvfsLimit`] is returned and nothing changes.
+///
+/// With the `low-power` feature, the new hclk is also the one restored after
+/// a deep sleep, so it stays within the mode ceiling.
+pub fn set_hclk(freq: Hertz) -> Result<Clocks, DvfsError> {
+    if let Some(ceiling) = mode_ceiling() {
+        if freq > ceiling.get_frequency_limit() {
+            return Err(DvfsError::AboveCeiling(ceiling));
+        }
+    }
+
+    let mut config = rcc::Config::new_keep();
+
+    if freq.0 > 0 && freq.0 <= 48_000_000 && 48_000_000 % freq.0 == 0 {
+        let div = u8::try_from(48_000_000 / freq.0).map_err(|_| DvfsError::UnsupportedFrequency(freq))?;
+        config.clk_sys_sel = ConfigOption::Update(ClkSysSel::Hxt48);
+        config.hclk_div = ConfigOption::Update(div);
+        unsafe { config.apply() }?;
+    } else if freq.0 % 24_000_000 == 0 && (2..=16).contains(&(freq.0 / 24_000_000)) {
+        config.dll1 = ConfigOption::Update(DllConfig {
+            enable: true,
+            stg: (freq.0 / 24_000_000 - 1) as u8,
+            div2: false,
+        });
+        config.clk_sys_sel = ConfigOption::Update(ClkSysSel::Dll1);
+        config.hclk_div = ConfigOption::Update(1);
+
+        // DLL1 can't be reprogrammed while clocking the core, move to hxt48 first.
+        // That step stays in the current mode: hxt48 is slower, and the DLL2
+        // limit of the D modes would reject the usual PSRAM clock.
+        if rcc::get_clk_sys_source() == ClkSysSel::Dll1 {
+            let mode = rcc::current_dvfs_mode();
+            let mut hxt48 = rcc::Config::new_keep();
+            hxt48.clk_sys_sel = ConfigOption::Update(ClkSysSel::Hxt48);
+            hxt48.hclk_div = ConfigOption::Update(1);
+
+            // Check the final configuration up front, so that a failing plan
+            // doesn't leave the core on hxt48.
+            let state = rcc::ClockState::read();
+            hxt48.plan_in_mode_from(&state, mode)?;
+            config.plan_from(&state)?;
+            unsafe { hxt48.apply_in_mode(mode) }?;
+            unsafe { config.apply_from_mode(mode) }?;
+        } else {
+            unsafe { config.apply() }?;
+        }
+    } else {
+        return Err(DvfsError::UnsupportedFrequency(freq));
+    }
+
+    // Restore this hclk, not the one of `init`, after a deep sleep.
+    #[cfg(feature = "low-power")]
+    crate::low_power::update_rcc_config(|stored| {
+        stored.clk_sys_sel = config.clk_sys_sel;
+        stored.hclk_div = config.hclk_div;
+        if config.dll1.is_update() {
+            stored.dll1 = config.dll1;
+        }
+    });
+
+    let clocks = rcc::clocks();
+    notify(&clocks);
+    Ok(clocks)
+}
+
+/// Mode ceiling set by [`set_mode_ceiling`], `NO_CEILING` if none.
+static MODE_CEILING: AtomicU8 = AtomicU8::new(NO_CEILING);
+const NO_CEILING: u8 = u8::MAX;
+
+/// Get the mode ceiling set by [`set_mode_ceiling`].
+pub fn mode_ceiling() -> Option<HpsysDvfsMode> {
+    match MODE_CEILING.load(Ordering::Relaxed) {
+        0 => Some(HpsysDvfsMode::D0),
+        1 => Some(HpsysDvfsMode::D1),
+        2 => Some(HpsysDvfsMode::S0),
+        3 => Some(HpsysDvfsMode::S1),
+        _ => None,
+    }
+}
+
+/// Cap hclk to the limit of `ceiling`, or remove the cap with `None`.
+///
+/// [`set_hclk`] then rejects higher frequencies. If hclk is currently above
+/// the limit, it is lowered to the limit right away and the new clocks are returned.
+pub fn set_mode_ceiling(ceiling: Option<HpsysDvfsMode>) -> Result<Option<Clocks>, DvfsError> {
+    MODE_CEILING.store(ceiling.map_or(NO_CEILING, |m| m as u8), Ordering::Relaxed);
+
+    match (ceiling, rcc::get_hclk_freq()) {
+        (Some(ceiling), Some(hclk)) if hclk > ceiling.get_frequency_limit() => set_mode(ceiling).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Switch hclk to the highest frequency of `mode`, see [`set_hclk`].
+pub fn set_mode(mode: HpsysDvfsMode) -> Result<Clocks, DvfsError> {
+    set_hclk(mode.get_frequency_limit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_from_frequency_edges() {
+        assert_eq!(HpsysDvfsMode::from_frequency(0), Ok(HpsysDvfsMode::D0));
+        assert_eq!(HpsysDvfsMode::from_frequency(24), Ok(HpsysDvfsMode::D0));
+        assert_eq!(HpsysDvfsMode::from_frequency(25), Ok(HpsysDvfsMode::D1));
+        assert_eq!(HpsysDvfsMode::from_frequency(48), Ok(HpsysDvfsMode::D1));
+        assert_eq!(HpsysDvfsMode::from_frequency(49), Ok(HpsysDvfsMode::S0));
+        assert_eq!(HpsysDvfsMode::from_frequency(144), Ok(HpsysDvfsMode::S0));
+        assert_eq!(HpsysDvfsMode::from_frequency(145), Ok(HpsysDvfsMode::S1));
+        assert_eq!(HpsysDvfsMode::from_frequency(240), Ok(HpsysDvfsMode::S1));
+        assert!(HpsysDvfsMode::from_frequency(241).is_err());
+    }
+
+    #[test]
+    fn mode_limits_round_trip() {
+        for mode in [HpsysDvfsMode::D0, HpsysDvfsMode::D1, HpsysDvfsMode::S0, HpsysDvfsMode::S1] {
+            assert_eq!(HpsysDvfsMode::from_hertz(mode.get_frequency_limit()), Ok(mode));
+        }
+    }
 }
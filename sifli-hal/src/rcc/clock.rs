@@ -1,5 +1,13 @@
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use critical_section::Mutex;
+
 use crate::time::Hertz;
-use crate::pac::{HPSYS_RCC, HPSYS_AON};
+use crate::pac::{HPSYS_RCC, HPSYS_AON, PMUC};
+use crate::pmu::dvfs::HpsysDvfsMode;
+
+use super::{ClockState, DllConfig, UsbConfig};
 
 pub use crate::pac::hpsys_rcc::vals::{
     SelSys as ClkSysSel,
@@ -13,17 +21,38 @@ pub use crate::pac::hpsys_rcc::vals::{
 // clk_aud_pll, aud_pll_div16
 // hxt48, hrc48
 // clk_dll1, clk_dll2
-// clk_rtc(TODO), clk_wdt(TODO)
+// clk_rtc, clk_wdt
 // hclk, pclk1, pclk2
 // clk_usb
-// TODO: lxt32, lrc32, lrc10
+// lxt32, lrc10, lrc32
+// dbl96
+
+/// Source of clk_rtc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcSel {
+    Lrc10,
+    Lxt32,
+}
+
+/// Source of clk_wdt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WdtSel {
+    Lrc10,
+    Lrc32,
+}
+
+/// LRC frequencies measured by the last calibration, 0 if not calibrated.
+pub(crate) static LRC10_CALIBRATED: AtomicU32 = AtomicU32::new(0);
+pub(crate) static LRC32_CALIBRATED: AtomicU32 = AtomicU32::new(0);
 
 /// clk_sys
 pub fn get_clk_sys_freq() -> Option<Hertz> {
     match HPSYS_RCC.csr().read().sel_sys() {
         ClkSysSel::Hrc48 => get_hrc48_freq(),
         ClkSysSel::Hxt48 => get_hxt48_freq(),
-        ClkSysSel::Dbl96 => todo!(),
+        ClkSysSel::Dbl96 => get_dbl96_freq(),
         ClkSysSel::Dll1 => get_clk_dll1_freq(),
     }
 }
@@ -47,7 +76,8 @@ pub fn get_clk_peri_div2_freq() -> Option<Hertz> {
 
 pub fn get_hclk_freq() -> Option<Hertz> {
     let clk_sys = get_clk_sys_freq()?;
-    Some(clk_sys / HPSYS_RCC.cfgr().read().hdiv())
+    // HDIV 0 doesn't divide, same as 1.
+    Some(clk_sys / HPSYS_RCC.cfgr().read().hdiv().max(1))
 }
 
 pub fn get_hclk_div() -> u8 {
@@ -80,6 +110,15 @@ pub fn get_hrc48_freq() -> Option<Hertz> {
     }
 }
 
+/// hxt48 doubled.
+pub fn get_dbl96_freq() -> Option<Hertz> {
+    if HPSYS_RCC.dbl96cr().read().rdy() {
+        get_hxt48_freq().map(|f| f * 2u32)
+    } else {
+        None
+    }
+}
+
 pub fn get_clk_dll1_freq() -> Option<Hertz> {
     let dllcr = HPSYS_RCC.dllcr(0).read();
     if dllcr.en() {
@@ -113,42 +152,232 @@ pub fn get_clk_aud_pll_div16_freq() -> Option<Hertz> {
     Some(Hertz(49_152_000 / 16))
 }
 
+/// clk_wdt. Clocks the watchdogs, and keeps running in deep sleep.
 pub fn get_clk_wdt_freq() -> Option<Hertz> {
-    todo!()
+    match get_clk_wdt_source() {
+        WdtSel::Lrc10 => get_lrc10_freq(),
+        WdtSel::Lrc32 => get_lrc32_freq(),
+    }
 }
 
+pub fn get_clk_wdt_source() -> WdtSel {
+    if PMUC.cr().read().sel_wdtclk() {
+        WdtSel::Lrc32
+    } else {
+        WdtSel::Lrc10
+    }
+}
+
+/// clk_rtc, also named lpclk. Clocks the RTC and the LPTIMs, and keeps running in deep sleep.
 pub fn get_clk_rtc_freq() -> Option<Hertz> {
-    todo!()
-}
-
-pub fn test_print_clocks() {
-    info!("Clock frequencies:");
-    
-    let clocks = [
-        ("clk_sys", get_clk_sys_freq()),
-        ("clk_peri", get_clk_peri_freq()),
-        ("clk_peri_div2", get_clk_peri_div2_freq()),
-        ("hclk", get_hclk_freq()),
-        ("pclk1", get_pclk1_freq()),
-        ("pclk2", get_pclk2_freq()),
-        ("hxt48", get_hxt48_freq()),
-        ("hrc48", get_hrc48_freq()),
-        ("clk_dll1", get_clk_dll1_freq()),
-        ("clk_dll2", get_clk_dll2_freq()),
-        ("clk_usb", get_clk_usb_freq()),
-        ("clk_aud_pll", get_clk_aud_pll_freq()),
-    ];
-
-    for (name, freq) in clocks {
-        if let Some(f) = freq {
-            let freq_khz = f.0 / 1_000;
-            info!("{}: {}.{:03} MHz", 
-                name,
-                freq_khz / 1_000,
-                freq_khz % 1_000
-            );
-        } else {
-            info!("{}: disabled", name);
+    match get_clk_rtc_source() {
+        RtcSel::Lxt32 => get_lxt32_freq(),
+        RtcSel::Lrc10 => get_lrc10_freq(),
+    }
+}
+
+pub fn get_clk_rtc_source() -> RtcSel {
+    if PMUC.cr().read().sel_lpclk() {
+        RtcSel::Lxt32
+    } else {
+        RtcSel::Lrc10
+    }
+}
+
+pub fn get_lxt32_freq() -> Option<Hertz> {
+    if PMUC.lxt_cr().read().rdy() {
+        Some(Hertz(32_768))
+    } else {
+        None
+    }
+}
+
+/// Calibrated frequency if [`Config::lrc_calibration`](super::Config::lrc_calibration) was set,
+/// otherwise the nominal one, which varies between chips and with temperature.
+pub fn get_lrc10_freq() -> Option<Hertz> {
+    if PMUC.lrc10_cr().read().rdy() {
+        Some(Hertz(calibrated_or(&LRC10_CALIBRATED, 10_000)))
+    } else {
+        None
+    }
+}
+
+/// Calibrated frequency if [`Config::lrc_calibration`](super::Config::lrc_calibration) was set,
+/// otherwise the nominal one, which varies between chips and with temperature.
+pub fn get_lrc32_freq() -> Option<Hertz> {
+    if PMUC.lrc32_cr().read().rdy() {
+        Some(Hertz(calibrated_or(&LRC32_CALIBRATED, 32_000)))
+    } else {
+        None
+    }
+}
+
+impl ClockState {
+    /// Read the current clock configuration from the hardware.
+    pub fn read() -> Self {
+        let dll = |n: usize| {
+            let dllcr = HPSYS_RCC.dllcr(n).read();
+            DllConfig {
+                enable: dllcr.en(),
+                stg: dllcr.stg(),
+                div2: dllcr.out_div2_en(),
+            }
+        };
+        let cfgr = HPSYS_RCC.cfgr().read();
+        let csr = HPSYS_RCC.csr().read();
+
+        Self {
+            hxt48: HPSYS_AON.acr().read().hxt48_rdy(),
+            hrc48: HPSYS_AON.acr().read().hrc48_rdy(),
+            lxt32: PMUC.lxt_cr().read().rdy(),
+            lrc10: PMUC.lrc10_cr().read().rdy(),
+            lrc32: PMUC.lrc32_cr().read().rdy(),
+            dbl96: HPSYS_RCC.dbl96cr().read().rdy(),
+            dll1: dll(0),
+            dll2: dll(1),
+            clk_sys_sel: csr.sel_sys(),
+            hclk_div: cfgr.hdiv(),
+            pclk1_div: cfgr.pdiv1(),
+            pclk2_div: cfgr.pdiv2(),
+            usb: UsbConfig {
+                sel: csr.sel_usbc(),
+                div: HPSYS_RCC.usbcr().read().div(),
+            },
+            clk_peri_sel: csr.sel_peri(),
+            clk_rtc_sel: get_clk_rtc_source(),
+            clk_wdt_sel: get_clk_wdt_source(),
+        }
+    }
+}
+
+fn calibrated_or(calibrated: &AtomicU32, nominal: u32) -> u32 {
+    match calibrated.load(Ordering::Relaxed) {
+        0 => nominal,
+        f => f,
+    }
+}
+
+/// Frozen snapshot of all clock frequencies, `None` for the disabled ones.
+///
+/// Computed by [`Config::apply`](super::Config::apply), usually from [`crate::init`],
+/// and returned by [`clocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clocks {
+    pub clk_sys: Option<Hertz>,
+    pub hclk: Option<Hertz>,
+    pub pclk1: Option<Hertz>,
+    pub pclk2: Option<Hertz>,
+    pub clk_peri: Option<Hertz>,
+    pub clk_peri_div2: Option<Hertz>,
+    pub hxt48: Option<Hertz>,
+    pub hrc48: Option<Hertz>,
+    pub dbl96: Option<Hertz>,
+    pub clk_dll1: Option<Hertz>,
+    pub clk_dll2: Option<Hertz>,
+    pub clk_usb: Option<Hertz>,
+    pub clk_aud_pll: Option<Hertz>,
+    pub clk_aud_pll_div16: Option<Hertz>,
+    pub clk_rtc: Option<Hertz>,
+    pub clk_wdt: Option<Hertz>,
+    pub lxt32: Option<Hertz>,
+    pub lrc10: Option<Hertz>,
+    pub lrc32: Option<Hertz>,
+    /// DVFS mode of hclk, `None` when overclocked.
+    pub dvfs_mode: Option<HpsysDvfsMode>,
+}
+
+static CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+
+impl Clocks {
+    /// Read all clock frequencies from the hardware.
+    pub fn read() -> Self {
+        let hclk = get_hclk_freq();
+        Self {
+            clk_sys: get_clk_sys_freq(),
+            hclk,
+            pclk1: get_pclk1_freq(),
+            pclk2: get_pclk2_freq(),
+            clk_peri: get_clk_peri_freq(),
+            clk_peri_div2: get_clk_peri_div2_freq(),
+            hxt48: get_hxt48_freq(),
+            hrc48: get_hrc48_freq(),
+            dbl96: get_dbl96_freq(),
+            clk_dll1: get_clk_dll1_freq(),
+            clk_dll2: get_clk_dll2_freq(),
+            clk_usb: get_clk_usb_freq(),
+            clk_aud_pll: get_clk_aud_pll_freq(),
+            clk_aud_pll_div16: get_clk_aud_pll_div16_freq(),
+            clk_rtc: get_clk_rtc_freq(),
+            clk_wdt: get_clk_wdt_freq(),
+            lxt32: get_lxt32_freq(),
+            lrc10: get_lrc10_freq(),
+            lrc32: get_lrc32_freq(),
+            dvfs_mode: hclk.and_then(|f| HpsysDvfsMode::from_hertz(f).ok()),
+        }
+    }
+
+    fn entries(&self) -> [(&'static str, Option<Hertz>); 19] {
+        [
+            ("clk_sys", self.clk_sys),
+            ("hclk", self.hclk),
+            ("pclk1", self.pclk1),
+            ("pclk2", self.pclk2),
+            ("clk_peri", self.clk_peri),
+            ("clk_peri_div2", self.clk_peri_div2),
+            ("hxt48", self.hxt48),
+            ("hrc48", self.hrc48),
+            ("dbl96", self.dbl96),
+            ("clk_dll1", self.clk_dll1),
+            ("clk_dll2", self.clk_dll2),
+            ("clk_usb", self.clk_usb),
+            ("clk_aud_pll", self.clk_aud_pll),
+            ("clk_aud_pll_div16", self.clk_aud_pll_div16),
+            ("clk_rtc", self.clk_rtc),
+            ("clk_wdt", self.clk_wdt),
+            ("lxt32", self.lxt32),
+            ("lrc10", self.lrc10),
+            ("lrc32", self.lrc32),
+        ]
+    }
+}
+
+impl core::fmt::Display for Clocks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Clock frequencies:")?;
+        for (name, freq) in self.entries() {
+            match freq {
+                Some(freq) => writeln!(f, "{}: {}.{:03} MHz", name, freq.0 / 1_000_000, freq.0 / 1_000 % 1_000)?,
+                None => writeln!(f, "{}: disabled", name)?,
+            }
         }
+        write!(f, "dvfs_mode: {:?}", self.dvfs_mode)
     }
 }
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Clocks {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "Clock frequencies:\n");
+        for (name, freq) in self.entries() {
+            match freq {
+                Some(freq) => defmt::write!(f, "{}: {}.{:03} MHz\n", name, freq.0 / 1_000_000, freq.0 / 1_000 % 1_000),
+                None => defmt::write!(f, "{}: disabled\n", name),
+            }
+        }
+        defmt::write!(f, "dvfs_mode: {}", self.dvfs_mode);
+    }
+}
+
+/// Get the clock frequencies.
+///
+/// This is cheap: the snapshot taken by the last [`Config::apply`](super::Config::apply)
+/// is returned, the registers are only read before the first one.
+pub fn clocks() -> Clocks {
+    critical_section::with(|cs| CLOCKS.borrow(cs).get()).unwrap_or_else(Clocks::read)
+}
+
+/// Take a new snapshot of the clock frequencies, after changing the clock configuration.
+pub(crate) fn refresh_clocks() {
+    let clocks = Clocks::read();
+    critical_section::with(|cs| CLOCKS.borrow(cs).set(Some(clocks)));
+}
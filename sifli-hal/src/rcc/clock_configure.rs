@@ -1,7 +1,12 @@
 use crate::pac::{HPSYS_RCC, HPSYS_AON, HPSYS_CFG, PMUC};
-use crate::time::Hertz;
+use crate::pmu::dvfs::HpsysDvfsMode;
 
-use super::{ClkSysSel, ClkPeriSel, UsbSel, TickSel};
+use super::{ClkSysSel, ClkPeriSel, UsbSel, TickSel, RtcSel, WdtSel, ClockError, ClockPlan, ClockState};
+
+/// Ready-wait timeouts, in microseconds.
+const HS_READY_TIMEOUT_US: u32 = 10_000;
+const LS_READY_TIMEOUT_US: u32 = 2_000_000;
+const DLL_READY_TIMEOUT_US: u32 = 1_000;
 
 /// Represents a configuration value that can either be updated with a new value
 /// or kept unchanged from its previous state.
@@ -48,6 +53,14 @@ pub struct Config {
     pub hxt48_enable: ConfigOption<bool>,
     /// Enable the 48MHz internal RC oscillator
     pub hrc48_enable: ConfigOption<bool>,
+    /// Enable the 32.768kHz external crystal oscillator
+    pub lxt32_enable: ConfigOption<bool>,
+    /// Enable the 10kHz internal RC oscillator
+    pub lrc10_enable: ConfigOption<bool>,
+    /// Enable the 32kHz internal RC oscillator
+    pub lrc32_enable: ConfigOption<bool>,
+    /// Enable the 96MHz doubler of hxt48, a clk_sys source
+    pub dbl96_enable: ConfigOption<bool>,
     /// Configuration for DLL1
     pub dll1: ConfigOption<DllConfig>,
     /// Configuration for DLL2 
@@ -71,8 +84,16 @@ pub struct Config {
     pub tick: ConfigOption<TickConfig>,
     /// Select the clock source for peripheral clock
     pub clk_peri_sel: ConfigOption<ClkPeriSel>,
+    /// Select the clock source for clk_rtc (RTC and LPTIMs)
+    pub clk_rtc_sel: ConfigOption<RtcSel>,
+    /// Select the clock source for clk_wdt (watchdogs)
+    pub clk_wdt_sel: ConfigOption<WdtSel>,
+    /// Calibrate the enabled LRCs against hxt48, counting this many LRC cycles.
+    /// More cycles take longer but are more accurate. Requires hxt48.
+    pub lrc_calibration: ConfigOption<u16>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DllConfig {
     /// Enable/disable the DLL
     pub enable: bool,
@@ -84,6 +105,7 @@ pub struct DllConfig {
     pub div2: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct UsbConfig {
     /// Select the clock source for USB
     pub sel: UsbSel,
@@ -92,6 +114,7 @@ pub struct UsbConfig {
     pub div: u8,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TickConfig {
     /// Select the clock source for system tick
     pub sel: TickSel,
@@ -105,6 +128,11 @@ impl Default for Config {
         Self {
             hxt48_enable: ConfigOption::new(true),
             hrc48_enable: ConfigOption::new(false),
+            // Low-speed clocks depend on the board, and are usually set up by the bootloader
+            lxt32_enable: ConfigOption::keep(),
+            lrc10_enable: ConfigOption::keep(),
+            lrc32_enable: ConfigOption::keep(),
+            dbl96_enable: ConfigOption::keep(),
             dll1: ConfigOption::new(DllConfig { enable: true, stg: 5, div2: false }),
             dll2: ConfigOption::keep(),
             clk_sys_sel: ConfigOption::new(ClkSysSel::Dll1),
@@ -114,6 +142,9 @@ impl Default for Config {
             usb: ConfigOption::new(UsbConfig { sel: UsbSel::ClkSys, div: 0 }),
             tick: ConfigOption::new(TickConfig { sel: TickSel::ClkRtc, div: 0 }),
             clk_peri_sel: ConfigOption::new(ClkPeriSel::Hxt48),
+            clk_rtc_sel: ConfigOption::keep(),
+            clk_wdt_sel: ConfigOption::keep(),
+            lrc_calibration: ConfigOption::keep(),
         }
     }
 }
@@ -123,6 +154,10 @@ impl Config {
         Self {
             hxt48_enable: ConfigOption::keep(),
             hrc48_enable: ConfigOption::keep(),
+            lxt32_enable: ConfigOption::keep(),
+            lrc10_enable: ConfigOption::keep(),
+            lrc32_enable: ConfigOption::keep(),
+            dbl96_enable: ConfigOption::keep(),
             dll1: ConfigOption::keep(),
             dll2: ConfigOption::keep(),
             clk_sys_sel: ConfigOption::keep(),
@@ -132,10 +167,24 @@ impl Config {
             usb: ConfigOption::keep(),
             tick: ConfigOption::keep(),
             clk_peri_sel: ConfigOption::keep(),
+            clk_rtc_sel: ConfigOption::keep(),
+            clk_wdt_sel: ConfigOption::keep(),
+            lrc_calibration: ConfigOption::keep(),
         }
     }
 
+    /// Compute and validate the clocks resulting from this configuration, reading
+    /// the current hardware state for `keep` values.
+    ///
+    /// This doesn't change any register.
+    pub fn plan(&self) -> Result<ClockPlan, ClockError> {
+        self.plan_from(&ClockState::read())
+    }
+
     /// Apply the RCC clock configuration to the hardware registers
+    ///
+    /// The configuration is validated with [`Config::plan`] first, nothing is
+    /// changed if it fails. Returns the resulting clocks.
     /// 
     /// Safety
     /// This function is typically called by sifli_hal::init() (configured 
@@ -146,16 +195,69 @@ impl Config {
     /// You must ensure that their clocks are not broken.
     /// If configuring the clock after calling sifli_hal::init(), make sure 
     /// not to break the clock of Timer used as the time driver.
-    pub unsafe fn apply(&self) {
+    pub unsafe fn apply(&self) -> Result<ClockPlan, ClockError> {
+        let plan = self.plan()?;
+        self.apply_plan(plan, current_dvfs_mode())
+    }
+
+    /// Apply this configuration without leaving the DVFS mode `mode`, see
+    /// [`Config::plan_in_mode_from`]. `mode` must be the current mode.
+    pub(crate) unsafe fn apply_in_mode(&self, mode: HpsysDvfsMode) -> Result<ClockPlan, ClockError> {
+        let plan = self.plan_in_mode_from(&ClockState::read(), mode)?;
+        self.apply_plan(plan, mode)
+    }
+
+    /// Apply this configuration after [`Config::apply_in_mode`]: the voltage is
+    /// the one of `mode`, not the one of the current hclk.
+    pub(crate) unsafe fn apply_from_mode(&self, mode: HpsysDvfsMode) -> Result<ClockPlan, ClockError> {
+        let plan = self.plan()?;
+        self.apply_plan(plan, mode)
+    }
+
+    unsafe fn apply_plan(&self, plan: ClockPlan, current_mode: HpsysDvfsMode) -> Result<ClockPlan, ClockError> {
+
         // Configure oscillators
         if let ConfigOption::Update(enable) = self.hxt48_enable {
             HPSYS_AON.acr().modify(|w| w.set_hxt48_req(enable));
-            while HPSYS_AON.acr().read().hxt48_rdy() != enable {}
+            wait_ready("hxt48", HS_READY_TIMEOUT_US, || HPSYS_AON.acr().read().hxt48_rdy() == enable)?;
         }
 
         if let ConfigOption::Update(enable) = self.hrc48_enable {
             HPSYS_AON.acr().modify(|w| w.set_hrc48_req(enable));
-            while HPSYS_AON.acr().read().hrc48_rdy() != enable {}
+            wait_ready("hrc48", HS_READY_TIMEOUT_US, || HPSYS_AON.acr().read().hrc48_rdy() == enable)?;
+        }
+
+        if let ConfigOption::Update(enable) = self.lxt32_enable {
+            PMUC.lxt_cr().modify(|w| w.set_en(enable));
+            wait_ready("lxt32", LS_READY_TIMEOUT_US, || PMUC.lxt_cr().read().rdy() == enable)?;
+        }
+
+        if let ConfigOption::Update(enable) = self.lrc10_enable {
+            PMUC.lrc10_cr().modify(|w| w.set_en(enable));
+            wait_ready("lrc10", LS_READY_TIMEOUT_US, || PMUC.lrc10_cr().read().rdy() == enable)?;
+        }
+
+        if let ConfigOption::Update(enable) = self.lrc32_enable {
+            PMUC.lrc32_cr().modify(|w| w.set_en(enable));
+            wait_ready("lrc32", LS_READY_TIMEOUT_US, || PMUC.lrc32_cr().read().rdy() == enable)?;
+        }
+
+        if let ConfigOption::Update(enable) = self.dbl96_enable {
+            HPSYS_RCC.dbl96cr().modify(|w| w.set_en(enable));
+            wait_ready("dbl96", HS_READY_TIMEOUT_US, || HPSYS_RCC.dbl96cr().read().rdy() == enable)?;
+        }
+
+        // Configure low-power clock muxes
+        if let ConfigOption::Update(sel) = self.clk_rtc_sel {
+            PMUC.cr().modify(|w| w.set_sel_lpclk(sel == RtcSel::Lxt32));
+        }
+
+        if let ConfigOption::Update(sel) = self.clk_wdt_sel {
+            PMUC.cr().modify(|w| w.set_sel_wdtclk(sel == WdtSel::Lrc32));
+        }
+
+        if let ConfigOption::Update(cycles) = self.lrc_calibration {
+            calibrate_lrc(cycles)?;
         }
 
         if let ConfigOption::Update(div) = self.pclk1_div {
@@ -184,38 +286,28 @@ impl Config {
 
         // Configure system clock
         if self.hclk_is_update() {
-            let hclk_freq = self.get_final_hclk_freq().unwrap();
-            let current_hclk_freq = super::get_hclk_freq().unwrap();
-
             let config_hclk_fn = || self.config_hclk();
-            crate::pmu::dvfs::config_hcpu_dvfs(current_hclk_freq, hclk_freq, config_hclk_fn); 
+            crate::pmu::dvfs::config_hcpu_dvfs(current_mode, plan.dvfs_mode, config_hclk_fn)?;
             if ConfigOption::Update(ClkSysSel::Dll1) != self.clk_sys_sel {
-                self.config_dll1();
+                self.config_dll1()?;
             }
         } else {
-            self.config_dll1();
+            self.config_dll1()?;
         }
 
         // Configure DLL2, Must be done after configuring DVFS
-        self.config_dll2();
+        self.config_dll2()?;
+
+        super::refresh_clocks();
+
+        Ok(plan)
     }
 
-    fn config_hclk(&self) {
+    fn config_hclk(&self) -> Result<(), ClockError> {
         // Configure system clock selection last
         if let ConfigOption::Update(sel) = self.clk_sys_sel {
-            match sel {
-                ClkSysSel::Hrc48 => if self.get_final_hrc48_enable() {
-                    panic!("clk_sys_sel is Hrc48, but hrc48 is disabled")
-                },
-                ClkSysSel::Hxt48 => if self.get_final_hxt48_enable() {
-                    panic!("clk_sys_sel is Hxt48, but hxt48 is disabled")
-                },
-                ClkSysSel::Dbl96 => todo!(),
-                ClkSysSel::Dll1 => if self.get_final_dll1_enable() {
-                    panic!("clk_sys_sel is dll1, but dll1 is disabled")
-                } else {
-                    self.config_dll1();
-                }
+            if sel == ClkSysSel::Dll1 {
+                self.config_dll1()?;
             }
             HPSYS_RCC.csr().modify(|w| w.set_sel_sys(sel));
         }
@@ -224,129 +316,21 @@ impl Config {
         if let ConfigOption::Update(div) = self.hclk_div {
             HPSYS_RCC.cfgr().modify(|w| w.set_hdiv(div));
         }
+        Ok(())
     }
 
-    fn config_dll1(&self) {
+    fn config_dll1(&self) -> Result<(), ClockError> {
         if let ConfigOption::Update(dll1) = &self.dll1 {
-            if dll1.enable {
-                rcc_assert!(max::DLL.contains(
-                    &self.get_final_dll1_freq().unwrap()
-                ));
-                
-                PMUC.hxt_cr1().modify(|w| w.set_buf_dll_en(true));
-
-                HPSYS_CFG.cau2_cr().modify(|w| {
-                    if !w.hpbg_en() { // SDK does this check, but it's not clear why
-                        w.set_hpbg_en(true);
-                    }
-                    if !w.hpbg_vddpsw_en() {
-                        w.set_hpbg_vddpsw_en(true);
-                    }
-                });
-                // Enable DLL1
-                HPSYS_RCC.dllcr(0).modify(|w| {
-                    w.set_en(true);
-                    w.set_stg(dll1.stg);
-                    w.set_out_div2_en(dll1.div2);
-                });
-                // SDK: wait for DLL ready, 5us at least
-                crate::cortex_m_blocking_delay_us(10);
-                while !HPSYS_RCC.dllcr(0).read().ready() {}
-            } else {
-                // Disable DLL1
-                HPSYS_RCC.dllcr(0).modify(|w| w.set_en(false));
-            }
+            config_dll(0, dll1)?;
         }
+        Ok(())
     }
 
-    fn config_dll2(&self) {
-        // Configure DLL2
+    fn config_dll2(&self) -> Result<(), ClockError> {
         if let ConfigOption::Update(dll2) = &self.dll2 {
-            if dll2.enable {
-                rcc_assert!(max::DLL.contains(
-                    &self.get_final_dll2_freq().unwrap()
-                ));
-
-                let dvfs_mode = crate::pmu::dvfs::HpsysDvfsMode::from_hertz(self.get_final_hclk_freq().unwrap()).unwrap();
-                let dll2_limit = dvfs_mode.get_dll2_limit();
-                let dll2_freq = self.get_final_dll2_freq().unwrap();
-                rcc_assert!(dll2_limit >= dll2_freq, 
-                    "DLL2 frequency({}) exceeds DVFS mode {:?} limit: {}",dll2_freq.0, dvfs_mode, dll2_limit.0
-                );
-
-                PMUC.hxt_cr1().modify(|w| w.set_buf_dll_en(true));
-
-                HPSYS_CFG.cau2_cr().modify(|w| {
-                    if !w.hpbg_en() { // SDK does this check, but it's not clear why
-                        w.set_hpbg_en(true);
-                    }
-                    if !w.hpbg_vddpsw_en() {
-                        w.set_hpbg_vddpsw_en(true);
-                    }
-                });
-
-                // Enable DLL2
-                HPSYS_RCC.dllcr(1).modify(|w| {
-                    w.set_en(true);
-                    w.set_stg(dll2.stg);
-                    w.set_out_div2_en(dll2.div2);
-                });
-                // SDK: wait for DLL ready, 5us at least
-                crate::cortex_m_blocking_delay_us(10);
-                while !HPSYS_RCC.dllcr(1).read().ready() {}
-            } else {
-                // Disable DLL1
-                HPSYS_RCC.dllcr(1).modify(|w| w.set_en(false));
-            }
-        }
-    }
-
-    fn get_final_dll1_freq(&self) -> Option<Hertz> {
-        if let ConfigOption::Update(dll1) = &self.dll1 {
-            if dll1.enable {
-                Some(Hertz((dll1.stg + 1) as u32 * 24_000_000 / (dll1.div2 as u32 + 1)))
-            } else {
-                None
-            }
-        } else {
-            super::get_clk_dll1_freq()
-        }
-    }
-
-    fn get_final_dll2_freq(&self) -> Option<Hertz> {
-        if let ConfigOption::Update(dll2) = &self.dll2 {
-            if dll2.enable {
-                Some(Hertz((dll2.stg + 1) as u32 * 24_000_000 / (dll2.div2 as u32 + 1)))
-            } else {
-                None
-            }
-        } else {
-            super::get_clk_dll2_freq()
-        }
-    }
-
-    fn get_final_clk_sys_freq(&self) -> Option<Hertz> {
-        match self.clk_sys_sel {
-            ConfigOption::Update(ClkSysSel::Hxt48) => Some(Hertz(48_000_000)),
-            ConfigOption::Update(ClkSysSel::Hrc48) => Some(Hertz(48_000_000)),
-            ConfigOption::Update(ClkSysSel::Dll1) => self.get_final_dll1_freq(),
-            ConfigOption::Update(ClkSysSel::Dbl96) => todo!(),
-            ConfigOption::Keep => super::get_clk_sys_freq(),
-        }
-    }
-
-    fn get_final_hclk_freq(&self) -> Option<Hertz> {
-        if self.hclk_is_update() {
-            let hclk_div = match self.hclk_div {
-                ConfigOption::Update(div) => div,
-                ConfigOption::Keep => super::get_hclk_div()
-            };
-            let clk_sys = self.get_final_clk_sys_freq()?;
-            Some(clk_sys / hclk_div as u32)
-            
-        } else {
-            super::get_hclk_freq()
+            config_dll(1, dll2)?;
         }
+        Ok(())
     }
 
     fn hclk_is_update(&self) -> bool {
@@ -357,40 +341,99 @@ impl Config {
         match super::get_clk_sys_source() {
             ClkSysSel::Hrc48 => false,
             ClkSysSel::Hxt48 => false,
-            ClkSysSel::Dbl96 => todo!(),
+            ClkSysSel::Dbl96 => self.dbl96_enable.is_update(),
             ClkSysSel::Dll1 => self.dll1.is_update()
         }
     }
+}
 
-    fn get_final_dll1_enable(&self) -> bool {
-        if let ConfigOption::Update(dll1) = &self.dll1 {
-            dll1.enable
-        } else {
-            super::get_clk_dll1_freq().is_some()
-        }
-    }
+/// DVFS mode of the current hclk.
+pub(crate) fn current_dvfs_mode() -> HpsysDvfsMode {
+    // An unknown current mode can only come from overclocking.
+    super::get_hclk_freq()
+        .and_then(|f| HpsysDvfsMode::from_hertz(f).ok())
+        .unwrap_or(HpsysDvfsMode::S1)
+}
 
-    fn get_final_hxt48_enable(&self) -> bool {
-        if let ConfigOption::Update(enable) = self.hxt48_enable {
-            enable
-        } else {
-            super::get_hxt48_freq().is_some()
-        }
+/// Configure DLL `n`, already validated by [`Config::plan`].
+fn config_dll(n: usize, dll: &DllConfig) -> Result<(), ClockError> {
+    let name = if n == 0 { "clk_dll1" } else { "clk_dll2" };
+
+    if dll.enable {
+        PMUC.hxt_cr1().modify(|w| w.set_buf_dll_en(true));
+
+        HPSYS_CFG.cau2_cr().modify(|w| {
+            if !w.hpbg_en() { // SDK does this check, but it's not clear why
+                w.set_hpbg_en(true);
+            }
+            if !w.hpbg_vddpsw_en() {
+                w.set_hpbg_vddpsw_en(true);
+            }
+        });
+
+        HPSYS_RCC.dllcr(n).modify(|w| {
+            w.set_en(true);
+            w.set_stg(dll.stg);
+            w.set_out_div2_en(dll.div2);
+        });
+        // SDK: wait for DLL ready, 5us at least
+        crate::cortex_m_blocking_delay_us(10);
+        wait_ready(name, DLL_READY_TIMEOUT_US, || HPSYS_RCC.dllcr(n).read().ready())
+    } else {
+        HPSYS_RCC.dllcr(n).modify(|w| w.set_en(false));
+        Ok(())
     }
+}
 
-    fn get_final_hrc48_enable(&self) -> bool {
-        if let ConfigOption::Update(enable) = self.hrc48_enable {
-            enable
-        } else {
-            super::get_hrc48_freq().is_some()
+/// Busy-wait until `ready` returns true, for at most `timeout_us`.
+fn wait_ready(clock: &'static str, timeout_us: u32, ready: impl Fn() -> bool) -> Result<(), ClockError> {
+    for _ in 0..timeout_us {
+        if ready() {
+            return Ok(());
         }
+        crate::cortex_m_blocking_delay_us(1);
+    }
+    if ready() {
+        Ok(())
+    } else {
+        Err(ClockError::Timeout(clock))
     }
 }
 
-#[cfg(feature = "sf32lb52x")]
-mod max {
-    use core::ops::RangeInclusive;
-    use crate::time::Hertz;
+/// Measure the frequency of the enabled LRCs against hxt48, counting `cycles` LRC cycles.
+///
+/// The results are returned by [`get_lrc10_freq`](super::get_lrc10_freq) and
+/// [`get_lrc32_freq`](super::get_lrc32_freq) from then on.
+pub fn calibrate_lrc(cycles: u16) -> Result<(), ClockError> {
+    use core::sync::atomic::Ordering;
 
-    pub(crate) const DLL: RangeInclusive<Hertz> = Hertz(24_000_000)..=Hertz(384_000_000);
+    if cycles == 0 {
+        return Err(ClockError::InvalidValue { field: "lrc_calibration", value: 0 });
+    }
+    let hxt48 = super::get_hxt48_freq()
+        .ok_or(ClockError::SourceDisabled { clock: "lrc_calibration", source: "hxt48" })?
+        .0 as u64;
+    // Twice the nominal duration on the slowest LRC.
+    let timeout_us = (cycles as u32 * 200) + 1_000;
+
+    let measure = |lrc32: bool| {
+        PMUC.cal_cr().write(|w| {
+            w.set_sel_lrc32(lrc32);
+            w.set_cycles(cycles);
+            w.set_start(true);
+        });
+        wait_ready("lrc_calibration", timeout_us, || PMUC.cal_cr().read().done())?;
+        let count = PMUC.cal_result().read().count() as u64;
+        // `cycles` LRC cycles took `count` hxt48 cycles.
+        Ok::<_, ClockError>((hxt48 * cycles as u64 / count.max(1)) as u32)
+    };
+
+    if super::get_lrc10_freq().is_some() {
+        super::LRC10_CALIBRATED.store(measure(false)?, Ordering::Relaxed);
+    }
+    if super::get_lrc32_freq().is_some() {
+        super::LRC32_CALIBRATED.store(measure(true)?, Ordering::Relaxed);
+    }
+    super::refresh_clocks();
+    Ok(())
 }
@@ -6,6 +6,9 @@ pub use clock::*;
 mod clock_configure;
 pub use clock_configure::*;
 
+mod plan;
+pub use plan::*;
+
 use crate::time::Hertz;
 
 // TODO: should we split this into `RccEnable` and `RccReset` ?
@@ -0,0 +1,480 @@
+//! Clock planning: computes and validates the clocks of a [`Config`].
+//!
+//! Nothing here accesses the hardware, the register side is
+//! [`ClockState::read`] and [`Config::apply`].
+use crate::pmu::dvfs::HpsysDvfsMode;
+use crate::time::Hertz;
+
+use super::{ClkPeriSel, ClkSysSel, Config, ConfigOption, DllConfig, RtcSel, UsbConfig, UsbSel, WdtSel};
+
+/// Clock configuration error, returned by [`Config::plan`] and [`Config::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockError {
+    /// `clock` uses `source`, but `source` is disabled.
+    SourceDisabled {
+        clock: &'static str,
+        source: &'static str,
+    },
+    /// A divider, multiplier or cycle count is out of its valid range.
+    InvalidValue { field: &'static str, value: u32 },
+    /// `clock` is out of its valid range.
+    FrequencyOutOfRange { clock: &'static str, freq: Hertz },
+    /// No DVFS mode supports this hclk frequency.
+    NoDvfsMode(Hertz),
+    /// DLL2 exceeds the limit of the DVFS mode of hclk.
+    Dll2ExceedsDvfsLimit {
+        dll2: Hertz,
+        mode: HpsysDvfsMode,
+        limit: Hertz,
+    },
+    /// `clock` didn't become ready in time.
+    Timeout(&'static str),
+}
+
+/// Snapshot of the hardware clock configuration.
+///
+/// [`Config::plan_from`] uses it for the `keep` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockState {
+    pub hxt48: bool,
+    pub hrc48: bool,
+    pub lxt32: bool,
+    pub lrc10: bool,
+    pub lrc32: bool,
+    pub dbl96: bool,
+    pub dll1: DllConfig,
+    pub dll2: DllConfig,
+    pub clk_sys_sel: ClkSysSel,
+    pub hclk_div: u8,
+    pub pclk1_div: u8,
+    pub pclk2_div: u8,
+    pub usb: UsbConfig,
+    pub clk_peri_sel: ClkPeriSel,
+    pub clk_rtc_sel: RtcSel,
+    pub clk_wdt_sel: WdtSel,
+}
+
+/// Clock frequencies resulting from a [`Config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockPlan {
+    pub clk_sys: Hertz,
+    pub hclk: Hertz,
+    pub pclk1: Hertz,
+    pub pclk2: Hertz,
+    pub clk_peri: Hertz,
+    pub clk_peri_div2: Hertz,
+    pub clk_usb: Option<Hertz>,
+    pub clk_dll1: Option<Hertz>,
+    pub clk_dll2: Option<Hertz>,
+    /// DVFS mode required by hclk
+    pub dvfs_mode: HpsysDvfsMode,
+}
+
+impl Config {
+    /// Compute and validate the clocks resulting from applying this configuration
+    /// on top of `current`.
+    ///
+    /// This doesn't access the hardware at all.
+    pub fn plan_from(&self, current: &ClockState) -> Result<ClockPlan, ClockError> {
+        self.plan_inner(current, None)
+    }
+
+    /// Like [`Config::plan_from`], but staying in the DVFS mode `mode` instead
+    /// of the mode of the resulting hclk, which must be within the limit of `mode`.
+    ///
+    /// Used to move the core to a slower clock for a moment, keeping the voltage
+    /// and the DLL2 limit of `mode`.
+    pub(crate) fn plan_in_mode_from(&self, current: &ClockState, mode: HpsysDvfsMode) -> Result<ClockPlan, ClockError> {
+        self.plan_inner(current, Some(mode))
+    }
+
+    fn plan_inner(&self, current: &ClockState, mode: Option<HpsysDvfsMode>) -> Result<ClockPlan, ClockError> {
+        let hxt48 = self.hxt48_enable.apply(current.hxt48);
+        let hrc48 = self.hrc48_enable.apply(current.hrc48);
+        let lxt32 = self.lxt32_enable.apply(current.lxt32);
+        let lrc10 = self.lrc10_enable.apply(current.lrc10);
+        let lrc32 = self.lrc32_enable.apply(current.lrc32);
+        let dbl96 = self.dbl96_enable.apply(current.dbl96);
+
+        if dbl96 {
+            require(hxt48, "dbl96", "hxt48")?;
+        }
+
+        let clk_dll1 = dll_freq("clk_dll1", &self.dll1.apply(current.dll1), hxt48)?;
+        let clk_dll2 = dll_freq("clk_dll2", &self.dll2.apply(current.dll2), hxt48)?;
+
+        let clk_sys = match self.clk_sys_sel.apply(current.clk_sys_sel) {
+            ClkSysSel::Hrc48 => require(hrc48, "clk_sys", "hrc48").map(|_| Hertz(48_000_000))?,
+            ClkSysSel::Hxt48 => require(hxt48, "clk_sys", "hxt48").map(|_| Hertz(48_000_000))?,
+            ClkSysSel::Dbl96 => require(dbl96, "clk_sys", "dbl96").map(|_| Hertz(96_000_000))?,
+            ClkSysSel::Dll1 => clk_dll1.ok_or(ClockError::SourceDisabled {
+                clock: "clk_sys",
+                source: "clk_dll1",
+            })?,
+        };
+
+        // HDIV is 8 bits wide, 0 doesn't divide, same as 1.
+        let hclk = clk_sys / range("hclk_div", self.hclk_div.apply(current.hclk_div), 0xFF)?.max(1) as u32;
+        let pclk1 = hclk / (1u32 << range("pclk1_div", self.pclk1_div.apply(current.pclk1_div), 7)?);
+        let pclk2 = hclk / (1u32 << range("pclk2_div", self.pclk2_div.apply(current.pclk2_div), 7)?);
+
+        let dvfs_mode = match (mode, HpsysDvfsMode::from_hertz(hclk)) {
+            (Some(mode), _) => {
+                limit(hclk <= mode.get_frequency_limit(), ClockError::NoDvfsMode(hclk))?;
+                mode
+            }
+            (None, Ok(mode)) => mode,
+            (None, Err(_)) => {
+                limit(false, ClockError::NoDvfsMode(hclk))?;
+                HpsysDvfsMode::S1
+            }
+        };
+
+        if let Some(dll2) = clk_dll2 {
+            let dll2_limit = dvfs_mode.get_dll2_limit();
+            limit(
+                dll2 <= dll2_limit,
+                ClockError::Dll2ExceedsDvfsLimit {
+                    dll2,
+                    mode: dvfs_mode,
+                    limit: dll2_limit,
+                },
+            )?;
+        }
+
+        let clk_peri = match self.clk_peri_sel.apply(current.clk_peri_sel) {
+            ClkPeriSel::Hxt48 => require(hxt48, "clk_peri", "hxt48"),
+            ClkPeriSel::Hrc48 => require(hrc48, "clk_peri", "hrc48"),
+        }
+        .map(|_| Hertz(48_000_000))?;
+
+        let usb = self.usb.apply(current.usb);
+        let usb_div = range("usb.div", usb.div, 7)?.max(1);
+        let clk_usb = match usb.sel {
+            UsbSel::ClkSys => Some(clk_sys),
+            UsbSel::Dll2 => {
+                // Only an error when asked for, an unused USB clock may stay without source.
+                if self.usb.is_update() && clk_dll2.is_none() {
+                    return Err(ClockError::SourceDisabled {
+                        clock: "clk_usb",
+                        source: "clk_dll2",
+                    });
+                }
+                clk_dll2
+            }
+        }
+        .map(|f| f / usb_div as u32);
+
+        if let ConfigOption::Update(tick) = &self.tick {
+            range("tick.div", tick.div, 63)?;
+        }
+
+        // The low-power muxes are only checked when changed, the bootloader may
+        // leave an unused one without source.
+        if self.clk_rtc_sel.is_update() || self.lxt32_enable.is_update() || self.lrc10_enable.is_update() {
+            match self.clk_rtc_sel.apply(current.clk_rtc_sel) {
+                RtcSel::Lxt32 => require(lxt32, "clk_rtc", "lxt32")?,
+                RtcSel::Lrc10 => require(lrc10, "clk_rtc", "lrc10")?,
+            }
+        }
+        if self.clk_wdt_sel.is_update() || self.lrc10_enable.is_update() || self.lrc32_enable.is_update() {
+            match self.clk_wdt_sel.apply(current.clk_wdt_sel) {
+                WdtSel::Lrc10 => require(lrc10, "clk_wdt", "lrc10")?,
+                WdtSel::Lrc32 => require(lrc32, "clk_wdt", "lrc32")?,
+            }
+        }
+
+        if let ConfigOption::Update(cycles) = self.lrc_calibration {
+            require(hxt48, "lrc_calibration", "hxt48")?;
+            if cycles == 0 {
+                return Err(ClockError::InvalidValue {
+                    field: "lrc_calibration",
+                    value: 0,
+                });
+            }
+        }
+
+        Ok(ClockPlan {
+            clk_sys,
+            hclk,
+            pclk1,
+            pclk2,
+            clk_peri,
+            clk_peri_div2: clk_peri / 2u8,
+            clk_usb,
+            clk_dll1,
+            clk_dll2,
+            dvfs_mode,
+        })
+    }
+}
+
+fn require(enabled: bool, clock: &'static str, source: &'static str) -> Result<(), ClockError> {
+    if enabled {
+        Ok(())
+    } else {
+        Err(ClockError::SourceDisabled { clock, source })
+    }
+}
+
+fn range(field: &'static str, value: u8, max: u8) -> Result<u8, ClockError> {
+    if value <= max {
+        Ok(value)
+    } else {
+        Err(ClockError::InvalidValue {
+            field,
+            value: value as u32,
+        })
+    }
+}
+
+/// Check a frequency limit, skipped with the `unchecked-overclocking` feature.
+fn limit(ok: bool, err: ClockError) -> Result<(), ClockError> {
+    if ok {
+        return Ok(());
+    }
+    #[cfg(feature = "unchecked-overclocking")]
+    {
+        warn!("clock limit check skipped: {:?}", err);
+        Ok(())
+    }
+    #[cfg(not(feature = "unchecked-overclocking"))]
+    Err(err)
+}
+
+fn dll_freq(clock: &'static str, dll: &DllConfig, hxt48: bool) -> Result<Option<Hertz>, ClockError> {
+    if !dll.enable {
+        return Ok(None);
+    }
+    // The DLLs are referenced to hxt48 / 2.
+    require(hxt48, clock, "hxt48")?;
+    let stg = range("dll.stg", dll.stg, 15)?;
+
+    let freq = Hertz((stg + 1) as u32 * 24_000_000 / (dll.div2 as u32 + 1));
+    limit(max::DLL.contains(&freq), ClockError::FrequencyOutOfRange { clock, freq })?;
+    Ok(Some(freq))
+}
+
+#[cfg(feature = "sf32lb52x")]
+mod max {
+    use core::ops::RangeInclusive;
+    use crate::time::Hertz;
+
+    pub(crate) const DLL: RangeInclusive<Hertz> = Hertz(24_000_000)..=Hertz(384_000_000);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rcc::{TickConfig, TickSel};
+
+    /// As left by the bootloader: clk_sys from DLL1 at 144 MHz, DLL2 at 288 MHz for the PSRAM.
+    fn bootloader_state() -> ClockState {
+        ClockState {
+            hxt48: true,
+            hrc48: true,
+            lxt32: false,
+            lrc10: true,
+            lrc32: false,
+            dbl96: false,
+            dll1: DllConfig { enable: true, stg: 5, div2: false },
+            dll2: DllConfig { enable: true, stg: 11, div2: false },
+            clk_sys_sel: ClkSysSel::Dll1,
+            hclk_div: 0,
+            pclk1_div: 0,
+            pclk2_div: 0,
+            usb: UsbConfig { sel: UsbSel::ClkSys, div: 0 },
+            clk_peri_sel: ClkPeriSel::Hxt48,
+            clk_rtc_sel: RtcSel::Lrc10,
+            clk_wdt_sel: WdtSel::Lrc10,
+        }
+    }
+
+    fn dll1(stg: u8, div2: bool) -> Config {
+        Config {
+            dll1: ConfigOption::new(DllConfig { enable: true, stg, div2 }),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn plan_240mhz() {
+        let plan = dll1(9, false).plan_from(&bootloader_state()).unwrap();
+        assert_eq!(plan.clk_sys, Hertz::mhz(240));
+        assert_eq!(plan.hclk, Hertz::mhz(240));
+        assert_eq!(plan.pclk1, Hertz::mhz(240));
+        assert_eq!(plan.clk_dll1, Some(Hertz::mhz(240)));
+        assert_eq!(plan.clk_dll2, Some(Hertz::mhz(288)));
+        assert_eq!(plan.clk_peri, Hertz::mhz(48));
+        assert_eq!(plan.clk_peri_div2, Hertz::mhz(24));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S1);
+    }
+
+    #[test]
+    fn plan_144mhz() {
+        let config = Config {
+            pclk1_div: ConfigOption::new(1),
+            pclk2_div: ConfigOption::new(3),
+            tick: ConfigOption::new(TickConfig { sel: TickSel::ClkRtc, div: 63 }),
+            ..Config::default()
+        };
+        let plan = config.plan_from(&bootloader_state()).unwrap();
+        assert_eq!(plan.clk_sys, Hertz::mhz(144));
+        assert_eq!(plan.hclk, Hertz::mhz(144));
+        assert_eq!(plan.pclk1, Hertz::mhz(72));
+        assert_eq!(plan.pclk2, Hertz::mhz(18));
+        assert_eq!(plan.clk_usb, Some(Hertz::mhz(144)));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S0);
+    }
+
+    #[test]
+    fn plan_keep_uses_current_state() {
+        let plan = Config::new_keep().plan_from(&bootloader_state()).unwrap();
+        assert_eq!(plan.clk_sys, Hertz::mhz(144));
+        assert_eq!(plan.clk_dll1, Some(Hertz::mhz(144)));
+        assert_eq!(plan.clk_dll2, Some(Hertz::mhz(288)));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S0);
+    }
+
+    #[test]
+    fn dll_stg_out_of_range() {
+        assert_eq!(
+            dll1(16, false).plan_from(&bootloader_state()),
+            Err(ClockError::InvalidValue {
+                field: "dll.stg",
+                value: 16
+            })
+        );
+    }
+
+    #[test]
+    fn dll_needs_hxt48() {
+        let config = Config {
+            hxt48_enable: ConfigOption::new(false),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.plan_from(&bootloader_state()),
+            Err(ClockError::SourceDisabled {
+                clock: "clk_dll1",
+                source: "hxt48"
+            })
+        );
+    }
+
+    #[test]
+    fn sys_needs_dll1() {
+        let config = Config {
+            dll1: ConfigOption::new(DllConfig { enable: false, stg: 0, div2: false }),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.plan_from(&bootloader_state()),
+            Err(ClockError::SourceDisabled {
+                clock: "clk_sys",
+                source: "clk_dll1"
+            })
+        );
+    }
+
+    #[cfg(not(feature = "unchecked-overclocking"))]
+    #[test]
+    fn dll_below_range() {
+        // 24 MHz / 2
+        assert_eq!(
+            dll1(0, true).plan_from(&bootloader_state()),
+            Err(ClockError::FrequencyOutOfRange {
+                clock: "clk_dll1",
+                freq: Hertz::mhz(12)
+            })
+        );
+    }
+
+    #[cfg(not(feature = "unchecked-overclocking"))]
+    #[test]
+    fn hclk_above_dvfs_limit() {
+        assert_eq!(
+            dll1(10, false).plan_from(&bootloader_state()),
+            Err(ClockError::NoDvfsMode(Hertz::mhz(264)))
+        );
+    }
+
+    #[cfg(not(feature = "unchecked-overclocking"))]
+    #[test]
+    fn dll2_above_dvfs_limit() {
+        // 48 MHz is D1, where DLL2 must be off.
+        let config = Config {
+            clk_sys_sel: ConfigOption::new(ClkSysSel::Hxt48),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.plan_from(&bootloader_state()),
+            Err(ClockError::Dll2ExceedsDvfsLimit {
+                dll2: Hertz::mhz(288),
+                mode: HpsysDvfsMode::D1,
+                limit: Hertz(0),
+            })
+        );
+    }
+
+    /// The steps of `dvfs::set_hclk(240 MHz)` from the bootloader state: DLL1
+    /// is reprogrammed with the core on hxt48, still in S0 for DLL2.
+    #[test]
+    fn hxt48_step_keeps_mode() {
+        let hxt48 = Config {
+            clk_sys_sel: ConfigOption::new(ClkSysSel::Hxt48),
+            hclk_div: ConfigOption::new(1),
+            ..Config::new_keep()
+        };
+        let plan = hxt48.plan_in_mode_from(&bootloader_state(), HpsysDvfsMode::S0).unwrap();
+        assert_eq!(plan.hclk, Hertz::mhz(48));
+        assert_eq!(plan.clk_dll2, Some(Hertz::mhz(288)));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S0);
+
+        let on_hxt48 = ClockState {
+            clk_sys_sel: ClkSysSel::Hxt48,
+            hclk_div: 1,
+            ..bootloader_state()
+        };
+        let config = Config {
+            dll1: ConfigOption::new(DllConfig { enable: true, stg: 9, div2: false }),
+            clk_sys_sel: ConfigOption::new(ClkSysSel::Dll1),
+            hclk_div: ConfigOption::new(1),
+            ..Config::new_keep()
+        };
+        let plan = config.plan_from(&on_hxt48).unwrap();
+        assert_eq!(plan.hclk, Hertz::mhz(240));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S1);
+    }
+
+    #[cfg(not(feature = "unchecked-overclocking"))]
+    #[test]
+    fn in_mode_checks_hclk_limit() {
+        assert_eq!(
+            dll1(9, false).plan_in_mode_from(&bootloader_state(), HpsysDvfsMode::S0),
+            Err(ClockError::NoDvfsMode(Hertz::mhz(240)))
+        );
+    }
+
+    #[cfg(feature = "unchecked-overclocking")]
+    #[test]
+    fn unchecked_overclocking_skips_limits() {
+        let plan = dll1(10, false).plan_from(&bootloader_state()).unwrap();
+        assert_eq!(plan.hclk, Hertz::mhz(264));
+        assert_eq!(plan.dvfs_mode, HpsysDvfsMode::S1);
+
+        let plan = dll1(0, true).plan_from(&bootloader_state()).unwrap();
+        assert_eq!(plan.clk_dll1, Some(Hertz::mhz(12)));
+    }
+
+    #[cfg(feature = "unchecked-overclocking")]
+    #[test]
+    fn unchecked_overclocking_keeps_range_checks() {
+        assert!(matches!(
+            dll1(16, false).plan_from(&bootloader_state()),
+            Err(ClockError::InvalidValue { field: "dll.stg", .. })
+        ));
+    }
+}
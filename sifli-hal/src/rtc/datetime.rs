@@ -0,0 +1,267 @@
+#[cfg(feature = "chrono")]
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike, Weekday};
+
+/// Errors regarding the [`DateTime`] struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DateTimeError {
+    /// The [DateTime] contains an invalid year value. Must be between `2000` and `2199`.
+    InvalidYear,
+    /// The [DateTime] contains an invalid month value. Must be between `1` and `12`.
+    InvalidMonth,
+    /// The [DateTime] contains an invalid day value. Must be between `1` and the
+    /// number of days of the month.
+    InvalidDay,
+    /// The [DateTime] contains an invalid day of week. Must be between `0` and `6` where 0 is Sunday.
+    InvalidDayOfWeek(
+        /// The value of the DayOfWeek that was given.
+        u8,
+    ),
+    /// The [DateTime] contains an invalid hour value. Must be between `0` and `23`.
+    InvalidHour,
+    /// The [DateTime] contains an invalid minute value. Must be between `0` and `59`.
+    InvalidMinute,
+    /// The [DateTime] contains an invalid second value. Must be between `0` and `59`.
+    InvalidSecond,
+}
+
+/// Structure containing date and time information
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    /// 2000..2199
+    year: u16,
+    /// 1..12, 1 is January
+    month: u8,
+    /// 1..28,29,30,31 depending on month
+    day: u8,
+    day_of_week: DayOfWeek,
+    /// 0..23
+    hour: u8,
+    /// 0..59
+    minute: u8,
+    /// 0..59
+    second: u8,
+}
+
+impl DateTime {
+    /// Get the year (2000..2199)
+    pub const fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Get the month (1..12, 1 is January)
+    pub const fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// Get the day (1..31)
+    pub const fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Get the day of week
+    pub const fn day_of_week(&self) -> DayOfWeek {
+        self.day_of_week
+    }
+
+    /// Get the hour (0..23)
+    pub const fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// Get the minute (0..59)
+    pub const fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// Get the second (0..59)
+    pub const fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// Create a new DateTime with the given information.
+    pub fn from(
+        year: u16,
+        month: u8,
+        day: u8,
+        day_of_week: DayOfWeek,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, DateTimeError> {
+        if !(2000..=2199).contains(&year) {
+            Err(DateTimeError::InvalidYear)
+        } else if !(1..=12).contains(&month) {
+            Err(DateTimeError::InvalidMonth)
+        } else if day < 1 || day > days_in_month(year, month) {
+            Err(DateTimeError::InvalidDay)
+        } else if hour > 23 {
+            Err(DateTimeError::InvalidHour)
+        } else if minute > 59 {
+            Err(DateTimeError::InvalidMinute)
+        } else if second > 59 {
+            Err(DateTimeError::InvalidSecond)
+        } else {
+            Ok(Self {
+                year,
+                month,
+                day,
+                day_of_week,
+                hour,
+                minute,
+                second,
+            })
+        }
+    }
+}
+
+/// Number of days of `month` (1..12) in `year`.
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+const fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Fails with [`DateTimeError::InvalidYear`] outside of `2000..=2199`.
+#[cfg(feature = "chrono")]
+impl TryFrom<NaiveDateTime> for DateTime {
+    type Error = DateTimeError;
+
+    fn try_from(date_time: NaiveDateTime) -> Result<Self, Self::Error> {
+        let year = u16::try_from(date_time.year()).map_err(|_| DateTimeError::InvalidYear)?;
+        Self::from(
+            year,
+            date_time.month() as u8,
+            date_time.day() as u8,
+            date_time.weekday().into(),
+            date_time.hour() as u8,
+            date_time.minute() as u8,
+            // A leap second is reported as the 59th.
+            date_time.second() as u8,
+        )
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime> for NaiveDateTime {
+    fn from(date_time: DateTime) -> Self {
+        NaiveDate::from_ymd_opt(date_time.year as i32, date_time.month as u32, date_time.day as u32)
+            .unwrap()
+            .and_hms_opt(date_time.hour as u32, date_time.minute as u32, date_time.second as u32)
+            .unwrap()
+    }
+}
+
+/// A day of the week
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum DayOfWeek {
+    Sunday = 0,
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+}
+
+#[cfg(feature = "chrono")]
+impl From<Weekday> for DayOfWeek {
+    fn from(weekday: Weekday) -> Self {
+        day_of_week_from_u8(weekday.num_days_from_sunday() as u8).unwrap()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DayOfWeek> for Weekday {
+    fn from(weekday: DayOfWeek) -> Self {
+        match weekday {
+            DayOfWeek::Sunday => Weekday::Sun,
+            DayOfWeek::Monday => Weekday::Mon,
+            DayOfWeek::Tuesday => Weekday::Tue,
+            DayOfWeek::Wednesday => Weekday::Wed,
+            DayOfWeek::Thursday => Weekday::Thu,
+            DayOfWeek::Friday => Weekday::Fri,
+            DayOfWeek::Saturday => Weekday::Sat,
+        }
+    }
+}
+
+pub(super) const fn day_of_week_from_u8(v: u8) -> Result<DayOfWeek, DateTimeError> {
+    Ok(match v {
+        0 => DayOfWeek::Sunday,
+        1 => DayOfWeek::Monday,
+        2 => DayOfWeek::Tuesday,
+        3 => DayOfWeek::Wednesday,
+        4 => DayOfWeek::Thursday,
+        5 => DayOfWeek::Friday,
+        6 => DayOfWeek::Saturday,
+        x => return Err(DateTimeError::InvalidDayOfWeek(x)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, day: u8) -> Result<DateTime, DateTimeError> {
+        DateTime::from(year, month, day, DayOfWeek::Monday, 0, 0, 0)
+    }
+
+    #[test]
+    fn days_per_month() {
+        assert!(date(2025, 1, 31).is_ok());
+        assert_eq!(date(2025, 4, 31), Err(DateTimeError::InvalidDay));
+        assert!(date(2025, 4, 30).is_ok());
+        assert_eq!(date(2025, 2, 29), Err(DateTimeError::InvalidDay));
+        assert_eq!(date(2025, 1, 0), Err(DateTimeError::InvalidDay));
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(date(2000, 2, 29).is_ok());
+        assert!(date(2024, 2, 29).is_ok());
+        assert_eq!(date(2100, 2, 29), Err(DateTimeError::InvalidDay));
+        assert_eq!(date(2024, 2, 30), Err(DateTimeError::InvalidDay));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(date(1999, 12, 31), Err(DateTimeError::InvalidYear));
+        assert_eq!(date(2200, 1, 1), Err(DateTimeError::InvalidYear));
+        assert!(date(2199, 12, 31).is_ok());
+        assert_eq!(date(2025, 13, 1), Err(DateTimeError::InvalidMonth));
+        assert_eq!(
+            DateTime::from(2025, 1, 1, DayOfWeek::Wednesday, 24, 0, 0),
+            Err(DateTimeError::InvalidHour)
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_round_trip() {
+        let naive = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap().and_hms_opt(23, 59, 58).unwrap();
+        let date_time = DateTime::try_from(naive).unwrap();
+        assert_eq!(date_time.day_of_week(), DayOfWeek::Thursday);
+        assert_eq!(NaiveDateTime::from(date_time), naive);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_year_out_of_range() {
+        let naive = NaiveDate::from_ymd_opt(1999, 12, 31).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(DateTime::try_from(naive), Err(DateTimeError::InvalidYear));
+        let naive = NaiveDate::from_ymd_opt(2200, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(DateTime::try_from(naive), Err(DateTimeError::InvalidYear));
+    }
+}
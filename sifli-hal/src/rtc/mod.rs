@@ -0,0 +1,465 @@
+//! Real Time Clock (RTC)
+//!
+//! The RTC is clocked by `clk_rtc` (LXT32 or LRC10) and lives in the always-on
+//! domain: the calendar, the alarms and the backup registers keep their state
+//! across HPSYS resets and deep sleep.
+mod datetime;
+
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+pub use self::datetime::{DateTime, DateTimeError, DayOfWeek};
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::pac::rtc::Rtc as Regs;
+use crate::pac::PMUC;
+use crate::{interrupt, pac, peripherals};
+
+/// Number of backup registers.
+pub const BACKUP_REGISTER_COUNT: usize = 10;
+
+/// Tick rate of the wakeup timer, the output of the first prescaler.
+pub const WAKEUP_TICK_HZ: u32 = 256;
+
+/// RTC error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcError {
+    /// The selected clock source is not running.
+    ClockDisabled,
+    /// The calendar holds an invalid date and time, e.g. it was never set.
+    InvalidDateTime(DateTimeError),
+    /// The calendar was never initialized.
+    NotRunning,
+}
+
+/// Clock source of `clk_rtc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcClockSource {
+    /// 32.768 kHz external crystal.
+    Lxt32,
+    /// 10 kHz internal RC oscillator. Its frequency varies between chips and
+    /// with temperature, so the calendar drifts.
+    Lrc10,
+}
+
+/// RTC config.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Clock source of `clk_rtc`.
+    ///
+    /// `clk_rtc` also clocks the LPTIMs, including an LPTIM time driver.
+    pub clock_source: RtcClockSource,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clock_source: RtcClockSource::Lxt32,
+        }
+    }
+}
+
+/// Alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    /// Alarm A.
+    A,
+    /// Alarm B.
+    B,
+}
+
+impl Alarm {
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Alarm time.
+///
+/// The alarm fires when the calendar matches the given fields; the day is
+/// ignored when `None`, so that the alarm fires every day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlarmTime {
+    /// Day of the month, 1..31, or `None` to match every day.
+    pub day: Option<u8>,
+    /// 0..23
+    pub hour: u8,
+    /// 0..59
+    pub minute: u8,
+    /// 0..59
+    pub second: u8,
+}
+
+struct State {
+    alarm_wakers: [AtomicWaker; 2],
+    wakeup_waker: AtomicWaker,
+    wakeups: AtomicU32,
+}
+
+static STATE: State = State {
+    alarm_wakers: [AtomicWaker::new(), AtomicWaker::new()],
+    wakeup_waker: AtomicWaker::new(),
+    wakeups: AtomicU32::new(0),
+};
+
+/// RTC interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RTC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = pac::RTC;
+        let isr = r.isr().read();
+
+        for alarm in [Alarm::A, Alarm::B] {
+            let n = alarm.index();
+            if isr.alrmf(n) {
+                r.cr().modify(|w| w.set_alrmie(n, false));
+                r.isr().modify(|w| w.set_alrmf(n, false));
+                STATE.alarm_wakers[n].wake();
+            }
+        }
+
+        if isr.wutf() {
+            r.isr().modify(|w| w.set_wutf(false));
+            STATE.wakeups.fetch_add(1, Ordering::Relaxed);
+            STATE.wakeup_waker.wake();
+        }
+    }
+}
+
+/// RTC driver.
+pub struct Rtc<'d> {
+    _rtc: PeripheralRef<'d, peripherals::RTC>,
+}
+
+impl<'d> Rtc<'d> {
+    /// Create a new RTC driver.
+    ///
+    /// A running calendar is left untouched, unless the clock source changes.
+    pub fn new(
+        rtc: impl Peripheral<P = peripherals::RTC> + 'd,
+        _irq: impl Binding<interrupt::typelevel::RTC, InterruptHandler> + 'd,
+        config: Config,
+    ) -> Result<Self, RtcError> {
+        into_ref!(rtc);
+
+        let lxt32 = match config.clock_source {
+            RtcClockSource::Lxt32 => true,
+            RtcClockSource::Lrc10 => false,
+        };
+        let freq = match config.clock_source {
+            RtcClockSource::Lxt32 => crate::rcc::get_lxt32_freq(),
+            RtcClockSource::Lrc10 => crate::rcc::get_lrc10_freq(),
+        }
+        .ok_or(RtcError::ClockDisabled)?;
+
+        let this = Self { _rtc: rtc };
+
+        let changed = PMUC.cr().read().sel_lpclk() != lxt32;
+        if changed {
+            PMUC.cr().modify(|w| w.set_sel_lpclk(lxt32));
+            crate::rcc::refresh_clocks();
+        }
+
+        if changed || !this.regs().isr().read().inits() {
+            // clk_rtc / DIV_A = 256 Hz, with a 14-bit fractional part; then / DIV_B = 1 Hz.
+            let div_a = freq.0 / WAKEUP_TICK_HZ;
+            let div_a_frac = ((freq.0 % WAKEUP_TICK_HZ) << 14) / WAKEUP_TICK_HZ;
+
+            this.write(|r| {
+                r.psclr().write(|w| {
+                    w.set_div_a_int(div_a as _);
+                    w.set_div_a_frac(div_a_frac as _);
+                    w.set_div_b(WAKEUP_TICK_HZ as _);
+                });
+                // 24-hour format
+                r.cr().modify(|w| w.set_fmt(false));
+            });
+        }
+
+        interrupt::typelevel::RTC::unpend();
+        unsafe { interrupt::typelevel::RTC::enable() };
+
+        Ok(this)
+    }
+
+    fn regs(&self) -> Regs {
+        pac::RTC
+    }
+
+    /// Run `f` in initialization mode, where the calendar and the prescalers are stopped and writable.
+    fn write<R>(&self, f: impl FnOnce(Regs) -> R) -> R {
+        let r = self.regs();
+        r.isr().modify(|w| w.set_init(true));
+        while !r.isr().read().initf() {}
+
+        let result = f(r);
+
+        r.isr().modify(|w| w.set_init(false));
+        result
+    }
+
+    /// Check whether the calendar was initialized, i.e. set since the last power-on.
+    pub fn is_running(&self) -> bool {
+        self.regs().isr().read().inits()
+    }
+
+    /// Set the date and time.
+    pub fn set_datetime(&mut self, t: DateTime) {
+        let (yt, yu) = byte_to_bcd2((t.year() % 100) as u8);
+        let (mt, mu) = byte_to_bcd2(t.month());
+        let (dt, du) = byte_to_bcd2(t.day());
+        let (ht, hu) = byte_to_bcd2(t.hour());
+        let (mnt, mnu) = byte_to_bcd2(t.minute());
+        let (st, su) = byte_to_bcd2(t.second());
+
+        self.write(|r| {
+            r.tr().write(|w| {
+                w.set_pm(false);
+                w.set_ht(ht);
+                w.set_hu(hu);
+                w.set_mnt(mnt);
+                w.set_mnu(mnu);
+                w.set_st(st);
+                w.set_su(su);
+            });
+            r.dr().write(|w| {
+                w.set_cb(t.year() >= 2100);
+                w.set_yt(yt);
+                w.set_yu(yu);
+                w.set_wd(t.day_of_week() as u8);
+                w.set_mt(mt);
+                w.set_mu(mu);
+                w.set_dt(dt);
+                w.set_du(du);
+            });
+        });
+    }
+
+    /// Get the current date and time.
+    pub fn now(&self) -> Result<DateTime, RtcError> {
+        if !self.is_running() {
+            return Err(RtcError::NotRunning);
+        }
+
+        let r = self.regs();
+        // Read again if the time rolled over between the two registers.
+        let (tr, dr) = loop {
+            let tr = r.tr().read();
+            let dr = r.dr().read();
+            if r.tr().read().0 == tr.0 {
+                break (tr, dr);
+            }
+        };
+
+        let year = if dr.cb() { 2100 } else { 2000 } + bcd2_to_byte((dr.yt(), dr.yu())) as u16;
+        let day_of_week = datetime::day_of_week_from_u8(dr.wd()).map_err(RtcError::InvalidDateTime)?;
+
+        DateTime::from(
+            year,
+            bcd2_to_byte((dr.mt(), dr.mu())),
+            bcd2_to_byte((dr.dt(), dr.du())),
+            day_of_week,
+            bcd2_to_byte((tr.ht(), tr.hu())),
+            bcd2_to_byte((tr.mnt(), tr.mnu())),
+            bcd2_to_byte((tr.st(), tr.su())),
+        )
+        .map_err(RtcError::InvalidDateTime)
+    }
+
+    /// Set an alarm and enable it.
+    ///
+    /// The alarm is reported by [`wait_for_alarm`](Self::wait_for_alarm), and wakes
+    /// the chip up from deep sleep.
+    pub fn set_alarm(&mut self, alarm: Alarm, time: AlarmTime) {
+        let r = self.regs();
+        let n = alarm.index();
+
+        let (dt, du) = byte_to_bcd2(time.day.unwrap_or(1));
+        let (ht, hu) = byte_to_bcd2(time.hour);
+        let (mnt, mnu) = byte_to_bcd2(time.minute);
+        let (st, su) = byte_to_bcd2(time.second);
+
+        r.cr().modify(|w| w.set_alrme(n, false));
+        while !r.isr().read().alrmwf(n) {}
+
+        r.alrmtr(n).write(|w| {
+            w.set_ht(ht);
+            w.set_hu(hu);
+            w.set_mnt(mnt);
+            w.set_mnu(mnu);
+            w.set_st(st);
+            w.set_su(su);
+        });
+        r.alrmdr(n).write(|w| {
+            w.set_dt(dt);
+            w.set_du(du);
+            // Only compare the day of the month, and only when given.
+            w.set_mskd(time.day.is_none());
+            w.set_mskm(true);
+            w.set_msky(true);
+            w.set_mskwd(true);
+        });
+
+        r.isr().modify(|w| w.set_alrmf(n, false));
+        r.cr().modify(|w| w.set_alrme(n, true));
+    }
+
+    /// Disable an alarm.
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        let n = alarm.index();
+        self.regs().cr().modify(|w| {
+            w.set_alrme(n, false);
+            w.set_alrmie(n, false);
+        });
+    }
+
+    /// Asynchronously wait for an alarm set with [`set_alarm`](Self::set_alarm) to fire.
+    pub async fn wait_for_alarm(&mut self, alarm: Alarm) {
+        let r = self.regs();
+        let n = alarm.index();
+
+        critical_section::with(|_| {
+            if !r.isr().read().alrmf(n) {
+                r.cr().modify(|w| w.set_alrmie(n, true));
+            }
+        });
+
+        let _guard = embassy_hal_internal::drop::OnDrop::new(|| {
+            r.cr().modify(|w| w.set_alrmie(n, false));
+        });
+
+        poll_fn(|cx| {
+            STATE.alarm_wakers[n].register(cx.waker());
+            // The interrupt handler clears ALRMIE and ALRMF once the alarm fired.
+            if r.isr().read().alrmf(n) {
+                r.isr().modify(|w| w.set_alrmf(n, false));
+                Poll::Ready(())
+            } else if !r.cr().read().alrmie(n) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Start the periodic wakeup timer, with a period of `ticks` ticks of [`WAKEUP_TICK_HZ`].
+    ///
+    /// The wakeup timer wakes the chip up from deep sleep.
+    pub fn start_wakeup_timer(&mut self, ticks: u32) {
+        let r = self.regs();
+        assert!(ticks > 0);
+
+        r.cr().modify(|w| {
+            w.set_wute(false);
+            w.set_wutie(false);
+        });
+        while !r.isr().read().wutwf() {}
+
+        r.wutr().write(|w| w.set_wut(ticks - 1));
+        r.isr().modify(|w| w.set_wutf(false));
+        r.cr().modify(|w| {
+            w.set_wute(true);
+            w.set_wutie(true);
+        });
+    }
+
+    /// Start the periodic wakeup timer with a period in milliseconds.
+    pub fn start_wakeup_timer_ms(&mut self, period_ms: u32) {
+        let ticks = (period_ms as u64 * WAKEUP_TICK_HZ as u64).div_ceil(1000);
+        self.start_wakeup_timer(unwrap!(ticks.max(1).try_into()));
+    }
+
+    /// Stop the periodic wakeup timer.
+    pub fn stop_wakeup_timer(&mut self) {
+        self.regs().cr().modify(|w| {
+            w.set_wute(false);
+            w.set_wutie(false);
+        });
+    }
+
+    /// Asynchronously wait for the next period of the wakeup timer.
+    pub async fn wait_for_wakeup(&mut self) {
+        let start = STATE.wakeups.load(Ordering::Relaxed);
+        poll_fn(|cx| {
+            STATE.wakeup_waker.register(cx.waker());
+            if STATE.wakeups.load(Ordering::Relaxed) != start {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Read content of the backup register.
+    ///
+    /// The registers retain their values during HPSYS resets and deep sleep.
+    pub fn read_backup_register(&self, register: usize) -> Option<u32> {
+        if register < BACKUP_REGISTER_COUNT {
+            Some(self.regs().bkpr(register).read().bkp())
+        } else {
+            None
+        }
+    }
+
+    /// Set content of the backup register.
+    ///
+    /// The registers retain their values during HPSYS resets and deep sleep.
+    pub fn write_backup_register(&self, register: usize, value: u32) {
+        if register < BACKUP_REGISTER_COUNT {
+            self.regs().bkpr(register).write(|w| w.set_bkp(value));
+        }
+    }
+}
+
+impl<'d> Drop for Rtc<'d> {
+    fn drop(&mut self) {
+        // The calendar keeps running, only stop reporting to this core.
+        interrupt::typelevel::RTC::disable();
+    }
+}
+
+/// Convert a byte to its BCD representation, as (tens, units).
+pub(crate) const fn byte_to_bcd2(byte: u8) -> (u8, u8) {
+    (byte / 10, byte % 10)
+}
+
+/// Convert a BCD (tens, units) pair to a byte.
+pub(crate) const fn bcd2_to_byte(bcd: (u8, u8)) -> u8 {
+    bcd.0 * 10 + bcd.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bcd_round_trip() {
+        for byte in 0..=99 {
+            let bcd = byte_to_bcd2(byte);
+            assert!(bcd.0 <= 9 && bcd.1 <= 9);
+            assert_eq!(bcd2_to_byte(bcd), byte);
+        }
+    }
+
+    #[test]
+    fn bcd_digits() {
+        assert_eq!(byte_to_bcd2(0), (0, 0));
+        assert_eq!(byte_to_bcd2(7), (0, 7));
+        assert_eq!(byte_to_bcd2(59), (5, 9));
+        assert_eq!(bcd2_to_byte((2, 3)), 23);
+    }
+}
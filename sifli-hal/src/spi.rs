@@ -0,0 +1,680 @@
+//! Serial Peripheral Interface (SPI)
+#![macro_use]
+use core::marker::PhantomData;
+
+use embassy_futures::join::join;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+pub use embedded_hal_1::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
+use crate::dma::{self, AnyChannel, Transfer, Word};
+use crate::gpio::{AnyPin, Flex, Pull};
+use crate::mode::{Async, Blocking, Mode as PeriMode};
+use crate::pac;
+use crate::pac::spi::Spi as Regs;
+use crate::rcc::{RccEnableReset, RccGetFreq};
+use crate::time::Hertz;
+
+/// SPI error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// RX FIFO overrun.
+    Overrun,
+    /// TX FIFO underrun (slave mode only).
+    Underrun,
+}
+
+/// SPI config error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The requested frequency is below the peripheral clock divided by 63.
+    FrequencyTooLow,
+    /// The peripheral clock is disabled.
+    ClockDisabled,
+}
+
+/// SPI config.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// SPI mode.
+    pub mode: Mode,
+    /// Clock frequency.
+    ///
+    /// The actual frequency is the closest one not above this value that
+    /// can be reached by dividing the peripheral clock by 2 to 63. Lower
+    /// frequencies are rejected with [`ConfigError::FrequencyTooLow`].
+    pub frequency: Hertz,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            frequency: Hertz(1_000_000),
+        }
+    }
+}
+
+/// SPI driver.
+pub struct Spi<'d, T: Instance, M: PeriMode> {
+    _peri: PeripheralRef<'d, T>,
+    _sck: Option<Flex<'d>>,
+    _mosi: Option<Flex<'d>>,
+    _miso: Option<Flex<'d>>,
+    _cs: Option<Flex<'d>>,
+    tx_dma: Option<PeripheralRef<'d, AnyChannel>>,
+    rx_dma: Option<PeripheralRef<'d, AnyChannel>>,
+    current_word_size: u8,
+    _phantom: PhantomData<M>,
+}
+
+impl<'d, T: Instance, M: PeriMode> Spi<'d, T, M> {
+    #[allow(clippy::too_many_arguments)]
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: Option<Flex<'d>>,
+        mosi: Option<Flex<'d>>,
+        miso: Option<Flex<'d>>,
+        cs: Option<Flex<'d>>,
+        tx_dma: Option<PeripheralRef<'d, AnyChannel>>,
+        rx_dma: Option<PeripheralRef<'d, AnyChannel>>,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(peri);
+
+        crate::rcc::enable_and_reset::<T>();
+
+        let r = T::regs();
+        r.top_ctrl().write(|w| {
+            // Motorola SPI frame format, master drives SCLK and SFRM.
+            w.set_frf(0);
+            w.set_sclkdir(false);
+            w.set_sfrmdir(false);
+            w.set_dss(8 - 1);
+        });
+        r.fifo_ctrl().write(|w| {
+            w.set_tsre(false);
+            w.set_rsre(false);
+        });
+        r.inte().write(|_| {});
+
+        let mut this = Self {
+            _peri: peri,
+            _sck: sck,
+            _mosi: mosi,
+            _miso: miso,
+            _cs: cs,
+            tx_dma,
+            rx_dma,
+            current_word_size: 8,
+            _phantom: PhantomData,
+        };
+        this.set_config(&config)?;
+        r.top_ctrl().modify(|w| w.set_sse(true));
+        Ok(this)
+    }
+
+    /// Reconfigures it with the supplied config.
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let r = T::regs();
+        let clk = T::frequency().ok_or(ConfigError::ClockDisabled)?;
+        let div = compute_clk_div(clk, config.frequency)?;
+        let (spo, sph) = (
+            config.mode.polarity == Polarity::IdleHigh,
+            config.mode.phase == Phase::CaptureOnSecondTransition,
+        );
+
+        let enabled = r.top_ctrl().read().sse();
+        r.top_ctrl().modify(|w| w.set_sse(false));
+        r.clk_ctrl().modify(|w| {
+            w.set_clk_div(div);
+            w.set_clk_ssp_en(true);
+        });
+        r.top_ctrl().modify(|w| {
+            w.set_spo(spo);
+            w.set_sph(sph);
+            w.set_sse(enabled);
+        });
+        Ok(())
+    }
+
+    /// Get current SPI configuration.
+    pub fn get_current_config(&self) -> Result<Config, ConfigError> {
+        let r = T::regs();
+        let top_ctrl = r.top_ctrl().read();
+        let div = r.clk_ctrl().read().clk_div();
+        let polarity = if top_ctrl.spo() { Polarity::IdleHigh } else { Polarity::IdleLow };
+        let phase = if top_ctrl.sph() {
+            Phase::CaptureOnSecondTransition
+        } else {
+            Phase::CaptureOnFirstTransition
+        };
+        let clk = T::frequency().ok_or(ConfigError::ClockDisabled)?;
+        Ok(Config {
+            mode: Mode { polarity, phase },
+            frequency: clk / div.max(1) as u32,
+        })
+    }
+
+    fn set_word_size(&mut self, word_size: u8) {
+        if self.current_word_size == word_size {
+            return;
+        }
+        let r = T::regs();
+        r.top_ctrl().modify(|w| w.set_sse(false));
+        r.top_ctrl().modify(|w| {
+            w.set_dss(word_size - 1);
+            w.set_sse(true);
+        });
+        self.current_word_size = word_size;
+    }
+
+    /// Blocking write.
+    pub fn blocking_write<W: SpiWord>(&mut self, words: &[W]) -> Result<(), Error> {
+        self.set_word_size(W::BITS);
+        for word in words.iter() {
+            let _ = transfer_word(T::regs(), *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking read.
+    pub fn blocking_read<W: SpiWord>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.set_word_size(W::BITS);
+        for word in words.iter_mut() {
+            *word = transfer_word(T::regs(), W::default())?;
+        }
+        Ok(())
+    }
+
+    /// Blocking in-place bidirectional transfer.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub fn blocking_transfer_in_place<W: SpiWord>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.set_word_size(W::BITS);
+        for word in words.iter_mut() {
+            *word = transfer_word(T::regs(), *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking bidirectional transfer.
+    ///
+    /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
+    /// If `write` is shorter it is padded with zero bytes.
+    pub fn blocking_transfer<W: SpiWord>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        self.set_word_size(W::BITS);
+        let len = read.len().max(write.len());
+        for i in 0..len {
+            let wb = write.get(i).copied().unwrap_or_default();
+            let rb = transfer_word(T::regs(), wb)?;
+            if let Some(r) = read.get_mut(i) {
+                *r = rb;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance> Spi<'d, T, Blocking> {
+    /// Create a new blocking SPI driver.
+    pub fn new_blocking(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(sck, mosi, miso);
+        let (sck_fsel, mosi_fsel, miso_fsel) = (sck.fsel(), mosi.fsel(), miso.fsel());
+        Self::new_inner(
+            peri,
+            Some(new_pin(sck.map_into(), sck_fsel, Pull::None)),
+            Some(new_pin(mosi.map_into(), mosi_fsel, Pull::None)),
+            Some(new_pin(miso.map_into(), miso_fsel, Pull::Down)),
+            None,
+            None,
+            None,
+            config,
+        )
+    }
+
+    /// Create a new blocking SPI driver, in TX-only mode (only MOSI pin, no MISO).
+    pub fn new_blocking_txonly(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(sck, mosi);
+        let (sck_fsel, mosi_fsel) = (sck.fsel(), mosi.fsel());
+        Self::new_inner(
+            peri,
+            Some(new_pin(sck.map_into(), sck_fsel, Pull::None)),
+            Some(new_pin(mosi.map_into(), mosi_fsel, Pull::None)),
+            None,
+            None,
+            None,
+            None,
+            config,
+        )
+    }
+
+    /// Create a new blocking SPI driver, with the hardware chip select pin
+    /// (SFRM) driven by the peripheral for every frame.
+    pub fn new_blocking_with_cs(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        cs: impl Peripheral<P = impl CsPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(sck, mosi, miso, cs);
+        let (sck_fsel, mosi_fsel, miso_fsel, cs_fsel) = (sck.fsel(), mosi.fsel(), miso.fsel(), cs.fsel());
+        Self::new_inner(
+            peri,
+            Some(new_pin(sck.map_into(), sck_fsel, Pull::None)),
+            Some(new_pin(mosi.map_into(), mosi_fsel, Pull::None)),
+            Some(new_pin(miso.map_into(), miso_fsel, Pull::Down)),
+            Some(new_pin(cs.map_into(), cs_fsel, Pull::Up)),
+            None,
+            None,
+            config,
+        )
+    }
+}
+
+impl<'d, T: Instance> Spi<'d, T, Async> {
+    /// Create a new SPI driver, using DMA channels for async transfers.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl dma::Channel> + 'd,
+        rx_dma: impl Peripheral<P = impl dma::Channel> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(sck, mosi, miso, tx_dma, rx_dma);
+        let (sck_fsel, mosi_fsel, miso_fsel) = (sck.fsel(), mosi.fsel(), miso.fsel());
+        Self::new_inner(
+            peri,
+            Some(new_pin(sck.map_into(), sck_fsel, Pull::None)),
+            Some(new_pin(mosi.map_into(), mosi_fsel, Pull::None)),
+            Some(new_pin(miso.map_into(), miso_fsel, Pull::Down)),
+            None,
+            Some(tx_dma.map_into()),
+            Some(rx_dma.map_into()),
+            config,
+        )
+    }
+
+    /// Create a new SPI driver, in TX-only mode (only MOSI pin, no MISO),
+    /// using a DMA channel for async transfers.
+    pub fn new_txonly(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl dma::Channel> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(sck, mosi, tx_dma);
+        let (sck_fsel, mosi_fsel) = (sck.fsel(), mosi.fsel());
+        Self::new_inner(
+            peri,
+            Some(new_pin(sck.map_into(), sck_fsel, Pull::None)),
+            Some(new_pin(mosi.map_into(), mosi_fsel, Pull::None)),
+            None,
+            None,
+            Some(tx_dma.map_into()),
+            None,
+            config,
+        )
+    }
+
+    /// SPI write, using DMA.
+    pub async fn write<W: SpiWord>(&mut self, data: &[W]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.set_word_size(W::BITS);
+        let r = T::regs();
+
+        r.fifo_ctrl().modify(|w| w.set_tsre(true));
+        let tx_dma = self.tx_dma.as_mut().unwrap();
+        let tx = unsafe { Transfer::new_write(tx_dma, T::TX_DMA_REQUEST, data, data_ptr(r), Default::default()) };
+        tx.await;
+        r.fifo_ctrl().modify(|w| w.set_tsre(false));
+
+        finish_write(r)
+    }
+
+    /// SPI read, using DMA.
+    pub async fn read<W: SpiWord>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.set_word_size(W::BITS);
+        let r = T::regs();
+        flush_rx_fifo(r);
+
+        r.fifo_ctrl().modify(|w| {
+            w.set_rsre(true);
+            w.set_tsre(true);
+        });
+        let len = data.len();
+        let rx_dma = self.rx_dma.as_mut().unwrap();
+        let rx = unsafe { Transfer::new_read(rx_dma, T::RX_DMA_REQUEST, data_ptr(r), data, Default::default()) };
+        // Clock out zeros to receive the data.
+        let zero = W::default();
+        let tx_dma = self.tx_dma.as_mut().unwrap();
+        let tx = unsafe {
+            Transfer::new_write_repeated(tx_dma, T::TX_DMA_REQUEST, &zero, len, data_ptr(r), Default::default())
+        };
+        join(tx, rx).await;
+        r.fifo_ctrl().modify(|w| {
+            w.set_rsre(false);
+            w.set_tsre(false);
+        });
+
+        check_error_flags(r)
+    }
+
+    async fn transfer_inner<W: SpiWord>(&mut self, read: *mut [W], write: *const [W]) -> Result<(), Error> {
+        assert_eq!(read.len(), write.len());
+        if read.len() == 0 {
+            return Ok(());
+        }
+        self.set_word_size(W::BITS);
+        let r = T::regs();
+        flush_rx_fifo(r);
+
+        r.fifo_ctrl().modify(|w| {
+            w.set_rsre(true);
+            w.set_tsre(true);
+        });
+        let rx_dma = self.rx_dma.as_mut().unwrap();
+        let rx = unsafe { Transfer::new_read_raw(rx_dma, T::RX_DMA_REQUEST, data_ptr(r), read, Default::default()) };
+        let tx_dma = self.tx_dma.as_mut().unwrap();
+        let tx = unsafe { Transfer::new_write_raw(tx_dma, T::TX_DMA_REQUEST, write, data_ptr(r), Default::default()) };
+        join(tx, rx).await;
+        r.fifo_ctrl().modify(|w| {
+            w.set_rsre(false);
+            w.set_tsre(false);
+        });
+
+        check_error_flags(r)
+    }
+
+    /// Bidirectional transfer, using DMA.
+    ///
+    /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
+    /// If `write` is shorter it is padded with zero bytes.
+    pub async fn transfer<W: SpiWord>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let common = read.len().min(write.len());
+        let (read_common, read_rest) = read.split_at_mut(common);
+        let (write_common, write_rest) = write.split_at(common);
+        self.transfer_inner(read_common, write_common).await?;
+        if !read_rest.is_empty() {
+            self.read(read_rest).await?;
+        }
+        if !write_rest.is_empty() {
+            self.write(write_rest).await?;
+        }
+        Ok(())
+    }
+
+    /// In-place bidirectional transfer, using DMA.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub async fn transfer_in_place<W: SpiWord>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        self.transfer_inner(data, data).await
+    }
+}
+
+impl<'d, T: Instance, M: PeriMode> Drop for Spi<'d, T, M> {
+    fn drop(&mut self) {
+        T::regs().top_ctrl().modify(|w| w.set_sse(false));
+        crate::rcc::disable::<T>();
+    }
+}
+
+fn new_pin<'d>(pin: PeripheralRef<'d, AnyPin>, fsel: u8, pull: Pull) -> Flex<'d> {
+    let mut pin = Flex::new(pin);
+    pin.set_pull(pull);
+    unsafe { pin.set_fsel_unchecked(fsel) };
+    pin
+}
+
+/// SCLK = clk_peri / CLK_DIV, CLK_DIV in 2..=63.
+fn compute_clk_div(kernel_clock: Hertz, target: Hertz) -> Result<u8, ConfigError> {
+    let div = kernel_clock.0.div_ceil(target.0.max(1));
+    if div > 63 {
+        return Err(ConfigError::FrequencyTooLow);
+    }
+    Ok(div.max(2) as u8)
+}
+
+fn data_ptr<W>(r: Regs) -> *mut W {
+    r.data().as_ptr() as *mut W
+}
+
+fn check_error_flags(r: Regs) -> Result<(), Error> {
+    let status = r.status().read();
+    if status.ror() {
+        r.status().write(|w| w.set_ror(true));
+        return Err(Error::Overrun);
+    }
+    if status.tur() {
+        r.status().write(|w| w.set_tur(true));
+        return Err(Error::Underrun);
+    }
+    Ok(())
+}
+
+fn flush_rx_fifo(r: Regs) {
+    while r.status().read().rne() {
+        let _ = r.data().read();
+    }
+}
+
+/// Wait for the last frame to be shifted out, then drop whatever was
+/// received during a write-only transfer.
+fn finish_write(r: Regs) -> Result<(), Error> {
+    while r.status().read().bsy() {}
+    flush_rx_fifo(r);
+    // Receiving while only writing overruns the RX FIFO on long transfers, that is expected.
+    r.status().write(|w| w.set_ror(true));
+    check_error_flags(r)
+}
+
+fn transfer_word<W: SpiWord>(r: Regs, tx_word: W) -> Result<W, Error> {
+    while !r.status().read().tnf() {}
+    r.data().write_value(tx_word.to_u32());
+
+    loop {
+        let status = r.status().read();
+        if status.ror() {
+            r.status().write(|w| w.set_ror(true));
+            return Err(Error::Overrun);
+        }
+        if status.rne() {
+            break;
+        }
+    }
+    Ok(W::from_u32(r.data().read()))
+}
+
+trait SealedSpiWord {
+    const BITS: u8;
+    fn to_u32(self) -> u32;
+    fn from_u32(v: u32) -> Self;
+}
+
+/// Word sizes usable for SPI.
+#[allow(private_bounds)]
+pub trait SpiWord: SealedSpiWord + Word + Default {}
+
+macro_rules! impl_word {
+    ($T:ty, $bits:expr) => {
+        impl SealedSpiWord for $T {
+            const BITS: u8 = $bits;
+            fn to_u32(self) -> u32 {
+                self as u32
+            }
+            fn from_u32(v: u32) -> Self {
+                v as $T
+            }
+        }
+        impl SpiWord for $T {}
+    };
+}
+
+impl_word!(u8, 8);
+impl_word!(u16, 16);
+
+trait SealedInstance: RccEnableReset + RccGetFreq + Peripheral<P = Self> {
+    const TX_DMA_REQUEST: dma::Request;
+    const RX_DMA_REQUEST: dma::Request;
+
+    fn regs() -> Regs;
+}
+
+/// SPI instance trait.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + 'static {}
+
+pin_trait!(SckPin, Instance);
+pin_trait!(MosiPin, Instance);
+pin_trait!(MisoPin, Instance);
+pin_trait!(CsPin, Instance);
+
+// TODO: move to _generated.rs
+macro_rules! impl_spi {
+    ($inst:ident, $tx_req:expr, $rx_req:expr) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            const TX_DMA_REQUEST: dma::Request = $tx_req;
+            const RX_DMA_REQUEST: dma::Request = $rx_req;
+
+            fn regs() -> Regs {
+                pac::$inst
+            }
+        }
+        impl Instance for crate::peripherals::$inst {}
+    };
+}
+
+impl_spi!(SPI1, 24, 25);
+impl_spi!(SPI2, 26, 27);
+
+// ====================
+
+mod eh02 {
+    use super::*;
+
+    impl<'d, T: Instance, M: PeriMode> embedded_hal_02::blocking::spi::Transfer<u8> for Spi<'d, T, M> {
+        type Error = Error;
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+            self.blocking_transfer_in_place(words)?;
+            Ok(words)
+        }
+    }
+
+    impl<'d, T: Instance, M: PeriMode> embedded_hal_02::blocking::spi::Write<u8> for Spi<'d, T, M> {
+        type Error = Error;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+            self.blocking_write(words)
+        }
+    }
+}
+
+impl embedded_hal_1::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match *self {
+            Self::Overrun => embedded_hal_1::spi::ErrorKind::Overrun,
+            Self::Underrun => embedded_hal_1::spi::ErrorKind::Other,
+        }
+    }
+}
+
+impl<'d, T: Instance, M: PeriMode> embedded_hal_1::spi::ErrorType for Spi<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, W: SpiWord, M: PeriMode> embedded_hal_1::spi::SpiBus<W> for Spi<'d, T, M> {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while T::regs().status().read().bsy() {}
+        Ok(())
+    }
+
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_read(words)
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.blocking_write(words)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.blocking_transfer(read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_transfer_in_place(words)
+    }
+}
+
+impl<'d, T: Instance, W: SpiWord> embedded_hal_async::spi::SpiBus<W> for Spi<'d, T, Async> {
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        while T::regs().status().read().bsy() {}
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.write(words).await
+    }
+
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.read(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clk_div_not_above_target() {
+        let clk = Hertz(48_000_000);
+        assert_eq!(compute_clk_div(clk, Hertz(4_000_000)), Ok(12));
+        assert_eq!(compute_clk_div(clk, Hertz(5_000_000)), Ok(10));
+        assert_eq!(compute_clk_div(clk, Hertz(7_000_000)), Ok(7));
+        assert_eq!(compute_clk_div(clk, Hertz(48_000_000)), Ok(2));
+    }
+
+    #[test]
+    fn clk_div_too_low() {
+        let clk = Hertz(48_000_000);
+        assert_eq!(compute_clk_div(clk, Hertz(762_000)), Ok(63));
+        assert_eq!(compute_clk_div(clk, Hertz(400_000)), Err(ConfigError::FrequencyTooLow));
+        assert_eq!(compute_clk_div(clk, Hertz(0)), Err(ConfigError::FrequencyTooLow));
+    }
+}
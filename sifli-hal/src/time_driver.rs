@@ -7,20 +7,19 @@ use embassy_sync::blocking_mutex::Mutex;
 use embassy_time_driver::{Driver, TICK_HZ};
 use embassy_time_queue_utils::Queue;
 
-#[cfg(feature = "_time-driver-gptim")]
+#[cfg(not(feature = "_time-driver-lptim"))]
 use sifli_pac::gptim::regs;
-#[cfg(feature = "_time-driver-atim")]
-use sifli_pac::atim::regs;
 
 use crate::interrupt::typelevel::Interrupt;
-use crate::rcc::{self, RccGetFreq};
+#[cfg(not(feature = "_time-driver-lptim"))]
+use crate::rcc;
+use crate::rcc::RccGetFreq;
+#[cfg(not(feature = "_time-driver-lptim"))]
 use crate::timer::Instance;
 use crate::{interrupt, peripherals};
+#[cfg(not(feature = "_time-driver-lptim"))]
 use crate::pac::tim_common::vals;
 
-#[cfg(feature = "_time-driver-atim")]
-compile_error!("ATIM time-driver is not ready");
-
 cfg_if::cfg_if! {
     if #[cfg(feature = "time-driver-atim1")] {
         type T = peripherals::ATIM1;
@@ -50,18 +49,42 @@ cfg_if::cfg_if! {
             DRIVER.on_interrupt()
         }
     }
+    else if  #[cfg(feature = "time-driver-lptim1")] {
+        type T = peripherals::LPTIM1;
+        type Irq = interrupt::typelevel::LPTIM1;
+
+        fn regs_lptim() -> crate::pac::lptim::Lptim {
+            crate::pac::LPTIM1
+        }
+
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn LPTIM1() {
+            DRIVER.on_interrupt()
+        }
+    }
+    else if  #[cfg(feature = "time-driver-lptim2")] {
+        type T = peripherals::LPTIM2;
+        type Irq = interrupt::typelevel::LPTIM2;
+
+        fn regs_lptim() -> crate::pac::lptim::Lptim {
+            crate::pac::LPTIM2
+        }
+
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn LPTIM2() {
+            DRIVER.on_interrupt()
+        }
+    }
 }
 
-#[cfg(feature = "_time-driver-gptim")]
+/// ATIM has the GPTIM register layout, with extra registers the time driver doesn't use.
+#[cfg(not(feature = "_time-driver-lptim"))]
 fn regs_gptim() -> crate::pac::gptim::Gptim {
     unsafe { crate::pac::gptim::Gptim::from_ptr(T::regs()) }
 }
 
-#[cfg(feature = "_time-driver-atim")]
-fn regs_atim() -> crate::pac::atim::Atim {
-    unsafe { crate::pac::atim::Atim::from_ptr(T::regs()) }
-}
-
 // Clock timekeeping works with something we call "periods", which are time intervals
 // of 2^15 ticks. The Clock counter value is 16 bits, so one "overflow cycle" is 2 periods.
 //
@@ -79,6 +102,13 @@ fn regs_atim() -> crate::pac::atim::Atim {
 // corresponds to the next period.
 //
 // `period` is a 32bit integer, so It overflows on 2^32 * 2^15 / 32768 seconds of uptime, which is 136 years.
+//
+// LPTIM only has one compare register, which is needed for the alarm, so it counts up to 0x7FFF
+// instead and `period` is only incremented on overflow. `now()` re-reads `period` to detect a
+// racing overflow, and checks the pending overflow flag in case interrupts are disabled. The flag
+// is read before and after the counter: set before, the counter is past the wrap; set only after,
+// the wrap happened during the read and the counter value tells on which side it was read.
+#[cfg(not(feature = "_time-driver-lptim"))]
 fn calc_now(period: u32, counter: u16) -> u64 {
     ((period as u64) << 15) + ((counter as u32 ^ ((period & 1) << 15)) as u64)
 }
@@ -101,40 +131,30 @@ pub(crate) struct RtcDriver {
     /// Number of 2^15 periods elapsed since boot.
     period: AtomicU32,
     alarm: Mutex<CriticalSectionRawMutex, AlarmState>,
-    // #[cfg(feature = "low-power")]
-    // rtc: Mutex<CriticalSectionRawMutex, Cell<Option<&'static Rtc>>>,
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    wakeup: Mutex<CriticalSectionRawMutex, Cell<Option<crate::low_power::WakeupTimer>>>,
     queue: Mutex<CriticalSectionRawMutex, RefCell<Queue>>,
 }
 
 embassy_time_driver::time_driver_impl!(static DRIVER: RtcDriver = RtcDriver {
     period: AtomicU32::new(0),
     alarm: Mutex::const_new(CriticalSectionRawMutex::new(), AlarmState::new()),
-    // #[cfg(feature = "low-power")]
-    // rtc: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    wakeup: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
     queue: Mutex::new(RefCell::new(Queue::new()))
 });
 
+#[cfg(not(feature = "_time-driver-lptim"))]
 impl RtcDriver {
     fn init(&'static self, cs: critical_section::CriticalSection) {
-        #[cfg(feature = "_time-driver-gptim")]
         let r = regs_gptim();
-        #[cfg(feature = "_time-driver-atim")]
-        let r = regs_atim();
 
         rcc::enable_and_reset_with_cs::<T>(cs);
 
-        let timer_freq = T::frequency().unwrap().0;
-
         r.cr1().modify(|w| w.set_cen(false));
         r.cnt().write(|w| w.set_cnt(0));
 
-        let psc = timer_freq / TICK_HZ as u32 - 1;
-        let psc: u16 = match psc.try_into() {
-            Err(_) => panic!("psc division overflow: {}", psc),
-            Ok(n) => n,
-        };
-
-        r.psc().write_value(regs::Psc(psc as _));
+        r.psc().write_value(regs::Psc(Self::prescaler() as _));
         r.arr().write(|w| w.set_arr(u16::MAX.into()));
 
         // Set URS, generate update and clear URS
@@ -154,14 +174,45 @@ impl RtcDriver {
         <T as crate::timer::Instance>::Interrupt::unpend();
         unsafe { <T as crate::timer::Instance>::Interrupt::enable() };
 
+        // The timer clock follows hclk changes when it is pclk1, e.g. for GPTIM1.
+        unwrap!(crate::pmu::dvfs::add_listener(|_| DRIVER.on_clock_change()));
+
         r.cr1().modify(|w| w.set_cen(true));
     }
 
+    fn prescaler() -> u16 {
+        let timer_freq = T::frequency().unwrap().0;
+
+        let psc = timer_freq / TICK_HZ as u32 - 1;
+        match psc.try_into() {
+            Err(_) => panic!("psc division overflow: {}", psc),
+            Ok(n) => n,
+        }
+    }
+
+    /// Follow a change of the timer clock, made by `pmu::dvfs::set_hclk`.
+    fn on_clock_change(&self) {
+        let r = regs_gptim();
+        let psc = Self::prescaler();
+
+        critical_section::with(|_| {
+            if r.psc().read().0 as u16 == psc {
+                return;
+            }
+
+            let cnt = r.cnt().read().cnt();
+            r.psc().write_value(regs::Psc(psc as _));
+
+            // Load the prescaler now. UG also clears the counter, restore it.
+            r.cr1().modify(|w| w.set_urs(vals::URS::CounterOnly));
+            r.egr().write(|w| w.set_ug(true));
+            r.cr1().modify(|w| w.set_urs(vals::URS::AnyEvent));
+            r.cnt().write(|w| w.set_cnt(cnt));
+        })
+    }
+
     fn on_interrupt(&self) {
-        #[cfg(feature = "_time-driver-gptim")]
         let r = regs_gptim();
-        #[cfg(feature = "_time-driver-atim")]
-        let r = regs_atim();
 
         critical_section::with(|cs| {
             let sr = r.sr().read();
@@ -190,10 +241,7 @@ impl RtcDriver {
     }
 
     fn next_period(&self) {
-        #[cfg(feature = "_time-driver-gptim")]
         let r = regs_gptim();
-        #[cfg(feature = "_time-driver-atim")]
-        let r = regs_atim();
 
         // We only modify the period from the timer interrupt, so we know this can't race.
         let period = self.period.load(Ordering::Relaxed) + 1;
@@ -214,6 +262,138 @@ impl RtcDriver {
         })
     }
 
+}
+
+#[cfg(feature = "_time-driver-lptim")]
+impl RtcDriver {
+    fn init(&'static self, _cs: critical_section::CriticalSection) {
+        let r = regs_lptim();
+
+        // LPTIM runs from clk_rtc, and is never disabled or reset by HPSYS_RCC.
+        let timer_freq = T::frequency().unwrap().0;
+        let div = timer_freq / TICK_HZ as u32;
+        assert!(
+            div * TICK_HZ as u32 == timer_freq && div.is_power_of_two() && div <= 128,
+            "clk_rtc can't be divided down to TICK_HZ"
+        );
+
+        r.cr().modify(|w| w.set_enable(false));
+        r.cfgr().write(|w| w.set_presc(div.trailing_zeros() as u8));
+
+        // IER can only be written while the timer is disabled, so both interrupts stay
+        // enabled and the compare match is filtered in `on_interrupt`.
+        r.icr().write(|w| {
+            w.set_arrmcf(true);
+            w.set_cmpmcf(true);
+        });
+        r.ier().write(|w| {
+            w.set_arrmie(true);
+            w.set_cmpmie(true);
+        });
+
+        // ARR and CMP can only be written while the timer is enabled.
+        r.cr().modify(|w| w.set_enable(true));
+        r.arr().write(|w| w.set_arr(0x7FFF));
+        r.cmp().write(|w| w.set_cmp(0x7FFF));
+
+        Irq::unpend();
+        unsafe { Irq::enable() };
+
+        #[cfg(feature = "low-power")]
+        crate::low_power::enable_wakeup::<T>();
+
+        r.cr().modify(|w| w.set_cntstrt(true));
+    }
+
+    fn on_interrupt(&self) {
+        let r = regs_lptim();
+
+        critical_section::with(|cs| {
+            let isr = r.isr().read();
+            r.icr().write(|w| {
+                w.set_arrmcf(isr.arrm());
+                w.set_cmpmcf(isr.cmpm());
+            });
+
+            // Overflow
+            if isr.arrm() {
+                self.next_period();
+            }
+
+            // The compare register may hold a stale value from another period.
+            if isr.cmpm() && self.alarm.borrow(cs).timestamp.get() <= self.now() {
+                self.trigger_alarm(cs);
+            }
+        })
+    }
+
+    fn next_period(&self) {
+        let r = regs_lptim();
+
+        // We only modify the period from the timer interrupt, so we know this can't race.
+        let period = self.period.load(Ordering::Relaxed) + 1;
+        self.period.store(period, Ordering::Relaxed);
+
+        critical_section::with(move |cs| {
+            let at = self.alarm.borrow(cs).timestamp.get();
+            if at >> 15 == period as u64 {
+                // The alarm is in this period, arm the compare register.
+                r.cmp().write(|w| w.set_cmp((at & 0x7FFF) as _));
+            }
+        })
+    }
+
+    /// Read the counter, which runs from an asynchronous clock: wait for two
+    /// consecutive reads to match.
+    fn read_counter(&self) -> u32 {
+        let r = regs_lptim();
+        let mut cnt = r.cnt().read().cnt() as u32;
+        loop {
+            let again = r.cnt().read().cnt() as u32;
+            if again == cnt {
+                return cnt;
+            }
+            cnt = again;
+        }
+    }
+
+    fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
+        let r = regs_lptim();
+
+        self.alarm.borrow(cs).timestamp.set(timestamp);
+
+        let t = self.now();
+        if timestamp <= t {
+            // If alarm timestamp has passed the alarm will not fire.
+            // Return `false` to indicate that.
+            self.alarm.borrow(cs).timestamp.set(u64::MAX);
+
+            return false;
+        }
+
+        // Arm the compare register if the alarm is in the current period.
+        // Otherwise, `next_period` will arm it.
+        if timestamp >> 15 == t >> 15 {
+            r.cmp().write(|w| w.set_cmp((timestamp & 0x7FFF) as _));
+        }
+
+        // Reevaluate if the alarm timestamp is still in the future
+        let t = self.now();
+        if timestamp <= t {
+            // If alarm timestamp has passed since we set it, we have a race condition and
+            // the alarm may or may not have fired.
+            // It is the caller's responsibility to handle this ambiguity.
+            self.alarm.borrow(cs).timestamp.set(u64::MAX);
+
+            return false;
+        }
+
+        // We're confident the alarm will ring in the future.
+        true
+    }
+}
+
+impl RtcDriver {
     fn trigger_alarm(&self, cs: CriticalSection) {
         let mut next = self
             .queue
@@ -233,129 +413,134 @@ impl RtcDriver {
         Low-power private functions: all operate within a critical seciton
     */
 
-    // #[cfg(feature = "low-power")]
-    // /// Compute the approximate amount of time until the next alarm
-    // fn time_until_next_alarm(&self, cs: CriticalSection) -> embassy_time::Duration {
-    //     let now = self.now() + 32;
-
-    //     embassy_time::Duration::from_ticks(self.alarm.borrow(cs).timestamp.get().saturating_sub(now))
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Add the given offset to the current time
-    // fn add_time(&self, offset: embassy_time::Duration, cs: CriticalSection) {
-    //     let offset = offset.as_ticks();
-    //     let cnt = regs_gptim().cnt().read().cnt() as u32;
-    //     let period = self.period.load(Ordering::SeqCst);
-
-    //     // Correct the race, if it exists
-    //     let period = if period & 1 == 1 && cnt < u16::MAX as u32 / 2 {
-    //         period + 1
-    //     } else {
-    //         period
-    //     };
-
-    //     // Normalize to the full overflow
-    //     let period = (period / 2) * 2;
-
-    //     // Add the offset
-    //     let period = period + 2 * (offset / u16::MAX as u64) as u32;
-    //     let cnt = cnt + (offset % u16::MAX as u64) as u32;
-
-    //     let (cnt, period) = if cnt > u16::MAX as u32 {
-    //         (cnt - u16::MAX as u32, period + 2)
-    //     } else {
-    //         (cnt, period)
-    //     };
-
-    //     let period = if cnt > u16::MAX as u32 / 2 { period + 1 } else { period };
-
-    //     self.period.store(period, Ordering::SeqCst);
-    //     regs_gptim().cnt().write(|w| w.set_cnt(cnt as u16));
-
-    //     // Now, recompute alarm
-    //     let alarm = self.alarm.borrow(cs);
-
-    //     if !self.set_alarm(cs, alarm.timestamp.get()) {
-    //         // If the alarm timestamp has passed, we need to trigger it
-    //         self.trigger_alarm(cs);
-    //     }
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Stop the wakeup alarm, if enabled, and add the appropriate offset
-    // fn stop_wakeup_alarm(&self, cs: CriticalSection) {
-    //     if let Some(offset) = self.rtc.borrow(cs).get().unwrap().stop_wakeup_alarm(cs) {
-    //         self.add_time(offset, cs);
-    //     }
-    // }
-
-    // /*
-    //     Low-power public functions: all create a critical section
-    // */
-    // #[cfg(feature = "low-power")]
-    // /// Set the rtc but panic if it's already been set
-    // pub(crate) fn set_rtc(&self, rtc: &'static Rtc) {
-    //     critical_section::with(|cs| {
-    //         rtc.stop_wakeup_alarm(cs);
-
-    //         assert!(self.rtc.borrow(cs).replace(Some(rtc)).is_none())
-    //     });
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// The minimum pause time beyond which the executor will enter a low-power state.
-    // pub(crate) const MIN_STOP_PAUSE: embassy_time::Duration = embassy_time::Duration::from_millis(250);
-
-    // #[cfg(feature = "low-power")]
-    // /// Pause the timer if ready; return err if not
-    // pub(crate) fn pause_time(&self) -> Result<(), ()> {
-    //     critical_section::with(|cs| {
-    //         /*
-    //             If the wakeup timer is currently running, then we need to stop it and
-    //             add the elapsed time to the current time, as this will impact the result
-    //             of `time_until_next_alarm`.
-    //         */
-    //         self.stop_wakeup_alarm(cs);
-
-    //         let time_until_next_alarm = self.time_until_next_alarm(cs);
-    //         if time_until_next_alarm < Self::MIN_STOP_PAUSE {
-    //             Err(())
-    //         } else {
-    //             self.rtc
-    //                 .borrow(cs)
-    //                 .get()
-    //                 .unwrap()
-    //                 .start_wakeup_alarm(time_until_next_alarm, cs);
-
-    //             regs_gptim().cr1().modify(|w| w.set_cen(false));
-
-    //             Ok(())
-    //         }
-    //     })
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Resume the timer with the given offset
-    // pub(crate) fn resume_time(&self) {
-    //     if regs_gptim().cr1().read().cen() {
-    //         // Time isn't currently stopped
-
-    //         return;
-    //     }
-
-    //     critical_section::with(|cs| {
-    //         self.stop_wakeup_alarm(cs);
-
-    //         regs_gptim().cr1().modify(|w| w.set_cen(true));
-    //     })
-    // }
+    #[cfg(feature = "low-power")]
+    /// Compute the approximate amount of time until the next alarm
+    fn time_until_next_alarm(&self, cs: CriticalSection) -> embassy_time::Duration {
+        let now = self.now() + 32;
+
+        embassy_time::Duration::from_ticks(self.alarm.borrow(cs).timestamp.get().saturating_sub(now))
+    }
+
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    /// Add the given offset to the current time
+    fn add_time(&self, offset: embassy_time::Duration, cs: CriticalSection) {
+        let r = regs_gptim();
+
+        // The timer is stopped and we are in a critical section, `now()` accounts for
+        // a pending overflow or half overflow.
+        let t = self.now() + offset.as_ticks();
+
+        // Clear the flags already accounted for, and move the counter.
+        r.sr().modify(|w| {
+            w.set_uif(false);
+            w.set_ccif(0, false);
+        });
+        self.period.store((t >> 15) as u32, Ordering::SeqCst);
+        r.cnt().write(|w| w.set_cnt((t & 0xFFFF) as _));
+
+        // Now, recompute alarm
+        let alarm = self.alarm.borrow(cs);
+
+        if !self.set_alarm(cs, alarm.timestamp.get()) {
+            // If the alarm timestamp has passed, we need to trigger it
+            self.trigger_alarm(cs);
+        }
+    }
+
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    /// Stop the wakeup alarm, if enabled, and add the appropriate offset
+    fn stop_wakeup_alarm(&self, cs: CriticalSection) {
+        if let Some(wakeup) = self.wakeup.borrow(cs).get() {
+            if let Some(offset) = wakeup.stop_wakeup_alarm(cs) {
+                self.add_time(offset, cs);
+            }
+        }
+    }
+
+    /*
+        Low-power public functions: all create a critical section
+    */
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    /// Set the wakeup timer but panic if it's already been set
+    pub(crate) fn set_wakeup_timer(&self, wakeup: crate::low_power::WakeupTimer) {
+        critical_section::with(|cs| {
+            wakeup.stop_wakeup_alarm(cs);
+
+            assert!(self.wakeup.borrow(cs).replace(Some(wakeup)).is_none())
+        });
+    }
+
+    #[cfg(feature = "low-power")]
+    /// The minimum pause time beyond which the executor will enter a low-power state.
+    pub(crate) const MIN_STOP_PAUSE: embassy_time::Duration = embassy_time::Duration::from_millis(250);
+
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    /// Pause the timer if ready; return err if not
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        critical_section::with(|cs| {
+            /*
+                If the wakeup timer is currently running, then we need to stop it and
+                add the elapsed time to the current time, as this will impact the result
+                of `time_until_next_alarm`.
+            */
+            self.stop_wakeup_alarm(cs);
+
+            let Some(wakeup) = self.wakeup.borrow(cs).get() else {
+                return Err(());
+            };
+
+            let time_until_next_alarm = self.time_until_next_alarm(cs);
+            if time_until_next_alarm < Self::MIN_STOP_PAUSE {
+                Err(())
+            } else {
+                wakeup.start_wakeup_alarm(time_until_next_alarm, cs);
+
+                regs_gptim().cr1().modify(|w| w.set_cen(false));
+
+                Ok(())
+            }
+        })
+    }
 
+    #[cfg(all(feature = "low-power", feature = "_time-driver-lptim"))]
+    /// Check whether it is worth sleeping; return err if not
+    ///
+    /// The LPTIM keeps counting and wakes the CPU up in sleep, so it is never paused.
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        critical_section::with(|cs| {
+            if self.time_until_next_alarm(cs) < Self::MIN_STOP_PAUSE {
+                Err(())
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    #[cfg(all(feature = "low-power", not(feature = "_time-driver-lptim")))]
+    /// Resume the timer with the given offset
+    pub(crate) fn resume_time(&self) {
+        if regs_gptim().cr1().read().cen() {
+            // Time isn't currently stopped
+
+            return;
+        }
+
+        critical_section::with(|cs| {
+            self.stop_wakeup_alarm(cs);
+
+            regs_gptim().cr1().modify(|w| w.set_cen(true));
+        })
+    }
+
+    #[cfg(all(feature = "low-power", feature = "_time-driver-lptim"))]
+    /// Resume the timer; the LPTIM is never paused
+    pub(crate) fn resume_time(&self) {}
+}
+
+#[cfg(not(feature = "_time-driver-lptim"))]
+impl RtcDriver {
     fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
-        #[cfg(feature = "_time-driver-gptim")]
         let r = regs_gptim();
-        #[cfg(feature = "_time-driver-atim")]
-        let r = regs_atim();
 
         let n = 0;
         self.alarm.borrow(cs).timestamp.set(timestamp);
@@ -399,11 +584,9 @@ impl RtcDriver {
 }
 
 impl Driver for RtcDriver {
+    #[cfg(not(feature = "_time-driver-lptim"))]
     fn now(&self) -> u64 {
-        #[cfg(feature = "_time-driver-gptim")]
         let r = regs_gptim();
-        #[cfg(feature = "_time-driver-atim")]
-        let r = regs_atim();
 
         let period = self.period.load(Ordering::Relaxed);
         compiler_fence(Ordering::Acquire);
@@ -411,6 +594,27 @@ impl Driver for RtcDriver {
         calc_now(period, counter as _)
     }
 
+    #[cfg(feature = "_time-driver-lptim")]
+    fn now(&self) -> u64 {
+        let r = regs_lptim();
+
+        loop {
+            let period = self.period.load(Ordering::Relaxed);
+            compiler_fence(Ordering::Acquire);
+            // Overflow not handled yet by `on_interrupt`. If it was already
+            // flagged before the counter read, the counter is in the new period,
+            // however long the flag has been pending. If it was flagged in between,
+            // a low counter is after the wrap and a high one before it.
+            let pending_before = r.isr().read().arrm();
+            let counter = self.read_counter();
+            let pending = pending_before || (r.isr().read().arrm() && counter < 0x4000);
+            compiler_fence(Ordering::Acquire);
+            if self.period.load(Ordering::Relaxed) == period {
+                return ((period + pending as u32) as u64) << 15 | counter as u64;
+            }
+        }
+    }
+
     fn schedule_wake(&self, at: u64, waker: &core::task::Waker) {
         critical_section::with(|cs| {
             let mut queue = self.queue.borrow(cs).borrow_mut();
@@ -425,10 +629,10 @@ impl Driver for RtcDriver {
     }
 }
 
-// #[cfg(feature = "low-power")]
-// pub(crate) fn get_driver() -> &'static RtcDriver {
-//     &DRIVER
-// }
+#[cfg(feature = "low-power")]
+pub(crate) fn get_driver() -> &'static RtcDriver {
+    &DRIVER
+}
 
 pub(crate) fn init() {
     critical_section::with(|cs| DRIVER.init(cs));
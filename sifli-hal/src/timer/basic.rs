@@ -0,0 +1,264 @@
+//! Basic timer driver for BTIM.
+//!
+//! The basic timers only have a counter, a prescaler and a reload register.
+//! This driver uses them for periodic ticks, one-shot delays and to trigger
+//! other peripherals through TRGO.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::{BimInstance, TimerBits};
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::pac::tim_common::vals;
+use crate::time::Hertz;
+use crate::{interrupt, pac};
+
+/// Update interrupt handler.
+pub struct InterruptHandler<T: BimInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: BimInstance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = pac::btim::Btim::from_ptr(T::regs());
+        let state = T::state();
+
+        let sr = regs.sr().read();
+        // Bits in SR are "write 0 to clear", so write the bitwise NOT.
+        regs.sr().write_value(pac::btim::regs::Sr(!sr.0));
+
+        if sr.uif() {
+            // The overflow count is used as the tick count.
+            state.overflows.fetch_add(1, Ordering::Relaxed);
+            state.up_waker.wake();
+        }
+    }
+}
+
+/// Master mode, the event sent to TRGO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MasterMode {
+    /// The UG bit is used as TRGO.
+    Reset = 0b000,
+    /// The counter enable is used as TRGO.
+    Enable = 0b001,
+    /// The update event is used as TRGO, e.g. to start an ADC conversion every period.
+    Update = 0b010,
+}
+
+/// Basic timer driver.
+pub struct BasicTimer<'d, T: BimInstance> {
+    _tim: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: BimInstance> Drop for BasicTimer<'d, T> {
+    fn drop(&mut self) {
+        T::Interrupt::disable();
+        crate::rcc::disable::<T>();
+    }
+}
+
+impl<'d, T: BimInstance> BasicTimer<'d, T> {
+    /// Create a new basic timer driver.
+    pub fn new(tim: impl Peripheral<P = T> + 'd, _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd) -> Self {
+        into_ref!(tim);
+
+        crate::rcc::enable_and_reset::<T>();
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self { _tim: tim }
+    }
+
+    fn regs(&self) -> pac::btim::Btim {
+        unsafe { pac::btim::Btim::from_ptr(T::regs()) }
+    }
+
+    fn max_arr(&self) -> u32 {
+        match T::BITS {
+            TimerBits::Bits16 => u16::MAX as u32,
+            TimerBits::Bits32 => u32::MAX,
+        }
+    }
+
+    /// Stop the timer.
+    pub fn stop(&mut self) {
+        self.regs().cr1().modify(|w| w.set_cen(false));
+    }
+
+    /// Set the prescaler and reload value, and load them with an update event
+    /// that doesn't set UIF.
+    fn configure(&mut self, psc: u16, arr: u32, one_pulse: bool) {
+        let r = self.regs();
+        r.cr1().modify(|w| {
+            w.set_cen(false);
+            w.set_opm(one_pulse);
+        });
+        r.cnt().write(|w| w.set_cnt(0));
+        r.psc().write_value(pac::btim::regs::Psc(psc as _));
+        r.arr().write(|w| w.set_arr(arr as _));
+
+        r.cr1().modify(|w| w.set_urs(vals::URS::CounterOnly));
+        r.egr().write(|w| w.set_ug(true));
+        r.cr1().modify(|w| w.set_urs(vals::URS::AnyEvent));
+        r.sr().write_value(pac::btim::regs::Sr(0));
+    }
+
+    /// Prescaler giving a counter clock of `freq`.
+    fn prescaler_for(&self, freq: Hertz) -> u16 {
+        let timer_f = T::frequency().unwrap().0;
+        unwrap!((timer_f / freq.0).saturating_sub(1).try_into())
+    }
+
+    /// Start generating an update event, and a tick, at `freq`.
+    ///
+    /// Panics if `freq` is 0 or above the timer clock.
+    pub fn start_periodic(&mut self, freq: Hertz) {
+        assert!(freq.0 > 0);
+        let timer_f = T::frequency().unwrap().0;
+
+        let ticks = (timer_f / freq.0) as u64;
+        assert!(ticks > 0, "frequency above the timer clock");
+        let psc: u16 = unwrap!(((ticks - 1) / (self.max_arr() as u64 + 1)).try_into());
+        let arr = ticks / (psc as u64 + 1) - 1;
+
+        self.configure(psc, arr as u32, false);
+
+        let r = self.regs();
+        r.dier().modify(|w| w.set_uie(true));
+        r.cr1().modify(|w| w.set_cen(true));
+    }
+
+    /// Number of ticks since the timer was created, wrapping around.
+    pub fn ticks(&self) -> u32 {
+        T::state().overflows.load(Ordering::Relaxed)
+    }
+
+    /// Asynchronously wait for the next tick of [`start_periodic`](Self::start_periodic).
+    pub async fn wait_next_tick(&mut self) {
+        let state = T::state();
+        let start = state.overflows.load(Ordering::Relaxed);
+        poll_fn(|cx| {
+            state.up_waker.register(cx.waker());
+            if state.overflows.load(Ordering::Relaxed) != start {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Select the event sent to TRGO, used to trigger other peripherals.
+    pub fn set_master_mode(&mut self, mode: MasterMode) {
+        self.regs().cr2().modify(|w| w.set_mms(vals::MMS::from_bits(mode as u8)));
+    }
+
+    /// Asynchronously wait for `ticks` ticks of `freq`, using the one-pulse mode.
+    ///
+    /// This stops the periodic ticks.
+    pub async fn one_shot(&mut self, freq: Hertz, ticks: u32) {
+        let psc = self.prescaler_for(freq);
+        let mut remaining = ticks;
+
+        while remaining > 0 {
+            let n = remaining.min(self.max_arr());
+            remaining -= n;
+
+            self.configure(psc, n - 1, true);
+            let r = self.regs();
+            r.dier().modify(|w| w.set_uie(true));
+
+            let state = T::state();
+            let start = state.overflows.load(Ordering::Relaxed);
+            r.cr1().modify(|w| w.set_cen(true));
+
+            let _stop = embassy_hal_internal::drop::OnDrop::new(|| {
+                r.cr1().modify(|w| w.set_cen(false));
+            });
+            poll_fn(|cx| {
+                state.up_waker.register(cx.waker());
+                if state.overflows.load(Ordering::Relaxed) != start {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+        }
+    }
+
+    /// Asynchronously wait for `us` microseconds.
+    pub async fn wait_us(&mut self, us: u32) {
+        self.one_shot(Hertz::mhz(1), us).await
+    }
+
+    /// Asynchronously wait for `ms` milliseconds.
+    pub async fn wait_ms(&mut self, ms: u32) {
+        // A 10 kHz counter keeps the prescaler within 16 bits up to a 655 MHz timer clock.
+        let mut remaining = ms as u64 * 10;
+        while remaining > 0 {
+            let n = remaining.min(u32::MAX as u64) as u32;
+            remaining -= n as u64;
+            self.one_shot(Hertz::khz(10), n).await
+        }
+    }
+
+    /// Busy-wait for `ticks` cycles of the timer kernel clock.
+    ///
+    /// This stops the periodic ticks.
+    fn blocking_delay_ticks(&mut self, ticks: u64) {
+        let r = self.regs();
+        r.dier().modify(|w| w.set_uie(false));
+
+        let mut remaining = ticks;
+        while remaining > 0 {
+            let n = remaining.min(self.max_arr() as u64) as u32;
+            remaining -= n as u64;
+
+            self.configure(0, n.max(1) - 1, true);
+            r.cr1().modify(|w| w.set_cen(true));
+            while !r.sr().read().uif() {}
+        }
+        r.sr().write_value(pac::btim::regs::Sr(0));
+    }
+}
+
+impl<'d, T: BimInstance> embedded_hal_1::delay::DelayNs for BasicTimer<'d, T> {
+    fn delay_ns(&mut self, ns: u32) {
+        let timer_f = T::frequency().unwrap().0 as u64;
+        self.blocking_delay_ticks((ns as u64 * timer_f).div_ceil(1_000_000_000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        let timer_f = T::frequency().unwrap().0 as u64;
+        self.blocking_delay_ticks((us as u64 * timer_f).div_ceil(1_000_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        let timer_f = T::frequency().unwrap().0 as u64;
+        self.blocking_delay_ticks((ms as u64 * timer_f).div_ceil(1_000));
+    }
+}
+
+impl<'d, T: BimInstance> embedded_hal_async::delay::DelayNs for BasicTimer<'d, T> {
+    async fn delay_ns(&mut self, ns: u32) {
+        let timer_f = T::frequency().unwrap().0 as u64;
+        let ticks = (ns as u64 * timer_f).div_ceil(1_000_000_000);
+        self.one_shot(T::frequency().unwrap(), ticks as u32).await
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.wait_us(us).await
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.wait_ms(ms).await
+    }
+}
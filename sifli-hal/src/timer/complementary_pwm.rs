@@ -0,0 +1,207 @@
+//! PWM driver with complementary output support.
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+pub use super::low_level::OutputPolarity;
+use super::low_level::{compute_dead_time_value, CountingMode, OutputCompareMode, Timer};
+use super::simple_pwm::PwmPin;
+use super::{
+    new_pin, AtimInstance, BreakInput2Pin, BreakInputPin, Ch1, Ch2, Ch3, Ch4, Channel, ComplementaryChannelPin,
+    Signal, TimerChannel,
+};
+use crate::gpio::Flex;
+use crate::time::Hertz;
+
+/// Complementary PWM pin wrapper.
+///
+/// This wraps a pin to make it usable with PWM.
+pub struct ComplementaryPwmPin<'d, T, C> {
+    _pin: Flex<'d>,
+    phantom: PhantomData<(T, C)>,
+}
+
+impl<'d, T: AtimInstance, C: TimerChannel> ComplementaryPwmPin<'d, T, C> {
+    /// Create a new complementary PWM pin instance.
+    pub fn new(pin: impl Peripheral<P = impl ComplementaryChannelPin<T, C>> + 'd) -> Self {
+        into_ref!(pin);
+        Self {
+            _pin: new_pin::<T>(pin.map_into(), Signal::ChN(C::CHANNEL)),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Break input pin wrapper.
+///
+/// When the break input becomes active, the main output enable (MOE) bit is
+/// cleared by hardware and all outputs go to their idle state.
+pub struct BreakPin<'d, T> {
+    _pin: Flex<'d>,
+    n: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<'d, T: AtimInstance> BreakPin<'d, T> {
+    /// Create a new BK break input pin.
+    pub fn new_bk(pin: impl Peripheral<P = impl BreakInputPin<T>> + 'd) -> Self {
+        into_ref!(pin);
+        Self {
+            _pin: new_pin::<T>(pin.map_into(), Signal::Bk),
+            n: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Create a new BK2 break input pin.
+    pub fn new_bk2(pin: impl Peripheral<P = impl BreakInput2Pin<T>> + 'd) -> Self {
+        into_ref!(pin);
+        Self {
+            _pin: new_pin::<T>(pin.map_into(), Signal::Bk2),
+            n: 1,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Break input configuration.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BreakConfig {
+    /// Polarity of the break input. `true` means the break is active when the input is high.
+    pub active_high: bool,
+    /// Re-enable the outputs automatically at the next update event once the break is released.
+    pub automatic_output_enable: bool,
+}
+
+impl Default for BreakConfig {
+    fn default() -> Self {
+        Self {
+            active_high: false,
+            automatic_output_enable: false,
+        }
+    }
+}
+
+/// PWM driver with support for standard and complementary outputs.
+pub struct ComplementaryPwm<'d, T: AtimInstance> {
+    inner: Timer<'d, T>,
+}
+
+impl<'d, T: AtimInstance> ComplementaryPwm<'d, T> {
+    /// Create a new complementary PWM driver.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: Option<PwmPin<'d, T, Ch1>>,
+        _ch1n: Option<ComplementaryPwmPin<'d, T, Ch1>>,
+        _ch2: Option<PwmPin<'d, T, Ch2>>,
+        _ch2n: Option<ComplementaryPwmPin<'d, T, Ch2>>,
+        _ch3: Option<PwmPin<'d, T, Ch3>>,
+        _ch3n: Option<ComplementaryPwmPin<'d, T, Ch3>>,
+        _ch4: Option<PwmPin<'d, T, Ch4>>,
+        freq: Hertz,
+        counting_mode: CountingMode,
+    ) -> Self {
+        Self::new_inner(tim, freq, counting_mode)
+    }
+
+    fn new_inner(tim: impl Peripheral<P = T> + 'd, freq: Hertz, counting_mode: CountingMode) -> Self {
+        let mut this = Self { inner: Timer::new(tim) };
+
+        this.inner.set_counting_mode(counting_mode);
+        this.set_frequency(freq);
+        this.inner.set_autoreload_preload(true);
+        this.inner.start();
+
+        this.inner.set_moe(true);
+
+        [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4]
+            .iter()
+            .for_each(|&channel| {
+                this.inner.set_output_compare_mode(channel, OutputCompareMode::PwmMode1);
+                this.inner.set_output_compare_preload(channel, true);
+            });
+
+        this
+    }
+
+    /// Enable the given channel.
+    pub fn enable(&mut self, channel: Channel) {
+        self.inner.enable_channel(channel, true);
+        self.inner.enable_complementary_channel(channel, true);
+    }
+
+    /// Disable the given channel.
+    pub fn disable(&mut self, channel: Channel) {
+        self.inner.enable_complementary_channel(channel, false);
+        self.inner.enable_channel(channel, false);
+    }
+
+    /// Set PWM frequency.
+    ///
+    /// Note: when you call this, the max duty value changes, so you will have to
+    /// call `set_duty` on all channels with the duty calculated based on the new max duty.
+    pub fn set_frequency(&mut self, freq: Hertz) {
+        let multiplier = if self.inner.get_counting_mode().is_center_aligned() {
+            2u8
+        } else {
+            1u8
+        };
+        self.inner.set_frequency(freq * multiplier);
+    }
+
+    /// Get max duty value.
+    ///
+    /// This value depends on the configured frequency and the timer's clock rate from RCC.
+    pub fn get_max_duty(&self) -> u32 {
+        self.inner.get_max_compare_value() + 1
+    }
+
+    /// Set the duty for a given channel.
+    ///
+    /// The value ranges from 0 for 0% duty, to [`get_max_duty`](Self::get_max_duty) for 100% duty, both included.
+    pub fn set_duty(&mut self, channel: Channel, duty: u32) {
+        assert!(duty <= self.get_max_duty());
+        self.inner.set_compare_value(channel, duty)
+    }
+
+    /// Set the output polarity for a given channel.
+    pub fn set_polarity(&mut self, channel: Channel, polarity: OutputPolarity) {
+        self.inner.set_output_polarity(channel, polarity);
+        self.inner.set_complementary_output_polarity(channel, polarity);
+    }
+
+    /// Set the dead time, in timer kernel clock ticks.
+    ///
+    /// Values that can't be represented exactly are rounded to the closest one.
+    pub fn set_dead_time(&mut self, value: u16) {
+        let (ckd, value) = compute_dead_time_value(value);
+
+        self.inner.set_dead_time_clock_division(ckd);
+        self.inner.set_dead_time_value(value);
+    }
+
+    /// Enable the break input `pin` with `config`.
+    ///
+    /// When the break input becomes active, all outputs are forced to their idle
+    /// state until [`clear_break`](Self::clear_break) is called.
+    pub fn enable_break(&mut self, pin: &BreakPin<'d, T>, config: BreakConfig) {
+        self.inner.set_break(pin.n, true, config.active_high);
+        self.inner.set_automatic_output_enable(config.automatic_output_enable);
+    }
+
+    /// Disable the break input `pin`.
+    pub fn disable_break(&mut self, pin: &BreakPin<'d, T>) {
+        self.inner.set_break(pin.n, false, false);
+    }
+
+    /// Re-enable the outputs after a break event.
+    ///
+    /// Only needed when [`BreakConfig::automatic_output_enable`] is not set.
+    pub fn clear_break(&mut self) {
+        self.inner.set_moe(true);
+    }
+}
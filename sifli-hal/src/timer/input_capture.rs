@@ -0,0 +1,207 @@
+//! Input capture driver.
+
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll};
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+pub use super::low_level::{FilterValue, InputCaptureMode, InputCapturePrescaler, InputTISelection};
+use super::low_level::{CountingMode, Timer};
+use super::{
+    new_pin, Ch1, Ch2, Ch3, Ch4, Channel, ChannelPin, GeneralInstance, InterruptHandler, Signal, TimerChannel,
+};
+use crate::gpio::{Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::time::Hertz;
+
+/// Capture pin wrapper.
+///
+/// This wraps a pin to make it usable with capture.
+pub struct CapturePin<'d, T, C> {
+    _pin: Flex<'d>,
+    phantom: PhantomData<(T, C)>,
+}
+
+impl<'d, T: GeneralInstance, C: TimerChannel> CapturePin<'d, T, C> {
+    /// Create a new capture pin instance.
+    pub fn new(pin: impl Peripheral<P = impl ChannelPin<T, C>> + 'd, pull: Pull) -> Self {
+        into_ref!(pin);
+        let mut pin = new_pin::<T>(pin.map_into(), Signal::Ch(C::CHANNEL));
+        pin.set_pull(pull);
+        Self {
+            _pin: pin,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Input capture driver.
+///
+/// The counter free-runs over 16 bits, the driver counts its overflows in the
+/// interrupt handler and extends the captured values to 32-bit timestamps in
+/// ticks of the frequency given to [`new`](Self::new).
+pub struct InputCapture<'d, T: GeneralInstance> {
+    inner: Timer<'d, T>,
+}
+
+impl<'d, T: GeneralInstance> InputCapture<'d, T> {
+    /// Create a new input capture driver.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: Option<CapturePin<'d, T, Ch1>>,
+        _ch2: Option<CapturePin<'d, T, Ch2>>,
+        _ch3: Option<CapturePin<'d, T, Ch3>>,
+        _ch4: Option<CapturePin<'d, T, Ch4>>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        Self::new_inner(tim, freq)
+    }
+
+    fn new_inner(tim: impl Peripheral<P = T> + 'd, freq: Hertz) -> Self {
+        let this = Self { inner: Timer::new(tim) };
+
+        this.inner.set_counting_mode(CountingMode::EdgeAlignedUp);
+        this.inner.set_max_compare_value(0xFFFF);
+        this.inner.set_tick_freq(freq);
+
+        T::state().overflows.store(0, Ordering::Relaxed);
+        this.inner.regs_gp().sr().write_value(crate::pac::gptim::regs::Sr(0));
+        this.inner.enable_update_interrupt(true);
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        this.inner.start();
+
+        this
+    }
+
+    /// Enable the given channel.
+    pub fn enable(&mut self, channel: Channel) {
+        self.inner.enable_channel(channel, true);
+    }
+
+    /// Disable the given channel.
+    pub fn disable(&mut self, channel: Channel) {
+        self.inner.enable_channel(channel, false);
+    }
+
+    /// Check whether given channel is enabled
+    pub fn is_enabled(&self, channel: Channel) -> bool {
+        self.inner.get_channel_enable_state(channel)
+    }
+
+    /// Set the input capture prescaler for the given channel.
+    pub fn set_prescaler(&mut self, channel: Channel, prescaler: InputCapturePrescaler) {
+        self.inner.set_input_capture_prescaler(channel, prescaler);
+    }
+
+    /// Set the input capture filter for the given channel.
+    pub fn set_filter(&mut self, channel: Channel, filter: FilterValue) {
+        self.inner.set_input_capture_filter(channel, filter);
+    }
+
+    /// Get the tick frequency of the timestamps.
+    pub fn tick_freq(&self) -> Hertz {
+        self.inner.get_tick_freq()
+    }
+
+    /// Get the current 32-bit extended counter value.
+    pub fn now(&self) -> u32 {
+        let state = T::state();
+        critical_section::with(|_| {
+            let overflows = state.overflows.load(Ordering::Relaxed);
+            let cnt = self.inner.regs_gp().cnt().read().cnt() as u32 & 0xFFFF;
+            // An overflow not handled yet by the interrupt handler.
+            let overflows = if self.inner.regs_gp().sr().read().uif() && cnt < 0x8000 {
+                overflows.wrapping_add(1)
+            } else {
+                overflows
+            };
+            (overflows << 16) | cnt
+        })
+    }
+
+    fn new_future(&self, channel: Channel, mode: InputCaptureMode, tisel: InputTISelection) -> InputCaptureFuture<T> {
+        // Configuration steps from the reference manual, with interrupts instead of polling.
+        self.inner.enable_channel(channel, false);
+        self.inner.set_input_ti_selection(channel, tisel);
+        self.inner.set_input_capture_mode(channel, mode);
+        self.inner.clear_input_interrupt(channel);
+        self.inner.enable_channel(channel, true);
+        self.inner.enable_input_interrupt(channel, true);
+
+        InputCaptureFuture {
+            channel,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Asynchronously wait until the pin sees an edge as configured by `mode`,
+    /// and return the 32-bit timestamp of the edge.
+    pub async fn wait_for_capture(&mut self, channel: Channel, mode: InputCaptureMode) -> u32 {
+        self.new_future(channel, mode, InputTISelection::Normal).await
+    }
+
+    /// Asynchronously wait until the pin sees a rising edge.
+    pub async fn wait_for_rising_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_capture(channel, InputCaptureMode::Rising).await
+    }
+
+    /// Asynchronously wait until the pin sees a falling edge.
+    pub async fn wait_for_falling_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_capture(channel, InputCaptureMode::Falling).await
+    }
+
+    /// Asynchronously wait until the pin sees any edge.
+    pub async fn wait_for_any_edge(&mut self, channel: Channel) -> u32 {
+        self.wait_for_capture(channel, InputCaptureMode::BothEdges).await
+    }
+
+    /// Asynchronously wait until the (alternate) pin sees an edge as configured by `mode`.
+    ///
+    /// Channel 1 captures the input of channel 2 and vice versa, same for channels 3 and 4.
+    pub async fn wait_for_capture_alternate(&mut self, channel: Channel, mode: InputCaptureMode) -> u32 {
+        self.new_future(channel, mode, InputTISelection::Alternate).await
+    }
+}
+
+/// Future resolving to the extended capture value once the interrupt handler latched a capture on `channel`.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub(super) struct InputCaptureFuture<T: GeneralInstance> {
+    pub(super) channel: Channel,
+    pub(super) phantom: PhantomData<T>,
+}
+
+impl<T: GeneralInstance> Drop for InputCaptureFuture<T> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            let regs = unsafe { crate::pac::gptim::Gptim::from_ptr(T::regs()) };
+
+            // disable interrupt enable
+            regs.dier().modify(|w| w.set_ccie(self.channel.index(), false));
+        });
+    }
+}
+
+impl<T: GeneralInstance> Future for InputCaptureFuture<T> {
+    type Output = u32;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let state = T::state();
+        state.cc_waker[self.channel.index()].register(cx.waker());
+
+        let regs = unsafe { crate::pac::gptim::Gptim::from_ptr(T::regs()) };
+
+        // The interrupt handler clears CCIE once it latched the capture.
+        let dier = regs.dier().read();
+        if !dier.ccie(self.channel.index()) {
+            Poll::Ready(state.captures[self.channel.index()].load(Ordering::Relaxed))
+        } else {
+            Poll::Pending
+        }
+    }
+}
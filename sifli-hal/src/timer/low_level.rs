@@ -0,0 +1,731 @@
+//! Low-level timer driver.
+//!
+//! This is an unopinionated, very low-level driver for all GPTIM and ATIM timers. It wraps the register
+//! block and provides convenience methods for the common operations, the higher-level drivers
+//! (e.g. [`SimplePwm`](super::simple_pwm::SimplePwm)) are built on top of it.
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::*;
+use crate::pac::tim_common::vals;
+use crate::time::Hertz;
+
+/// Timer counting mode.
+#[repr(u8)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CountingMode {
+    #[default]
+    /// The timer counts up to the reload value and then resets back to 0.
+    EdgeAlignedUp,
+    /// The timer counts down to 0 and then resets back to the reload value.
+    EdgeAlignedDown,
+    /// The timer counts up to the reload value and then counts back to 0.
+    ///
+    /// The output compare interrupt flags of channels configured in output are
+    /// set when the counter is counting down.
+    CenterAlignedDownInterrupts,
+    /// The timer counts up to the reload value and then counts back to 0.
+    ///
+    /// The output compare interrupt flags of channels configured in output are
+    /// set when the counter is counting up.
+    CenterAlignedUpInterrupts,
+    /// The timer counts up to the reload value and then counts back to 0.
+    ///
+    /// The output compare interrupt flags of channels configured in output are
+    /// set when the counter is counting both up or down.
+    CenterAlignedBothInterrupts,
+}
+
+impl CountingMode {
+    /// Return whether this mode is edge-aligned (up or down).
+    pub fn is_edge_aligned(&self) -> bool {
+        matches!(self, CountingMode::EdgeAlignedUp | CountingMode::EdgeAlignedDown)
+    }
+
+    /// Return whether this mode is center-aligned.
+    pub fn is_center_aligned(&self) -> bool {
+        !self.is_edge_aligned()
+    }
+
+    /// (CMS, DIR) register values.
+    fn bits(&self) -> (u8, u8) {
+        match self {
+            CountingMode::EdgeAlignedUp => (0b00, 0),
+            CountingMode::EdgeAlignedDown => (0b00, 1),
+            CountingMode::CenterAlignedDownInterrupts => (0b01, 0),
+            CountingMode::CenterAlignedUpInterrupts => (0b10, 0),
+            CountingMode::CenterAlignedBothInterrupts => (0b11, 0),
+        }
+    }
+
+    fn from_bits(cms: u8, dir: u8) -> Self {
+        match (cms, dir) {
+            (0b00, 0) => CountingMode::EdgeAlignedUp,
+            (0b00, _) => CountingMode::EdgeAlignedDown,
+            (0b01, _) => CountingMode::CenterAlignedDownInterrupts,
+            (0b10, _) => CountingMode::CenterAlignedUpInterrupts,
+            _ => CountingMode::CenterAlignedBothInterrupts,
+        }
+    }
+}
+
+/// Output compare mode.
+#[repr(u8)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutputCompareMode {
+    /// The comparison between the output compare register TIMx_CCRx and
+    /// the counter TIMx_CNT has no effect on the outputs.
+    Frozen = 0b000,
+    /// Set channel to active level on match. OCxREF signal is forced high when the
+    /// counter TIMx_CNT matches the capture/compare register x (TIMx_CCRx).
+    ActiveOnMatch = 0b001,
+    /// Set channel to inactive level on match. OCxREF signal is forced low when the
+    /// counter TIMx_CNT matches the capture/compare register x (TIMx_CCRx).
+    InactiveOnMatch = 0b010,
+    /// Toggle - OCxREF toggles when TIMx_CNT=TIMx_CCRx.
+    Toggle = 0b011,
+    /// Force inactive level - OCxREF is forced low.
+    ForceInactive = 0b100,
+    /// Force active level - OCxREF is forced high.
+    ForceActive = 0b101,
+    /// PWM mode 1 - In upcounting, channel is active as long as TIMx_CNT<TIMx_CCRx
+    /// else inactive. In downcounting, channel is inactive (OCxREF=0) as long as
+    /// TIMx_CNT>TIMx_CCRx else active (OCxREF=1).
+    PwmMode1 = 0b110,
+    /// PWM mode 2 - In upcounting, channel is inactive as long as
+    /// TIMx_CNT<TIMx_CCRx else active. In downcounting, channel is active as long as
+    /// TIMx_CNT>TIMx_CCRx else inactive.
+    PwmMode2 = 0b111,
+}
+
+/// Timer output pin polarity.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutputPolarity {
+    /// Active high (higher duty value makes the pin spend more time high).
+    ActiveHigh,
+    /// Active low (higher duty value makes the pin spend more time low).
+    ActiveLow,
+}
+
+/// Input capture mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputCaptureMode {
+    /// Rising edge only.
+    Rising,
+    /// Falling edge only.
+    Falling,
+    /// Both rising or falling edges.
+    BothEdges,
+}
+
+/// Input TI selection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputTISelection {
+    /// Normal
+    Normal,
+    /// Alternate
+    Alternate,
+    /// TRC
+    TRC,
+}
+
+impl From<InputTISelection> for u8 {
+    fn from(tisel: InputTISelection) -> Self {
+        match tisel {
+            InputTISelection::Normal => 0b01,
+            InputTISelection::Alternate => 0b10,
+            InputTISelection::TRC => 0b11,
+        }
+    }
+}
+
+/// Input capture prescaler, the number of edges needed for one capture.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputCapturePrescaler {
+    /// Capture on every edge.
+    #[default]
+    Div1 = 0b00,
+    /// Capture once every 2 edges.
+    Div2 = 0b01,
+    /// Capture once every 4 edges.
+    Div4 = 0b10,
+    /// Capture once every 8 edges.
+    Div8 = 0b11,
+}
+
+/// Input capture filter.
+///
+/// `FdtsDivN_M`/`FckIntN_M` sample the input at `f_DTS / N` (or `f_CK_INT`)
+/// and need `M` consecutive equal samples to validate a transition.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FilterValue {
+    /// No filter, sampling is done at `f_DTS`.
+    #[default]
+    NoFilter = 0b0000,
+    FckIntN2 = 0b0001,
+    FckIntN4 = 0b0010,
+    FckIntN8 = 0b0011,
+    FdtsDiv2N6 = 0b0100,
+    FdtsDiv2N8 = 0b0101,
+    FdtsDiv4N6 = 0b0110,
+    FdtsDiv4N8 = 0b0111,
+    FdtsDiv8N6 = 0b1000,
+    FdtsDiv8N8 = 0b1001,
+    FdtsDiv16N5 = 0b1010,
+    FdtsDiv16N6 = 0b1011,
+    FdtsDiv16N8 = 0b1100,
+    FdtsDiv32N5 = 0b1101,
+    FdtsDiv32N6 = 0b1110,
+    FdtsDiv32N8 = 0b1111,
+}
+
+/// Slave mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaveMode {
+    /// Slave mode disabled, the prescaler is clocked directly by the internal clock.
+    Disabled = 0b000,
+    /// Encoder mode 1, counter counts on TI1FP1 edges depending on TI2FP2 level.
+    EncoderMode1 = 0b001,
+    /// Encoder mode 2, counter counts on TI2FP2 edges depending on TI1FP1 level.
+    EncoderMode2 = 0b010,
+    /// Encoder mode 3, counter counts on both TI1FP1 and TI2FP2 edges.
+    EncoderMode3 = 0b011,
+    /// Reset mode, a rising edge of the trigger input reinitializes the counter.
+    Reset = 0b100,
+    /// Gated mode, the counter counts while the trigger input is high.
+    Gated = 0b101,
+    /// Trigger mode, the counter starts at a rising edge of the trigger input.
+    Trigger = 0b110,
+    /// External clock mode 1, rising edges of the trigger input clock the counter.
+    ExternalClock = 0b111,
+}
+
+/// Master mode, selects the trigger output (TRGO) sent to other timers and the GPADC.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MasterMode {
+    /// The UG bit is used as trigger output.
+    Reset = 0b000,
+    /// The counter enable signal is used as trigger output.
+    Enable = 0b001,
+    /// The update event is used as trigger output.
+    Update = 0b010,
+    /// A pulse is sent when CC1IF is set.
+    ComparePulse = 0b011,
+    /// OC1REF is used as trigger output.
+    Compare1 = 0b100,
+    /// OC2REF is used as trigger output.
+    Compare2 = 0b101,
+    /// OC3REF is used as trigger output.
+    Compare3 = 0b110,
+    /// OC4REF is used as trigger output.
+    Compare4 = 0b111,
+}
+
+/// Trigger source, used by the slave mode controller.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerSource {
+    /// Internal trigger 0.
+    Itr0 = 0b000,
+    /// Internal trigger 1.
+    Itr1 = 0b001,
+    /// Internal trigger 2.
+    Itr2 = 0b010,
+    /// Internal trigger 3.
+    Itr3 = 0b011,
+    /// TI1 edge detector.
+    Ti1FEd = 0b100,
+    /// Filtered timer input 1.
+    Ti1Fp1 = 0b101,
+    /// Filtered timer input 2.
+    Ti2Fp2 = 0b110,
+    /// External trigger input.
+    Etrf = 0b111,
+}
+
+/// Low-level timer driver.
+pub struct Timer<'d, T: GeneralInstance> {
+    tim: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: GeneralInstance> Drop for Timer<'d, T> {
+    fn drop(&mut self) {
+        crate::rcc::disable::<T>();
+    }
+}
+
+impl<'d, T: GeneralInstance> Timer<'d, T> {
+    /// Create a new timer driver.
+    pub fn new(tim: impl Peripheral<P = T> + 'd) -> Self {
+        into_ref!(tim);
+
+        crate::rcc::enable_and_reset::<T>();
+
+        Self { tim }
+    }
+
+    /// Get access to the timer registers.
+    ///
+    /// ATIM shares the GPTIM register layout, advanced features are in [`regs_advanced`](Self::regs_advanced).
+    pub fn regs_gp(&self) -> crate::pac::gptim::Gptim {
+        unsafe { crate::pac::gptim::Gptim::from_ptr(T::regs()) }
+    }
+
+    /// Start the timer.
+    pub fn start(&self) {
+        self.regs_gp().cr1().modify(|r| r.set_cen(true));
+    }
+
+    /// Stop the timer.
+    pub fn stop(&self) {
+        self.regs_gp().cr1().modify(|r| r.set_cen(false));
+    }
+
+    /// Reset the counter value to 0
+    pub fn reset(&self) {
+        self.regs_gp().cnt().write(|r| r.set_cnt(0));
+    }
+
+    /// Generate timer update event from software.
+    ///
+    /// Set URS to avoid generating interrupt or DMA request. This update event is only
+    /// used to load value from pre-load registers.
+    pub fn generate_update_event(&self) {
+        let r = self.regs_gp();
+        r.cr1().modify(|w| w.set_urs(vals::URS::CounterOnly));
+        r.egr().write(|w| w.set_ug(true));
+        r.cr1().modify(|w| w.set_urs(vals::URS::AnyEvent));
+    }
+
+    /// Largest reload value of this timer.
+    pub fn max_arr(&self) -> u32 {
+        match T::BITS {
+            TimerBits::Bits16 => u16::MAX as u32,
+            TimerBits::Bits32 => u32::MAX,
+        }
+    }
+
+    /// Set the frequency of how many times per second the timer counts up to the max value or down to 0.
+    ///
+    /// This means that in the default edge-aligned mode,
+    /// the timer counter will wrap around at the same frequency as is being set.
+    /// In center-aligned mode (which not all timers support), the wrap-around frequency is effectively halved
+    /// because it needs to count up and down.
+    pub fn set_frequency(&self, frequency: Hertz) {
+        self.set_frequency_with_max_arr(frequency, self.max_arr());
+    }
+
+    /// Set the frequency like [`Self::set_frequency`], keeping the reload value
+    /// at most `max_arr`. The prescaler is raised instead, losing resolution.
+    pub fn set_frequency_with_max_arr(&self, frequency: Hertz, max_arr: u32) {
+        let f = frequency.0;
+        assert!(f > 0);
+        let timer_f = T::frequency().unwrap().0;
+
+        let pclk_ticks_per_timer_period = (timer_f / f) as u64;
+        let max_arr = max_arr.min(self.max_arr()) as u64;
+        let psc: u16 = unwrap!(((pclk_ticks_per_timer_period - 1) / (max_arr + 1)).try_into());
+        let arr = pclk_ticks_per_timer_period / (psc as u64 + 1) - 1;
+
+        let r = self.regs_gp();
+        r.psc().write_value(crate::pac::gptim::regs::Psc(psc as _));
+        r.arr().write(|w| w.set_arr(arr as _));
+        self.generate_update_event();
+    }
+
+    /// Get the timer frequency.
+    pub fn get_frequency(&self) -> Hertz {
+        let timer_f = T::frequency().unwrap();
+
+        let r = self.regs_gp();
+        let arr = r.arr().read().arr() as u32;
+        let psc = r.psc().read().0 as u32;
+
+        timer_f / psc.saturating_add(1) / arr.saturating_add(1)
+    }
+
+    /// Get the clock frequency of the counter, after the prescaler.
+    pub fn get_tick_freq(&self) -> Hertz {
+        let timer_f = T::frequency().unwrap();
+        let psc = self.regs_gp().psc().read().0 as u32;
+        timer_f / psc.saturating_add(1)
+    }
+
+    /// Set the prescaler value directly.
+    pub fn set_prescaler(&self, psc: u16) {
+        self.regs_gp().psc().write_value(crate::pac::gptim::regs::Psc(psc as _));
+    }
+
+    /// Set the reload value directly.
+    pub fn set_max_compare_value(&self, arr: u32) {
+        self.regs_gp().arr().write(|w| w.set_arr(arr as _));
+    }
+
+    /// Get max compare value. This depends on the timer frequency and the clock frequency from RCC.
+    pub fn get_max_compare_value(&self) -> u32 {
+        self.regs_gp().arr().read().arr() as u32
+    }
+
+    /// Set counting mode.
+    ///
+    /// The counting mode can only be changed while the timer is stopped.
+    pub fn set_counting_mode(&self, mode: CountingMode) {
+        let r = self.regs_gp();
+        assert!(!r.cr1().read().cen(), "counting mode can only be changed while the timer is stopped");
+
+        let (cms, dir) = mode.bits();
+        r.cr1().modify(|w| {
+            w.set_cms(vals::CMS::from_bits(cms));
+            w.set_dir(vals::DIR::from_bits(dir));
+        });
+    }
+
+    /// Get counting mode.
+    pub fn get_counting_mode(&self) -> CountingMode {
+        let cr1 = self.regs_gp().cr1().read();
+        CountingMode::from_bits(cr1.cms().to_bits(), cr1.dir().to_bits())
+    }
+
+    /// Enable/disable auto-reload preload.
+    pub fn set_autoreload_preload(&self, enable: bool) {
+        self.regs_gp().cr1().modify(|w| w.set_arpe(enable));
+    }
+
+    /// Set output compare mode.
+    pub fn set_output_compare_mode(&self, channel: Channel, mode: OutputCompareMode) {
+        let raw_channel: usize = channel.index();
+        self.regs_gp()
+            .ccmr_output(raw_channel / 2)
+            .modify(|w| w.set_ocm(raw_channel % 2, vals::OCM::from_bits(mode as u8)));
+    }
+
+    /// Set output compare preload.
+    pub fn set_output_compare_preload(&self, channel: Channel, preload: bool) {
+        let channel_index = channel.index();
+        self.regs_gp()
+            .ccmr_output(channel_index / 2)
+            .modify(|w| w.set_ocpe(channel_index % 2, preload));
+    }
+
+    /// Set output polarity.
+    pub fn set_output_polarity(&self, channel: Channel, polarity: OutputPolarity) {
+        self.regs_gp()
+            .ccer()
+            .modify(|w| w.set_ccp(channel.index(), polarity == OutputPolarity::ActiveLow));
+    }
+
+    /// Enable/disable a channel.
+    pub fn enable_channel(&self, channel: Channel, enable: bool) {
+        self.regs_gp().ccer().modify(|w| w.set_cce(channel.index(), enable));
+    }
+
+    /// Get enable/disable state of a channel
+    pub fn get_channel_enable_state(&self, channel: Channel) -> bool {
+        self.regs_gp().ccer().read().cce(channel.index())
+    }
+
+    /// Set the tick frequency of the counter, by only changing the prescaler.
+    pub fn set_tick_freq(&self, freq: Hertz) {
+        let f = freq.0;
+        assert!(f > 0);
+        let timer_f = T::frequency().unwrap().0;
+        let psc: u16 = unwrap!((timer_f / f).saturating_sub(1).try_into());
+
+        self.set_prescaler(psc);
+        self.generate_update_event();
+    }
+
+    /// Set input capture filter.
+    pub fn set_input_capture_filter(&self, channel: Channel, icf: FilterValue) {
+        let raw_channel = channel.index();
+        self.regs_gp()
+            .ccmr_input(raw_channel / 2)
+            .modify(|r| r.set_icf(raw_channel % 2, vals::ICF::from_bits(icf as u8)));
+    }
+
+    /// Clear input interrupt.
+    pub fn clear_input_interrupt(&self, channel: Channel) {
+        self.regs_gp().sr().modify(|r| r.set_ccif(channel.index(), false));
+    }
+
+    /// Enable input interrupt.
+    pub fn enable_input_interrupt(&self, channel: Channel, enable: bool) {
+        self.regs_gp().dier().modify(|r| r.set_ccie(channel.index(), enable));
+    }
+
+    /// Get whether the input interrupt of a channel is enabled.
+    pub fn input_interrupt_enabled(&self, channel: Channel) -> bool {
+        self.regs_gp().dier().read().ccie(channel.index())
+    }
+
+    /// Enable/disable the update interrupt.
+    pub fn enable_update_interrupt(&self, enable: bool) {
+        self.regs_gp().dier().modify(|r| r.set_uie(enable));
+    }
+
+    /// Set input capture prescaler.
+    pub fn set_input_capture_prescaler(&self, channel: Channel, factor: InputCapturePrescaler) {
+        let raw_channel = channel.index();
+        self.regs_gp()
+            .ccmr_input(raw_channel / 2)
+            .modify(|r| r.set_icpsc(raw_channel % 2, factor as u8));
+    }
+
+    /// Set input TI selection.
+    pub fn set_input_ti_selection(&self, channel: Channel, tisel: InputTISelection) {
+        let raw_channel = channel.index();
+        self.regs_gp()
+            .ccmr_input(raw_channel / 2)
+            .modify(|r| r.set_ccs(raw_channel % 2, vals::CCS::from_bits(tisel.into())));
+    }
+
+    /// Set input capture mode.
+    pub fn set_input_capture_mode(&self, channel: Channel, mode: InputCaptureMode) {
+        self.regs_gp().ccer().modify(|r| match mode {
+            InputCaptureMode::Rising => {
+                r.set_ccnp(channel.index(), false);
+                r.set_ccp(channel.index(), false);
+            }
+            InputCaptureMode::Falling => {
+                r.set_ccnp(channel.index(), false);
+                r.set_ccp(channel.index(), true);
+            }
+            InputCaptureMode::BothEdges => {
+                r.set_ccnp(channel.index(), true);
+                r.set_ccp(channel.index(), true);
+            }
+        });
+    }
+
+    /// Get capture value for a channel.
+    pub fn get_capture_value(&self, channel: Channel) -> u32 {
+        self.get_compare_value(channel)
+    }
+
+    /// Set the slave mode.
+    pub fn set_slave_mode(&self, sms: SlaveMode) {
+        self.regs_gp().smcr().modify(|r| r.set_sms(vals::SMS::from_bits(sms as u8)));
+    }
+
+    /// Set the master mode, i.e. the trigger output.
+    pub fn set_master_mode(&self, mms: MasterMode) {
+        self.regs_gp().cr2().modify(|r| r.set_mms(vals::MMS::from_bits(mms as u8)));
+    }
+
+    /// Set the trigger source used by the slave mode controller.
+    pub fn set_trigger_source(&self, ts: TriggerSource) {
+        self.regs_gp().smcr().modify(|r| r.set_ts(vals::TS::from_bits(ts as u8)));
+    }
+
+    /// Set compare value for a channel.
+    pub fn set_compare_value(&self, channel: Channel, value: u32) {
+        self.regs_gp().ccr(channel.index()).write(|w| w.set_ccr(value as _));
+    }
+
+    /// Get compare value for a channel.
+    pub fn get_compare_value(&self, channel: Channel) -> u32 {
+        self.regs_gp().ccr(channel.index()).read().ccr() as u32
+    }
+}
+
+impl<'d, T: AtimInstance> Timer<'d, T> {
+    /// Get access to the advanced timer registers.
+    pub fn regs_advanced(&self) -> crate::pac::atim::Atim {
+        unsafe { crate::pac::atim::Atim::from_ptr(T::regs()) }
+    }
+
+    /// Set clock divider for the dead time.
+    pub fn set_dead_time_clock_division(&self, value: u8) {
+        self.regs_advanced().cr1().modify(|w| w.set_ckd(vals::CKD::from_bits(value)));
+    }
+
+    /// Set dead time, as a raw DTG value.
+    pub fn set_dead_time_value(&self, value: u8) {
+        self.regs_advanced().bdtr().modify(|w| w.set_dtg(value));
+    }
+
+    /// Set state of MOE-bit in BDTR register to en-/disable output
+    pub fn set_moe(&self, enable: bool) {
+        self.regs_advanced().bdtr().modify(|w| w.set_moe(enable));
+    }
+
+    /// Set complementary output polarity.
+    pub fn set_complementary_output_polarity(&self, channel: Channel, polarity: OutputPolarity) {
+        self.regs_advanced()
+            .ccer()
+            .modify(|w| w.set_ccnp(channel.index(), polarity == OutputPolarity::ActiveLow));
+    }
+
+    /// Enable/disable a complementary channel.
+    pub fn enable_complementary_channel(&self, channel: Channel, enable: bool) {
+        self.regs_advanced()
+            .ccer()
+            .modify(|w| w.set_ccne(channel.index(), enable));
+    }
+
+    /// Configure break input `n` (0 for BK, 1 for BK2).
+    pub fn set_break(&self, n: usize, enable: bool, active_high: bool) {
+        self.regs_advanced().bdtr().modify(|w| match n {
+            0 => {
+                w.set_bke(enable);
+                w.set_bkp(active_high);
+            }
+            _ => {
+                w.set_bk2e(enable);
+                w.set_bk2p(active_high);
+            }
+        });
+    }
+
+    /// Re-enable the outputs automatically at the next update event after a break.
+    pub fn set_automatic_output_enable(&self, enable: bool) {
+        self.regs_advanced().bdtr().modify(|w| w.set_aoe(enable));
+    }
+
+    /// Set the off-state selection for run mode (OSSR) and idle mode (OSSI).
+    pub fn set_off_state_selection(&self, run: bool, idle: bool) {
+        self.regs_advanced().bdtr().modify(|w| {
+            w.set_ossr(run);
+            w.set_ossi(idle);
+        });
+    }
+}
+
+/// Compute the DTG value and the clock division from a dead time in timer kernel clock ticks.
+///
+/// Returns `(ckd, dtg)`.
+pub(crate) fn compute_dead_time_value(value: u16) -> (u8, u8) {
+    /*
+        Dead-time = T_clk * T_dts * T_dtg
+
+        T_dts:
+        This bit-field indicates the division ratio between the timer clock (CK_INT) frequency and the
+        dead-time and sampling clock (tDTS)used by the dead-time generators and the digital filters
+        (ETR, TIx),
+        00: tDTS=tCK_INT
+        01: tDTS=2*tCK_INT
+        10: tDTS=4*tCK_INT
+
+        T_dtg:
+        This bit-field defines the duration of the dead-time inserted between the complementary
+        outputs. DT correspond to this duration.
+        DTG[7:5]=0xx => DT=DTG[7:0]x tdtg with tdtg=tDTS.
+        DTG[7:5]=10x => DT=(64+DTG[5:0])xtdtg with Tdtg=2xtDTS.
+        DTG[7:5]=110 => DT=(32+DTG[4:0])xtdtg with Tdtg=8xtDTS.
+        DTG[7:5]=111 => DT=(32+DTG[4:0])xtdtg with Tdtg=16xtDTS.
+        Example if TDTS=125ns (8MHz), dead-time possible values are:
+        0 to 15875 ns by 125 ns steps,
+        16 us to 31750 ns by 250 ns steps,
+        32 us to 63us by 1 us steps,
+        64 us to 126 us by 2 us steps
+    */
+
+    let mut error = u16::MAX;
+    let mut ckd = 0;
+    let mut bits = 0u8;
+
+    for this_ckd in [0u8, 1, 2] {
+        let outdiv = (value as u32 >> this_ckd) as u16;
+
+        // 127
+        // 128
+        // ..
+        // 254
+        // 256
+        // ..
+        // 504
+        // 512
+        // ..
+        // 1008
+
+        let target = outdiv;
+        let (these_bits, result) = if target < 128 {
+            (target as u8, target)
+        } else if target < 256 {
+            ((64 + (target / 2) as u8) | 128, (target - target % 2))
+        } else if target < 512 {
+            ((32 + (target / 8) as u8) | 192, (target - target % 8))
+        } else if target < 1024 {
+            ((32 + (target / 16) as u8) | 224, (target - target % 16))
+        } else {
+            (u8::MAX, 1008)
+        };
+
+        let this_error = value.abs_diff(result << this_ckd);
+        if error > this_error {
+            ckd = this_ckd;
+            bits = these_bits;
+            error = this_error;
+        }
+
+        if error == 0 {
+            break;
+        }
+    }
+
+    (ckd, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dead time in timer kernel clock ticks of a `(ckd, dtg)` pair.
+    fn dead_time(ckd: u8, dtg: u8) -> u32 {
+        let dtg = dtg as u32;
+        let ticks = match dtg >> 5 {
+            0..=3 => dtg,
+            4 | 5 => (64 + (dtg & 0x3F)) * 2,
+            6 => (32 + (dtg & 0x1F)) * 8,
+            _ => (32 + (dtg & 0x1F)) * 16,
+        };
+        ticks << ckd
+    }
+
+    #[test]
+    fn dead_time_exact() {
+        assert_eq!(compute_dead_time_value(0), (0, 0));
+        assert_eq!(compute_dead_time_value(100), (0, 100));
+        assert_eq!(compute_dead_time_value(127), (0, 127));
+        assert_eq!(compute_dead_time_value(200), (0, 164));
+        assert_eq!(compute_dead_time_value(256), (0, 192));
+        assert_eq!(compute_dead_time_value(1008), (0, 255));
+        assert_eq!(compute_dead_time_value(4032), (2, 255));
+    }
+
+    #[test]
+    fn dead_time_at_range_edges() {
+        assert_eq!(dead_time(0, compute_dead_time_value(255).1), 254);
+        assert_eq!(compute_dead_time_value(510), (1, 191));
+        assert_eq!(compute_dead_time_value(u16::MAX), (2, 255));
+    }
+
+    #[test]
+    fn dead_time_closest() {
+        for value in 0..=4032u16 {
+            let (ckd, dtg) = compute_dead_time_value(value);
+            assert!(ckd <= 2);
+            let error = dead_time(ckd, dtg).abs_diff(value as u32);
+            // Truncated to the step of the encoding of the value.
+            let max_error = match value {
+                0..=127 => 0,
+                128..=255 => 1,
+                256..=511 => 7,
+                512..=1023 => 15,
+                1024..=2047 => 31,
+                _ => 63,
+            };
+            assert!(error <= max_error, "{} -> {:?}, error {}", value, (ckd, dtg), error);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Timers, PWM, quadrature decoder.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_hal_internal::Peripheral;
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt;
+use crate::rcc::{RccEnableReset, RccGetFreq};
+
+pub mod basic;
+pub mod complementary_pwm;
+pub mod input_capture;
+pub mod low_level;
+pub mod pwm_input;
+pub mod qei;
+pub mod simple_pwm;
+
+/// Timer channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    /// Channel 1.
+    Ch1,
+    /// Channel 2.
+    Ch2,
+    /// Channel 3.
+    Ch3,
+    /// Channel 4.
+    Ch4,
+    /// Channel 5, only available on ATIM.
+    Ch5,
+    /// Channel 6, only available on ATIM.
+    Ch6,
+}
+
+impl Channel {
+    /// Get the channel index (0..3)
+    pub fn index(&self) -> usize {
+        match self {
+            Channel::Ch1 => 0,
+            Channel::Ch2 => 1,
+            Channel::Ch3 => 2,
+            Channel::Ch4 => 3,
+            Channel::Ch5 => 4,
+            Channel::Ch6 => 5,
+        }
+    }
+}
+
+trait SealedTimerChannel {}
+
+/// Timer channel type, used by the channel pin traits.
+#[allow(private_bounds)]
+pub trait TimerChannel: SealedTimerChannel {
+    /// The runtime channel.
+    const CHANNEL: Channel;
+}
+
+macro_rules! timer_channel_impl {
+    ($name:ident, $channel:ident) => {
+        #[doc = concat!("Channel ", stringify!($channel), " type.")]
+        pub enum $name {}
+        impl SealedTimerChannel for $name {}
+        impl TimerChannel for $name {
+            const CHANNEL: Channel = Channel::$channel;
+        }
+    };
+}
+
+timer_channel_impl!(Ch1, Ch1);
+timer_channel_impl!(Ch2, Ch2);
+timer_channel_impl!(Ch3, Ch3);
+timer_channel_impl!(Ch4, Ch4);
+
+/// Amount of bits of a timer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimerBits {
+    /// 16 bits.
+    Bits16,
+    /// 32 bits.
+    Bits32,
+}
+
+struct State {
+    up_waker: AtomicWaker,
+    cc_waker: [AtomicWaker; 4],
+    /// Number of counter overflows, used to extend the 16-bit capture values.
+    overflows: AtomicU32,
+    /// Last extended capture value of each channel.
+    captures: [AtomicU32; 4],
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            up_waker: AtomicWaker::new(),
+            cc_waker: [const { AtomicWaker::new() }; 4],
+            overflows: AtomicU32::new(0),
+            captures: [const { AtomicU32::new(0) }; 4],
+        }
+    }
+}
+
+/// Timer signal that can be routed to a pad through HPSYS_CFG.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum Signal {
+    Ch(Channel),
+    ChN(Channel),
+    Etr,
+    Bk,
+    Bk2,
+}
+
+trait SealedInstance: RccEnableReset + RccGetFreq + Peripheral<P = Self> {
+    /// Async state for this timer
+    fn state() -> &'static State;
+
+    /// HPSYS_CFG signal for `signal`, `None` if this timer doesn't have it.
+    fn route_signal(signal: Signal) -> Option<crate::gpio::Signal>;
+}
+
+/// timer instance.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + 'static {
+    /// Interrupt for this timer.
+    type Interrupt: interrupt::typelevel::Interrupt;
+
+    /// Amount of bits this timer has.
+    const BITS: TimerBits;
+
+    /// Registers for this timer.
+    ///
+    /// This is a raw pointer to the register block. The actual register block layout varies depending on the timer type.
+    fn regs() -> *mut ();
+}
+
+pin_trait!(ChannelPin, Instance, TimerChannel);
+pin_trait!(ComplementaryChannelPin, Instance, TimerChannel);
+pin_trait!(ExternalTriggerPin, Instance);
+pin_trait!(BreakInputPin, Instance);
+pin_trait!(BreakInput2Pin, Instance);
+
+/// Timer with capture/compare channels (GPTIM and ATIM).
+pub trait GeneralInstance: Instance + 'static {}
+/// Advanced-control timer instance.
+pub trait AtimInstance: GeneralInstance + 'static {}
+/// General-purpose timer instance.
+pub trait GptimInstance: GeneralInstance + 'static {}
+/// Basic timer instance.
+pub trait BimInstance: Instance + 'static {}
+
+/// Capture/compare and update interrupt handler.
+///
+/// Counts counter overflows, latches the extended value of the channels
+/// being waited on and wakes the corresponding tasks.
+pub struct InterruptHandler<T: GeneralInstance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: GeneralInstance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let regs = crate::pac::gptim::Gptim::from_ptr(T::regs());
+        let state = T::state();
+
+        let sr = regs.sr().read();
+        let dier = regs.dier().read();
+
+        // Bits in SR are "write 0 to clear", so write the bitwise NOT.
+        regs.sr().write_value(crate::pac::gptim::regs::Sr(!sr.0));
+
+        // Only the interrupt handler modifies the overflow count, so this can't race.
+        let overflows = state.overflows.load(Ordering::Relaxed);
+
+        for n in 0..4 {
+            if sr.ccif(n) && dier.ccie(n) {
+                let ccr = regs.ccr(n).read().ccr() as u32 & 0xFFFF;
+                // If the counter overflowed together with the capture, a small
+                // captured value was taken after the overflow.
+                let overflows = if sr.uif() && ccr < 0x8000 {
+                    overflows.wrapping_add(1)
+                } else {
+                    overflows
+                };
+                state.captures[n].store((overflows << 16) | ccr, Ordering::Relaxed);
+
+                // The future checks CCIE to know the capture happened.
+                regs.dier().modify(|w| w.set_ccie(n, false));
+                state.cc_waker[n].wake();
+            }
+        }
+
+        if sr.uif() {
+            // In encoder mode the counter also wraps downwards, which is told apart by the
+            // counter being close to the top instead of close to 0.
+            let encoder = matches!(regs.smcr().read().sms().to_bits(), 0b001..=0b011);
+            let overflows = if encoder && regs.cnt().read().cnt() as u32 & 0xFFFF >= 0x8000 {
+                overflows.wrapping_sub(1)
+            } else {
+                overflows.wrapping_add(1)
+            };
+            state.overflows.store(overflows, Ordering::Relaxed);
+            state.up_waker.wake();
+        }
+    }
+}
+
+/// Route `signal` of timer `T` to `pin`.
+pub(crate) fn new_pin<'d, T: Instance>(
+    pin: embassy_hal_internal::PeripheralRef<'d, crate::gpio::AnyPin>,
+    signal: Signal,
+) -> crate::gpio::Flex<'d> {
+    let mut pin = crate::gpio::Flex::new(pin);
+    pin.set_pull(crate::gpio::Pull::None);
+    unwrap!(pin.route(unwrap!(T::route_signal(signal))));
+    pin
+}
+
+// TODO: move to _generated.rs
+use crate::peripherals;
+
+impl SealedInstance for peripherals::ATIM1 {
+    fn state() -> &'static State {
+        static STATE: State = State::new();
+        &STATE
+    }
+
+    fn route_signal(signal: Signal) -> Option<crate::gpio::Signal> {
+        use crate::gpio::Signal as S;
+        Some(match signal {
+            Signal::Ch(Channel::Ch1) => S::Atim1Ch1,
+            Signal::Ch(Channel::Ch2) => S::Atim1Ch2,
+            Signal::Ch(Channel::Ch3) => S::Atim1Ch3,
+            Signal::Ch(Channel::Ch4) => S::Atim1Ch4,
+            Signal::ChN(Channel::Ch1) => S::Atim1Ch1n,
+            Signal::ChN(Channel::Ch2) => S::Atim1Ch2n,
+            Signal::ChN(Channel::Ch3) => S::Atim1Ch3n,
+            Signal::Etr => S::Atim1Etr,
+            Signal::Bk => S::Atim1Bk,
+            Signal::Bk2 => S::Atim1Bk2,
+            _ => return None,
+        })
+    }
+}
+impl Instance for peripherals::ATIM1 {
+    type Interrupt = interrupt::typelevel::ATIM1;
+    const BITS: TimerBits = TimerBits::Bits32;
+    fn regs() -> *mut () {
+        crate::pac::ATIM1.as_ptr()
+    }
+}
+impl GeneralInstance for peripherals::ATIM1 {}
+impl AtimInstance for peripherals::ATIM1 {}
+
+macro_rules! impl_gptim_route {
+    ($inst:ident, [$ch1:ident, $ch2:ident, $ch3:ident, $ch4:ident], $etr:ident) => {
+        impl SealedInstance for peripherals::$inst {
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
+
+            fn route_signal(signal: Signal) -> Option<crate::gpio::Signal> {
+                use crate::gpio::Signal as S;
+                Some(match signal {
+                    Signal::Ch(Channel::Ch1) => S::$ch1,
+                    Signal::Ch(Channel::Ch2) => S::$ch2,
+                    Signal::Ch(Channel::Ch3) => S::$ch3,
+                    Signal::Ch(Channel::Ch4) => S::$ch4,
+                    Signal::Etr => S::$etr,
+                    _ => return None,
+                })
+            }
+        }
+    };
+}
+
+impl_gptim_route!(GPTIM1, [Gptim1Ch1, Gptim1Ch2, Gptim1Ch3, Gptim1Ch4], Gptim1Etr);
+impl Instance for peripherals::GPTIM1 {
+    type Interrupt = interrupt::typelevel::GPTIM1;
+    const BITS: TimerBits = TimerBits::Bits32;
+    fn regs() -> *mut () { 
+        crate::pac::GPTIM1.as_ptr()
+    }
+}
+impl GeneralInstance for peripherals::GPTIM1 {}
+impl GptimInstance for peripherals::GPTIM1 {}
+
+impl_gptim_route!(GPTIM2, [Gptim2Ch1, Gptim2Ch2, Gptim2Ch3, Gptim2Ch4], Gptim2Etr);
+impl Instance for peripherals::GPTIM2 {
+    type Interrupt = interrupt::typelevel::GPTIM2;
+    const BITS: TimerBits = TimerBits::Bits32;
+    fn regs() -> *mut () { 
+        crate::pac::GPTIM2.as_ptr()
+    }
+}
+impl GeneralInstance for peripherals::GPTIM2 {}
+impl GptimInstance for peripherals::GPTIM2 {}
+
+impl SealedInstance for peripherals::BTIM1 {
+    fn state() -> &'static State {
+        static STATE: State = State::new();
+        &STATE
+    }
+
+    fn route_signal(_signal: Signal) -> Option<crate::gpio::Signal> {
+        None
+    }
+}
+impl Instance for peripherals::BTIM1 {
+    type Interrupt = interrupt::typelevel::BTIM1;
+    const BITS: TimerBits = TimerBits::Bits32;
+    fn regs() -> *mut () { 
+        crate::pac::BTIM1.as_ptr()
+    }
+}
+impl BimInstance for peripherals::BTIM1 {}
+
+impl SealedInstance for peripherals::BTIM2 {
+    fn state() -> &'static State {
+        static STATE: State = State::new();
+        &STATE
+    }
+
+    fn route_signal(_signal: Signal) -> Option<crate::gpio::Signal> {
+        None
+    }
+}
+impl Instance for peripherals::BTIM2 {
+    type Interrupt = interrupt::typelevel::BTIM2;
+    const BITS: TimerBits = TimerBits::Bits32;
+    fn regs() -> *mut () { 
+        crate::pac::BTIM2.as_ptr()
+    }
+}
+impl BimInstance for peripherals::BTIM2 {}
@@ -0,0 +1,151 @@
+//! PWM Input driver.
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+use super::input_capture::InputCaptureFuture;
+use super::low_level::{CountingMode, InputCaptureMode, InputTISelection, SlaveMode, Timer, TriggerSource};
+use super::{new_pin, Ch1, Ch2, Channel, ChannelPin, GeneralInstance, InterruptHandler, Signal};
+use crate::gpio::{Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt};
+use crate::time::Hertz;
+
+/// PWM Input driver.
+///
+/// Measures the period and the high time of a PWM signal using two channels:
+/// the first one captures the rising edges and resets the counter, the second
+/// one captures the falling edges of the same input. Both values are in ticks
+/// of the frequency given to the constructor, and limited to 16 bits.
+pub struct PwmInput<'d, T: GeneralInstance> {
+    channel: Channel,
+    inner: Timer<'d, T>,
+    _pin: Flex<'d>,
+}
+
+impl<'d, T: GeneralInstance> PwmInput<'d, T> {
+    /// Create a new PWM input driver measuring the input of channel 1.
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl ChannelPin<T, Ch1>> + 'd,
+        pull: Pull,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        let mut pin = new_pin::<T>(pin.map_into(), Signal::Ch(Channel::Ch1));
+        pin.set_pull(pull);
+
+        Self::new_inner(tim, pin, freq, Channel::Ch1, Channel::Ch2)
+    }
+
+    /// Create a new PWM input driver measuring the input of channel 2.
+    pub fn new_alt(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl ChannelPin<T, Ch2>> + 'd,
+        pull: Pull,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        freq: Hertz,
+    ) -> Self {
+        into_ref!(pin);
+        let mut pin = new_pin::<T>(pin.map_into(), Signal::Ch(Channel::Ch2));
+        pin.set_pull(pull);
+
+        Self::new_inner(tim, pin, freq, Channel::Ch2, Channel::Ch1)
+    }
+
+    fn new_inner(
+        tim: impl Peripheral<P = T> + 'd,
+        pin: Flex<'d>,
+        freq: Hertz,
+        ch1: Channel,
+        ch2: Channel,
+    ) -> Self {
+        let inner = Timer::new(tim);
+
+        inner.set_counting_mode(CountingMode::EdgeAlignedUp);
+        inner.set_max_compare_value(0xFFFF);
+        inner.set_tick_freq(freq);
+
+        // Configuration steps from the reference manual, "PWM input mode".
+        inner.set_input_ti_selection(ch1, InputTISelection::Normal);
+        inner.set_input_capture_mode(ch1, InputCaptureMode::Rising);
+
+        inner.set_input_ti_selection(ch2, InputTISelection::Alternate);
+        inner.set_input_capture_mode(ch2, InputCaptureMode::Falling);
+
+        inner.set_trigger_source(match ch1 {
+            Channel::Ch1 => TriggerSource::Ti1Fp1,
+            _ => TriggerSource::Ti2Fp2,
+        });
+        inner.set_slave_mode(SlaveMode::Reset);
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            channel: ch1,
+            inner,
+            _pin: pin,
+        }
+    }
+
+    /// Enable the capture and start the timer.
+    pub fn enable(&mut self) {
+        self.inner.enable_channel(Channel::Ch1, true);
+        self.inner.enable_channel(Channel::Ch2, true);
+        self.inner.start();
+    }
+
+    /// Disable the capture and stop the timer.
+    pub fn disable(&mut self) {
+        self.inner.stop();
+        self.inner.enable_channel(Channel::Ch1, false);
+        self.inner.enable_channel(Channel::Ch2, false);
+    }
+
+    /// Check whether the capture is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.get_channel_enable_state(Channel::Ch1)
+    }
+
+    /// Get the tick frequency of the measured values.
+    pub fn tick_freq(&self) -> Hertz {
+        self.inner.get_tick_freq()
+    }
+
+    /// Get the period tick count of the last measured cycle.
+    pub fn get_period_ticks(&self) -> u32 {
+        self.inner.get_capture_value(self.channel) & 0xFFFF
+    }
+
+    /// Get the pulse width tick count of the last measured cycle.
+    pub fn get_width_ticks(&self) -> u32 {
+        self.inner.get_capture_value(match self.channel {
+            Channel::Ch1 => Channel::Ch2,
+            _ => Channel::Ch1,
+        }) & 0xFFFF
+    }
+
+    /// Get the duty cycle of the last measured cycle, in percent.
+    pub fn get_duty_cycle(&self) -> f32 {
+        let period = self.get_period_ticks();
+        if period == 0 {
+            return 0.;
+        }
+        100. * (self.get_width_ticks() as f32) / (period as f32)
+    }
+
+    /// Asynchronously wait for the end of the next cycle, and return its `(period, width)` in ticks.
+    pub async fn wait_for_cycle(&mut self) -> (u32, u32) {
+        self.inner.clear_input_interrupt(self.channel);
+        self.inner.enable_input_interrupt(self.channel, true);
+        InputCaptureFuture::<T> {
+            channel: self.channel,
+            phantom: PhantomData,
+        }
+        .await;
+
+        (self.get_period_ticks(), self.get_width_ticks())
+    }
+}
@@ -0,0 +1,227 @@
+//! Quadrature decoder using a timer.
+
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+use super::input_capture::InputCaptureFuture;
+use super::low_level::{
+    FilterValue, InputCaptureMode, InputTISelection, OutputCompareMode, SlaveMode, Timer,
+};
+use super::{new_pin, Ch1, Ch2, Ch3, Channel, ChannelPin, GeneralInstance, InterruptHandler, Signal, TimerChannel};
+use crate::gpio::{Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt};
+
+/// Counting direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Counting up.
+    Upcounting,
+    /// Counting down.
+    Downcounting,
+}
+
+/// Encoder counting mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QeiMode {
+    /// Count both edges of channel 1, two counts per encoder cycle.
+    X2,
+    /// Count both edges of both channels, four counts per encoder cycle.
+    #[default]
+    X4,
+}
+
+/// Quadrature decoder configuration.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Encoder counting mode.
+    pub mode: QeiMode,
+    /// Input filter applied to both channels and the index.
+    pub filter: FilterValue,
+    /// Invert the counting direction.
+    pub invert: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: QeiMode::X4,
+            filter: FilterValue::NoFilter,
+            invert: false,
+        }
+    }
+}
+
+/// Wrapper for using a pin with QEI.
+pub struct QeiPin<'d, T, C> {
+    _pin: Flex<'d>,
+    phantom: PhantomData<(T, C)>,
+}
+
+impl<'d, T: GeneralInstance, C: TimerChannel> QeiPin<'d, T, C> {
+    /// Create a new QEI pin instance.
+    pub fn new(pin: impl Peripheral<P = impl ChannelPin<T, C>> + 'd, pull: Pull) -> Self {
+        into_ref!(pin);
+        let mut pin = new_pin::<T>(pin.map_into(), Signal::Ch(C::CHANNEL));
+        pin.set_pull(pull);
+        Self {
+            _pin: pin,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Channel capturing the index pulse.
+const INDEX_CHANNEL: Channel = Channel::Ch3;
+/// Channel used for count compare notifications.
+const COMPARE_CHANNEL: Channel = Channel::Ch4;
+
+/// Quadrature decoder driver.
+///
+/// Works on the general-purpose and advanced timers. The 16-bit hardware
+/// counter is extended to a signed 32-bit position in the interrupt handler,
+/// which wraps around at the `i32` bounds.
+pub struct QeiEncoder<'d, T: GeneralInstance> {
+    inner: Timer<'d, T>,
+}
+
+impl<'d, T: GeneralInstance> QeiEncoder<'d, T> {
+    /// Create a new quadrature decoder driver.
+    ///
+    /// The index pulse, when given, can be waited for with [`wait_for_index`](Self::wait_for_index).
+    pub fn new(
+        tim: impl Peripheral<P = T> + 'd,
+        _ch1: QeiPin<'d, T, Ch1>,
+        _ch2: QeiPin<'d, T, Ch2>,
+        _index: Option<QeiPin<'d, T, Ch3>>,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        let inner = Timer::new(tim);
+
+        for channel in [Channel::Ch1, Channel::Ch2, INDEX_CHANNEL] {
+            inner.set_input_ti_selection(channel, InputTISelection::Normal);
+            inner.set_input_capture_filter(channel, config.filter);
+        }
+        inner.set_input_capture_mode(Channel::Ch1, InputCaptureMode::Rising);
+        inner.set_input_capture_mode(
+            Channel::Ch2,
+            if config.invert {
+                InputCaptureMode::Falling
+            } else {
+                InputCaptureMode::Rising
+            },
+        );
+        inner.set_input_capture_mode(INDEX_CHANNEL, InputCaptureMode::Rising);
+        inner.enable_channel(INDEX_CHANNEL, true);
+
+        inner.set_output_compare_mode(COMPARE_CHANNEL, OutputCompareMode::Frozen);
+
+        inner.set_slave_mode(match config.mode {
+            QeiMode::X2 => SlaveMode::EncoderMode1,
+            QeiMode::X4 => SlaveMode::EncoderMode3,
+        });
+
+        inner.set_max_compare_value(0xFFFF);
+        inner.reset();
+
+        T::state().overflows.store(0, Ordering::Relaxed);
+        inner.regs_gp().sr().write_value(crate::pac::gptim::regs::Sr(0));
+        inner.enable_update_interrupt(true);
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        inner.start();
+
+        Self { inner }
+    }
+
+    /// Get the counting direction of the last count.
+    pub fn read_direction(&self) -> Direction {
+        match self.inner.regs_gp().cr1().read().dir().to_bits() {
+            0 => Direction::Upcounting,
+            _ => Direction::Downcounting,
+        }
+    }
+
+    /// Get the raw 16-bit hardware counter value.
+    pub fn count(&self) -> u16 {
+        self.inner.regs_gp().cnt().read().cnt() as u16
+    }
+
+    /// Get the signed position, in counts.
+    pub fn position(&self) -> i32 {
+        let state = T::state();
+        critical_section::with(|_| {
+            let overflows = state.overflows.load(Ordering::Relaxed);
+            let cnt = self.count() as u32;
+            // A wrap not handled yet by the interrupt handler.
+            let overflows = match (self.inner.regs_gp().sr().read().uif(), cnt < 0x8000) {
+                (false, _) => overflows,
+                (true, true) => overflows.wrapping_add(1),
+                (true, false) => overflows.wrapping_sub(1),
+            };
+            ((overflows << 16) | cnt) as i32
+        })
+    }
+
+    /// Set the position.
+    pub fn set_position(&mut self, position: i32) {
+        let state = T::state();
+        critical_section::with(|_| {
+            let r = self.inner.regs_gp();
+            r.cnt().write(|w| w.set_cnt(position as u32 & 0xFFFF));
+            r.sr().modify(|w| w.set_uif(false));
+            state.overflows.store((position as u32) >> 16, Ordering::Relaxed);
+        });
+    }
+
+    /// Asynchronously wait for the index pulse, and return the position at the pulse.
+    pub async fn wait_for_index(&mut self) -> i32 {
+        self.inner.clear_input_interrupt(INDEX_CHANNEL);
+        self.inner.enable_input_interrupt(INDEX_CHANNEL, true);
+        InputCaptureFuture::<T> {
+            channel: INDEX_CHANNEL,
+            phantom: PhantomData,
+        }
+        .await;
+
+        // The extension done by the interrupt handler assumes an upcounting timer,
+        // recompute it from the current position.
+        self.extend(self.inner.get_capture_value(INDEX_CHANNEL) as u16)
+    }
+
+    /// Asynchronously wait until the position reaches `position`, counting in either direction.
+    pub async fn wait_for_position(&mut self, position: i32) {
+        loop {
+            self.inner.set_compare_value(COMPARE_CHANNEL, position as u32 & 0xFFFF);
+            if self.position() == position {
+                return;
+            }
+
+            self.inner.clear_input_interrupt(COMPARE_CHANNEL);
+            self.inner.enable_input_interrupt(COMPARE_CHANNEL, true);
+            InputCaptureFuture::<T> {
+                channel: COMPARE_CHANNEL,
+                phantom: PhantomData,
+            }
+            .await;
+
+            // The counter matched the lower 16 bits, check it was not a different turn.
+            if self.extend(position as u16) == position {
+                return;
+            }
+        }
+    }
+
+    /// Extend a recent 16-bit counter value to a position, choosing the value closest to the current position.
+    fn extend(&self, count: u16) -> i32 {
+        let now = self.position();
+        now.wrapping_add(count.wrapping_sub(now as u16) as i16 as i32)
+    }
+}
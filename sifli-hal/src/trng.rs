@@ -0,0 +1,289 @@
+//! True Random Number Generator (TRNG)
+//!
+//! The TRNG seeds an internal PRNG from a noise source, then produces random
+//! numbers 256 bits at a time. The PRNG is seeded from the noise source when
+//! the blocking driver is created, before the first request of the async
+//! driver, and on [`Rng::blocking_reseed`] / [`Rng::reseed`].
+//!
+//! Two health checks are reported as [`Error`]: the PRNG lockup detected by
+//! the hardware, and a repeated 256-bit block detected by the driver.
+//!
+//! With the `rand-core` feature, [`Rng`] implements `rand_core::RngCore` and
+//! `rand_core::CryptoRng`.
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::mode::{Async, Blocking, Mode};
+use crate::rcc::SealedRccEnableReset;
+use crate::{interrupt, pac, peripherals};
+
+/// Number of 32-bit words produced at a time.
+const BLOCK_WORDS: usize = 8;
+
+/// TRNG error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The PRNG locked up, the output is not random. Reseed to recover.
+    Lockup,
+    /// Two consecutive blocks were identical, the output is not random.
+    RepeatedOutput,
+}
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// TRNG interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::TRNG> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = pac::TRNG;
+        let irq = r.irq().read();
+        if irq.seed_gen_done_raw() || irq.rand_num_avail_raw() || irq.prng_lockup_raw() {
+            // Masked until the next request, the flags are handled by the task.
+            r.irq().modify(|w| {
+                w.set_seed_gen_done_msk(true);
+                w.set_rand_num_avail_msk(true);
+                w.set_prng_lockup_msk(true);
+            });
+            WAKER.wake();
+        }
+    }
+}
+
+/// TRNG driver.
+pub struct Rng<'d, M: Mode> {
+    _peri: PeripheralRef<'d, peripherals::TRNG>,
+    /// Last block, for the repetition check.
+    last: [u32; BLOCK_WORDS],
+    _phantom: PhantomData<M>,
+}
+
+impl<'d> Rng<'d, Blocking> {
+    /// Create a new blocking TRNG driver. This seeds the PRNG.
+    pub fn new_blocking(peri: impl Peripheral<P = peripherals::TRNG> + 'd) -> Self {
+        let mut this = Self::new_inner(peri);
+        this.blocking_reseed();
+        this
+    }
+}
+
+impl<'d> Rng<'d, Async> {
+    /// Create a new async TRNG driver.
+    ///
+    /// The PRNG is not seeded yet, the first [`async_fill_bytes`](Self::async_fill_bytes)
+    /// waits for the seed.
+    pub fn new(
+        peri: impl Peripheral<P = peripherals::TRNG> + 'd,
+        _irq: impl Binding<interrupt::typelevel::TRNG, InterruptHandler> + 'd,
+    ) -> Self {
+        let this = Self::new_inner(peri);
+
+        interrupt::typelevel::TRNG::unpend();
+        unsafe { interrupt::typelevel::TRNG::enable() };
+
+        this
+    }
+
+    /// Reseed the PRNG from the noise source, waiting for the seed-ready interrupt.
+    pub async fn reseed(&mut self) {
+        let r = pac::TRNG;
+        r.irq().write(|w| w.set_seed_gen_done_clr(true));
+        r.irq().modify(|w| w.set_seed_gen_done_msk(false));
+        r.ctrl().modify(|w| w.set_gen_seed_start(true));
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            // SEED_VALID stays set from the previous seed, wait for this one.
+            if r.irq().read().seed_gen_done_raw() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        r.irq().write(|w| w.set_seed_gen_done_clr(true));
+    }
+
+    /// Fill `dest` with random bytes.
+    pub async fn async_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        if !pac::TRNG.stat().read().seed_valid() {
+            self.reseed().await;
+        }
+
+        let r = pac::TRNG;
+        for chunk in dest.chunks_mut(BLOCK_WORDS * 4) {
+            r.irq().write(|w| {
+                w.set_rand_num_avail_clr(true);
+                w.set_prng_lockup_clr(true);
+            });
+            r.irq().modify(|w| {
+                w.set_rand_num_avail_msk(false);
+                w.set_prng_lockup_msk(false);
+            });
+            r.ctrl().modify(|w| w.set_gen_rand_num_start(true));
+
+            poll_fn(|cx| {
+                WAKER.register(cx.waker());
+                let irq = r.irq().read();
+                if irq.prng_lockup_raw() {
+                    Poll::Ready(Err(Error::Lockup))
+                } else if r.stat().read().rand_num_valid() {
+                    Poll::Ready(Ok(()))
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await?;
+
+            self.read_block(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'d, M: Mode> Rng<'d, M> {
+    fn new_inner(peri: impl Peripheral<P = peripherals::TRNG> + 'd) -> Self {
+        into_ref!(peri);
+        crate::rcc::enable_and_reset::<peripherals::TRNG>();
+
+        // Masked until a request is awaited
+        pac::TRNG.irq().write(|w| {
+            w.set_seed_gen_done_msk(true);
+            w.set_rand_num_avail_msk(true);
+            w.set_prng_lockup_msk(true);
+            w.set_seed_gen_done_clr(true);
+            w.set_rand_num_avail_clr(true);
+            w.set_prng_lockup_clr(true);
+        });
+
+        Self {
+            _peri: peri,
+            last: [0; BLOCK_WORDS],
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Reseed the PRNG from the noise source, blocking.
+    pub fn blocking_reseed(&mut self) {
+        let r = pac::TRNG;
+        r.irq().write(|w| w.set_seed_gen_done_clr(true));
+        r.ctrl().modify(|w| w.set_gen_seed_start(true));
+        // SEED_VALID stays set from the previous seed, wait for this one.
+        while !r.irq().read().seed_gen_done_raw() {}
+        r.irq().write(|w| w.set_seed_gen_done_clr(true));
+    }
+
+    /// Fill `dest` with random bytes, blocking.
+    pub fn blocking_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        let r = pac::TRNG;
+        if !r.stat().read().seed_valid() {
+            self.blocking_reseed();
+        }
+
+        for chunk in dest.chunks_mut(BLOCK_WORDS * 4) {
+            r.irq().write(|w| {
+                w.set_rand_num_avail_clr(true);
+                w.set_prng_lockup_clr(true);
+            });
+            r.ctrl().modify(|w| w.set_gen_rand_num_start(true));
+            loop {
+                if r.irq().read().prng_lockup_raw() {
+                    return Err(Error::Lockup);
+                }
+                if r.stat().read().rand_num_valid() {
+                    break;
+                }
+            }
+
+            self.read_block(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Read the available block into `chunk`, checking it against the previous one.
+    fn read_block(&mut self, chunk: &mut [u8]) -> Result<(), Error> {
+        let r = pac::TRNG;
+        let mut block = [0u32; BLOCK_WORDS];
+        for (n, word) in block.iter_mut().enumerate() {
+            *word = r.rand_num(n).read().val();
+        }
+        r.irq().write(|w| w.set_rand_num_avail_clr(true));
+
+        if block == self.last {
+            return Err(Error::RepeatedOutput);
+        }
+        self.last = block;
+
+        for (dst, word) in chunk.chunks_mut(4).zip(block) {
+            dst.copy_from_slice(&word.to_le_bytes()[..dst.len()]);
+        }
+        Ok(())
+    }
+
+    /// Get a random `u32`, blocking.
+    pub fn blocking_next_u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0; 4];
+        self.blocking_fill_bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Get a random `u64`, blocking.
+    pub fn blocking_next_u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        self.blocking_fill_bytes(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+impl<'d, M: Mode> Drop for Rng<'d, M> {
+    fn drop(&mut self) {
+        interrupt::typelevel::TRNG::disable();
+        peripherals::TRNG::rcc_disable();
+    }
+}
+
+#[cfg(feature = "rand-core")]
+mod rand {
+    use core::num::NonZeroU32;
+
+    use super::*;
+
+    impl From<Error> for rand_core::Error {
+        fn from(e: Error) -> Self {
+            let code = match e {
+                Error::Lockup => 0,
+                Error::RepeatedOutput => 1,
+            };
+            // Both are constant offsets from a nonzero value.
+            rand_core::Error::from(NonZeroU32::new(rand_core::Error::CUSTOM_START + code).unwrap())
+        }
+    }
+
+    impl<'d, M: Mode> rand_core::RngCore for Rng<'d, M> {
+        fn next_u32(&mut self) -> u32 {
+            unwrap!(self.blocking_next_u32(), "TRNG health check failed")
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            unwrap!(self.blocking_next_u64(), "TRNG health check failed")
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            unwrap!(self.blocking_fill_bytes(dest), "TRNG health check failed")
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            Ok(self.blocking_fill_bytes(dest)?)
+        }
+    }
+
+    impl<'d, M: Mode> rand_core::CryptoRng for Rng<'d, M> {}
+}
@@ -0,0 +1,162 @@
+//! On-die temperature sensor (TSEN)
+//!
+//! The sensor is powered only during a conversion. Results are converted with
+//! the nominal transfer function of the sensor, corrected by the factory
+//! calibration from the eFuses when the chip has one.
+//!
+//! To throttle on temperature, feed the result to
+//! [`pmu::dvfs::set_mode_ceiling`](crate::pmu::dvfs::set_mode_ceiling).
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::efuse::TsenCalibration;
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::rcc::SealedRccEnableReset;
+use crate::{interrupt, pac, peripherals};
+
+/// Power-up time of the sensor, in microseconds.
+const POWER_UP_US: u32 = 100;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// TSEN interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::TSEN> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let r = pac::TSEN;
+        if r.tsen_irq().read().tsen_irsr() {
+            r.tsen_irq().modify(|w| w.set_tsen_imr(true));
+            WAKER.wake();
+        }
+    }
+}
+
+/// Temperature sensor driver.
+pub struct Tsen<'d> {
+    _peri: PeripheralRef<'d, peripherals::TSEN>,
+    /// Calibration offset, in 0.1 °C.
+    offset: i16,
+}
+
+impl<'d> Tsen<'d> {
+    /// Create a new temperature sensor driver.
+    pub fn new(
+        peri: impl Peripheral<P = peripherals::TSEN> + 'd,
+        _irq: impl Binding<interrupt::typelevel::TSEN, InterruptHandler> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+        crate::rcc::enable_and_reset::<peripherals::TSEN>();
+
+        let offset = match TsenCalibration::read() {
+            Ok(Some(calibration)) => calibration.offset_decicelsius,
+            _ => 0,
+        };
+
+        // Masked until a conversion is awaited
+        pac::TSEN.tsen_irq().write(|w| {
+            w.set_tsen_imr(true);
+            w.set_tsen_icr(true);
+        });
+        interrupt::typelevel::TSEN::unpend();
+        unsafe { interrupt::typelevel::TSEN::enable() };
+
+        Self { _peri: peri, offset }
+    }
+
+    /// Read the die temperature, in °C.
+    pub async fn read_celsius(&mut self) -> f32 {
+        let r = pac::TSEN;
+        self.start();
+        // Unmask
+        r.tsen_irq().modify(|w| w.set_tsen_imr(false));
+
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if r.tsen_irq().read().tsen_irsr() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        self.finish()
+    }
+
+    /// Read the die temperature, in °C, blocking.
+    pub fn blocking_read_celsius(&mut self) -> f32 {
+        self.start();
+        while !pac::TSEN.tsen_irq().read().tsen_irsr() {}
+        self.finish()
+    }
+
+    /// Power the sensor up and start a conversion.
+    fn start(&self) {
+        let r = pac::TSEN;
+        r.tsen_irq().write(|w| w.set_tsen_icr(true));
+        r.tsen_ctrl_reg().modify(|w| {
+            w.set_anau_tsen_pu(true);
+            w.set_anau_tsen_en(true);
+            w.set_anau_tsen_rstb(false);
+        });
+        crate::blocking_delay_us(POWER_UP_US);
+        r.tsen_ctrl_reg().modify(|w| {
+            w.set_anau_tsen_rstb(true);
+            w.set_anau_tsen_run(true);
+        });
+    }
+
+    /// Read the result and power the sensor down.
+    fn finish(&self) -> f32 {
+        let r = pac::TSEN;
+        let raw = r.tsen_rdata().read().tsen_rdata();
+        r.tsen_irq().write(|w| w.set_tsen_icr(true));
+        r.tsen_ctrl_reg().modify(|w| {
+            w.set_anau_tsen_run(false);
+            w.set_anau_tsen_en(false);
+            w.set_anau_tsen_pu(false);
+        });
+
+        to_celsius(raw) + self.offset as f32 / 10.0
+    }
+}
+
+impl<'d> Drop for Tsen<'d> {
+    fn drop(&mut self) {
+        interrupt::typelevel::TSEN::disable();
+        peripherals::TSEN::rcc_disable();
+    }
+}
+
+/// Nominal transfer function of the sensor, 10-bit result.
+fn to_celsius(raw: u32) -> f32 {
+    (raw & 0x3FF) as f32 * 749.2916 / 1023.0 - 277.3457
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn to_celsius_range() {
+        assert_close(to_celsius(0), -277.3457);
+        assert_close(to_celsius(1023), 471.9459);
+        assert_close(to_celsius(413), 25.15);
+    }
+
+    #[test]
+    fn to_celsius_ignores_upper_bits() {
+        assert_eq!(to_celsius(0x400 | 413), to_celsius(413));
+        assert_eq!(to_celsius(0xFFFF_FC00), to_celsius(0));
+    }
+}
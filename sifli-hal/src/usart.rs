@@ -0,0 +1,1016 @@
+//! Universal Synchronous/Asynchronous Receiver Transmitter (USART) driver.
+#![macro_use]
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+use embassy_sync::waitqueue::AtomicWaker;
+use embedded_hal_nb::nb;
+
+use crate::gpio::{AnyPin, Flex, Pull};
+use crate::interrupt::typelevel::{Binding, Interrupt as _};
+use crate::mode::{Async, Blocking, Mode};
+use crate::pac::usart::Usart as Regs;
+use crate::rcc::RccGetFreq;
+use crate::time::Hertz;
+use crate::{interrupt, pac};
+
+/// Number of data bits
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataBits {
+    /// 6 Data Bits
+    DataBits6,
+    /// 7 Data Bits
+    DataBits7,
+    /// 8 Data Bits
+    DataBits8,
+}
+
+/// Parity
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Parity {
+    /// No parity
+    ParityNone,
+    /// Even Parity
+    ParityEven,
+    /// Odd Parity
+    ParityOdd,
+}
+
+/// Number of stop bits
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StopBits {
+    #[doc = "1 stop bit"]
+    STOP1,
+    #[doc = "1.5 stop bits"]
+    STOP1P5,
+    #[doc = "2 stop bits"]
+    STOP2,
+}
+
+/// Hardware flow control
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlowControl {
+    /// No flow control
+    None,
+    /// RTS and CTS, on the pins given to a `*_with_rtscts` constructor
+    RtsCts,
+}
+
+/// Config Error
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Baudrate too low
+    BaudrateTooLow,
+    /// Baudrate too high
+    BaudrateTooHigh,
+    /// Peripheral clock is disabled
+    ClockDisabled,
+}
+
+/// USART config.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Baud rate
+    pub baudrate: u32,
+    /// Number of data bits
+    pub data_bits: DataBits,
+    /// Number of stop bits
+    pub stop_bits: StopBits,
+    /// Parity type
+    pub parity: Parity,
+    /// Hardware flow control, forced to [`FlowControl::RtsCts`] by the
+    /// `*_with_rtscts` constructors and ignored without RTS/CTS pins
+    pub flow_control: FlowControl,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            baudrate: 115200,
+            data_bits: DataBits::DataBits8,
+            stop_bits: StopBits::STOP1,
+            parity: Parity::ParityNone,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+/// Serial error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Framing error
+    Framing,
+    /// Noise error
+    Noise,
+    /// RX buffer overrun
+    Overrun,
+    /// Parity check error
+    Parity,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let message = match self {
+            Self::Framing => "Framing Error",
+            Self::Noise => "Noise Error",
+            Self::Overrun => "RX Buffer Overrun",
+            Self::Parity => "Parity Check Error",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        let cr1 = r.cr1().read();
+        let isr = r.isr().read();
+
+        let rx_event = isr.rxne() || isr.idle() || isr.pe() || isr.fe() || isr.nf() || isr.ore();
+        if rx_event && (cr1.rxneie() || cr1.idleie() || cr1.peie()) {
+            // Disable the rx interrupts, the future re-enables them on the next poll.
+            r.cr1().modify(|w| {
+                w.set_rxneie(false);
+                w.set_idleie(false);
+                w.set_peie(false);
+            });
+            r.cr3().modify(|w| w.set_eie(false));
+            s.rx_waker.wake();
+        }
+
+        if (isr.txe() && cr1.txeie()) || (isr.tc() && cr1.tcie()) {
+            r.cr1().modify(|w| {
+                w.set_txeie(false);
+                w.set_tcie(false);
+            });
+            s.tx_waker.wake();
+        }
+    }
+}
+
+/// Bidirectional UART Driver, which acts as a combination of [`UartTx`] and [`UartRx`].
+pub struct Uart<'d, T: Instance, M: Mode> {
+    tx: UartTx<'d, T, M>,
+    rx: UartRx<'d, T, M>,
+}
+
+/// Tx-only UART Driver.
+///
+/// Can be obtained from [`Uart::split`], or can be constructed independently,
+/// if you do not need the receiving half of the driver.
+pub struct UartTx<'d, T: Instance, M: Mode> {
+    _tx: Option<Flex<'d>>,
+    cts: Option<Flex<'d>>,
+    _phantom: PhantomData<(T, M)>,
+}
+
+/// Rx-only UART Driver.
+///
+/// Can be obtained from [`Uart::split`], or can be constructed independently,
+/// if you do not need the transmitting half of the driver.
+pub struct UartRx<'d, T: Instance, M: Mode> {
+    _rx: Option<Flex<'d>>,
+    rts: Option<Flex<'d>>,
+    _phantom: PhantomData<(T, M)>,
+}
+
+impl<'d, T: Instance> UartTx<'d, T, Blocking> {
+    /// Create a new blocking tx-only UART with no hardware flow control.
+    pub fn new_blocking(
+        _peri: impl Peripheral<P = T> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(tx);
+        let tx = new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None);
+        Self::new_inner(Some(tx), None, config)
+    }
+}
+
+impl<'d, T: Instance> UartTx<'d, T, Async> {
+    /// Create a new async tx-only UART with no hardware flow control.
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(tx);
+        let tx = new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None);
+        let this = Self::new_inner(Some(tx), None, config)?;
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        Ok(this)
+    }
+
+    /// Write all bytes in `buffer`, waiting for space in the transmit register
+    /// via the USART interrupt.
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let r = T::regs();
+        let s = T::state();
+        for &b in buffer {
+            poll_fn(|cx| {
+                s.tx_waker.register(cx.waker());
+                if r.isr().read().txe() {
+                    return Poll::Ready(());
+                }
+                r.cr1().modify(|w| w.set_txeie(true));
+                Poll::Pending
+            })
+            .await;
+            r.tdr().write(|w| w.set_tdr(b as _));
+        }
+        Ok(())
+    }
+
+    /// Wait until transmission complete
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let r = T::regs();
+        let s = T::state();
+        poll_fn(|cx| {
+            s.tx_waker.register(cx.waker());
+            if r.isr().read().tc() {
+                return Poll::Ready(());
+            }
+            r.cr1().modify(|w| w.set_tcie(true));
+            Poll::Pending
+        })
+        .await;
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, M: Mode> UartTx<'d, T, M> {
+    fn new_inner(
+        tx: Option<Flex<'d>>,
+        cts: Option<Flex<'d>>,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let r = T::regs();
+        enable_and_configure::<T>(&config)?;
+        let this = Self {
+            _tx: tx,
+            cts,
+            _phantom: PhantomData,
+        };
+        this.set_flow_control(&config);
+        r.cr1().modify(|w| {
+            w.set_te(true);
+            w.set_ue(true);
+        });
+
+        Ok(this)
+    }
+
+    /// Reconfigure the driver
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)?;
+        self.set_flow_control(config);
+        Ok(())
+    }
+
+    fn set_flow_control(&self, config: &Config) {
+        let enable = self.cts.is_some() && config.flow_control == FlowControl::RtsCts;
+        set_cr3::<T>(|w| w.set_ctse(enable));
+    }
+
+    /// Perform a blocking UART write
+    pub fn blocking_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let r = T::regs();
+        for &b in buffer {
+            while !r.isr().read().txe() {}
+            r.tdr().write(|w| w.set_tdr(b as _));
+        }
+        Ok(())
+    }
+
+    /// Block until transmission complete
+    pub fn blocking_flush(&mut self) -> Result<(), Error> {
+        let r = T::regs();
+        while !r.isr().read().tc() {}
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, M: Mode> Drop for UartTx<'d, T, M> {
+    fn drop(&mut self) {
+        T::regs().cr1().modify(|w| {
+            w.set_te(false);
+            w.set_txeie(false);
+            w.set_tcie(false);
+        });
+        drop_tx_rx::<T>();
+    }
+}
+
+impl<'d, T: Instance> UartRx<'d, T, Blocking> {
+    /// Create a new blocking rx-only UART with no hardware flow control.
+    pub fn new_blocking(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx);
+        let rx = new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up);
+        Self::new_inner(Some(rx), None, config)
+    }
+}
+
+impl<'d, T: Instance> UartRx<'d, T, Async> {
+    /// Create a new async rx-only UART with no hardware flow control.
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx);
+        let rx = new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up);
+        let this = Self::new_inner(Some(rx), None, config)?;
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        Ok(this)
+    }
+
+    /// Read a single byte, waiting for it via the USART interrupt.
+    async fn read_byte(&mut self) -> Result<u8, Error> {
+        let r = T::regs();
+        let s = T::state();
+        poll_fn(|cx| {
+            s.rx_waker.register(cx.waker());
+            match check_rx::<T>() {
+                Err(e) => Poll::Ready(Err(e)),
+                Ok(Some(b)) => Poll::Ready(Ok(b)),
+                Ok(None) => {
+                    r.cr1().modify(|w| {
+                        w.set_rxneie(true);
+                        w.set_peie(true);
+                    });
+                    r.cr3().modify(|w| w.set_eie(true));
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Read enough bytes to fill `buffer`
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        for b in buffer.iter_mut() {
+            *b = self.read_byte().await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for at least one byte, then read as many as are available without waiting.
+    ///
+    /// Returns the number of bytes read.
+    pub async fn read_available(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        buffer[0] = self.read_byte().await?;
+        let mut n = 1;
+        while n < buffer.len() {
+            match check_rx::<T>()? {
+                Some(b) => {
+                    buffer[n] = b;
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<'d, T: Instance, M: Mode> UartRx<'d, T, M> {
+    fn new_inner(
+        rx: Option<Flex<'d>>,
+        rts: Option<Flex<'d>>,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let r = T::regs();
+        enable_and_configure::<T>(&config)?;
+        let this = Self {
+            _rx: rx,
+            rts,
+            _phantom: PhantomData,
+        };
+        this.set_flow_control(&config);
+        r.cr1().modify(|w| {
+            w.set_re(true);
+            w.set_ue(true);
+        });
+
+        Ok(this)
+    }
+
+    /// Reconfigure the driver
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)?;
+        self.set_flow_control(config);
+        Ok(())
+    }
+
+    fn set_flow_control(&self, config: &Config) {
+        let enable = self.rts.is_some() && config.flow_control == FlowControl::RtsCts;
+        set_cr3::<T>(|w| w.set_rtse(enable));
+    }
+
+    /// Read a single byte if one is available.
+    pub fn nb_read(&mut self) -> Result<u8, nb::Error<Error>> {
+        match check_rx::<T>() {
+            Ok(Some(b)) => Ok(b),
+            Ok(None) => Err(nb::Error::WouldBlock),
+            Err(e) => Err(nb::Error::Other(e)),
+        }
+    }
+
+    /// Perform a blocking read into `buffer`
+    pub fn blocking_read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        for b in buffer.iter_mut() {
+            *b = loop {
+                if let Some(b) = check_rx::<T>()? {
+                    break b;
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, M: Mode> Drop for UartRx<'d, T, M> {
+    fn drop(&mut self) {
+        T::regs().cr1().modify(|w| {
+            w.set_re(false);
+            w.set_rxneie(false);
+            w.set_idleie(false);
+            w.set_peie(false);
+        });
+        drop_tx_rx::<T>();
+    }
+}
+
+impl<'d, T: Instance> Uart<'d, T, Blocking> {
+    /// Create a new blocking bidirectional UART.
+    pub fn new_blocking(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx, tx);
+        Self::new_inner(
+            new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up),
+            new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None),
+            None,
+            None,
+            config,
+        )
+    }
+
+    /// Create a new blocking bidirectional UART with request-to-send and clear-to-send pins
+    pub fn new_blocking_with_rtscts(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        rts: impl Peripheral<P = impl RtsPin<T>> + 'd,
+        cts: impl Peripheral<P = impl CtsPin<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx, tx, rts, cts);
+        Self::new_inner(
+            new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up),
+            new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None),
+            Some(new_pin::<T>(rts.map_into(), Signal::Rts, Pull::None)),
+            Some(new_pin::<T>(cts.map_into(), Signal::Cts, Pull::Down)),
+            Config {
+                flow_control: FlowControl::RtsCts,
+                ..config
+            },
+        )
+    }
+}
+
+impl<'d, T: Instance> Uart<'d, T, Async> {
+    /// Create a new async bidirectional UART.
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx, tx);
+        let this = Self::new_inner(
+            new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up),
+            new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None),
+            None,
+            None,
+            config,
+        )?;
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        Ok(this)
+    }
+
+    /// Create a new async bidirectional UART with request-to-send and clear-to-send pins
+    pub fn new_with_rtscts(
+        _peri: impl Peripheral<P = T> + 'd,
+        rx: impl Peripheral<P = impl RxPin<T>> + 'd,
+        tx: impl Peripheral<P = impl TxPin<T>> + 'd,
+        rts: impl Peripheral<P = impl RtsPin<T>> + 'd,
+        cts: impl Peripheral<P = impl CtsPin<T>> + 'd,
+        _irq: impl Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        into_ref!(rx, tx, rts, cts);
+        let this = Self::new_inner(
+            new_pin::<T>(rx.map_into(), Signal::Rx, Pull::Up),
+            new_pin::<T>(tx.map_into(), Signal::Tx, Pull::None),
+            Some(new_pin::<T>(rts.map_into(), Signal::Rts, Pull::None)),
+            Some(new_pin::<T>(cts.map_into(), Signal::Cts, Pull::Down)),
+            Config {
+                flow_control: FlowControl::RtsCts,
+                ..config
+            },
+        )?;
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        Ok(this)
+    }
+
+    /// Write all bytes in `buffer`
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.tx.write(buffer).await
+    }
+
+    /// Wait until transmission complete
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.tx.flush().await
+    }
+
+    /// Read enough bytes to fill `buffer`
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.rx.read(buffer).await
+    }
+
+    /// Wait for at least one byte, then read as many as are available without waiting.
+    pub async fn read_available(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read_available(buffer).await
+    }
+}
+
+impl<'d, T: Instance, M: Mode> Uart<'d, T, M> {
+    fn new_inner(
+        rx: Flex<'d>,
+        tx: Flex<'d>,
+        rts: Option<Flex<'d>>,
+        cts: Option<Flex<'d>>,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        Ok(Self {
+            tx: UartTx::new_inner(Some(tx), cts, config)?,
+            rx: UartRx::new_inner(Some(rx), rts, config)?,
+        })
+    }
+
+    /// Reconfigure the driver
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        reconfigure::<T>(config)?;
+        self.tx.set_flow_control(config);
+        self.rx.set_flow_control(config);
+        Ok(())
+    }
+
+    /// Perform a blocking write
+    pub fn blocking_write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.tx.blocking_write(buffer)
+    }
+
+    /// Block until transmission complete
+    pub fn blocking_flush(&mut self) -> Result<(), Error> {
+        self.tx.blocking_flush()
+    }
+
+    /// Read a single byte if one is available.
+    pub fn nb_read(&mut self) -> Result<u8, nb::Error<Error>> {
+        self.rx.nb_read()
+    }
+
+    /// Perform a blocking read into `buffer`
+    pub fn blocking_read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.rx.blocking_read(buffer)
+    }
+
+    /// Split the Uart into a transmitter and receiver, which is
+    /// particularly useful when having two tasks correlating to
+    /// transmitting and receiving.
+    pub fn split(self) -> (UartTx<'d, T, M>, UartRx<'d, T, M>) {
+        (self.tx, self.rx)
+    }
+
+    /// Split the Uart into a transmitter and receiver by mutable reference,
+    /// which is particularly useful when having two tasks correlating to
+    /// transmitting and receiving.
+    pub fn split_ref(&mut self) -> (&mut UartTx<'d, T, M>, &mut UartRx<'d, T, M>) {
+        (&mut self.tx, &mut self.rx)
+    }
+}
+
+/// USART signal, used to route a `PAxx_I2C_UART` pad to the right input/output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Signal {
+    Tx,
+    Rx,
+    Rts,
+    Cts,
+}
+
+fn new_pin<'d, T: Instance>(
+    pin: embassy_hal_internal::PeripheralRef<'d, AnyPin>,
+    signal: Signal,
+    pull: Pull,
+) -> Flex<'d> {
+    let mut pin = Flex::new(pin);
+    pin.set_pull(pull);
+    unwrap!(pin.route(T::route_signal(signal)));
+    pin
+}
+
+fn enable_and_configure<T: Instance>(config: &Config) -> Result<(), ConfigError> {
+    let s = T::state();
+    // Only enable and reset the peripheral when the first half is created,
+    // so that creating the second half does not break the first one.
+    if s.tx_rx_refcount.fetch_add(1, core::sync::atomic::Ordering::AcqRel) == 0 {
+        T::enable_and_reset();
+    }
+    reconfigure::<T>(config).inspect_err(|_| drop_tx_rx::<T>())
+}
+
+fn drop_tx_rx<T: Instance>() {
+    let s = T::state();
+    if s.tx_rx_refcount.fetch_sub(1, core::sync::atomic::Ordering::AcqRel) == 1 {
+        T::regs().cr1().modify(|w| w.set_ue(false));
+    }
+}
+
+fn reconfigure<T: Instance>(config: &Config) -> Result<(), ConfigError> {
+    let r = T::regs();
+    let clk = T::frequency().ok_or(ConfigError::ClockDisabled)?;
+    let brr = calculate_brr(clk, config.baudrate)?;
+
+    // Most configuration bits can only be written while UE is cleared.
+    let cr1 = r.cr1().read();
+    r.cr1().modify(|w| w.set_ue(false));
+
+    r.brr().write(|w| {
+        w.set_int(brr >> 4);
+        w.set_frac((brr & 0xf) as u8);
+    });
+
+    r.cr2().modify(|w| {
+        w.set_stop(match config.stop_bits {
+            StopBits::STOP1 => 0b00,
+            StopBits::STOP2 => 0b10,
+            StopBits::STOP1P5 => 0b11,
+        });
+    });
+
+    // The M field counts the parity bit as part of the word.
+    let word_bits = match config.data_bits {
+        DataBits::DataBits6 => 6,
+        DataBits::DataBits7 => 7,
+        DataBits::DataBits8 => 8,
+    } + (config.parity != Parity::ParityNone) as u8;
+
+    r.cr1().modify(|w| {
+        // 0b00: 6 bits, 0b01: 7 bits, 0b10: 8 bits, 0b11: 9 bits
+        w.set_m(word_bits - 6);
+        w.set_pce(config.parity != Parity::ParityNone);
+        w.set_ps(config.parity == Parity::ParityOdd);
+        w.set_ue(cr1.ue());
+    });
+
+    Ok(())
+}
+
+/// Modify CR3, whose flow control bits can only be written while UE is cleared.
+fn set_cr3<T: Instance>(f: impl FnOnce(&mut crate::pac::usart::regs::Cr3)) {
+    let r = T::regs();
+    let ue = r.cr1().read().ue();
+    r.cr1().modify(|w| w.set_ue(false));
+    r.cr3().modify(f);
+    r.cr1().modify(|w| w.set_ue(ue));
+}
+
+/// Compute the BRR value (12.4 fixed point USARTDIV, 16x oversampling).
+fn calculate_brr(clk: Hertz, baudrate: u32) -> Result<u32, ConfigError> {
+    if baudrate == 0 {
+        return Err(ConfigError::BaudrateTooLow);
+    }
+    let div = (clk.0 + baudrate / 2) / baudrate;
+    if div < 16 {
+        return Err(ConfigError::BaudrateTooHigh);
+    }
+    if div >> 4 > 0xffff {
+        return Err(ConfigError::BaudrateTooLow);
+    }
+    Ok(div)
+}
+
+/// Check the receiver for errors and data.
+///
+/// Errors are cleared when reported.
+fn check_rx<T: Instance>() -> Result<Option<u8>, Error> {
+    let r = T::regs();
+    let isr = r.isr().read();
+    if isr.pe() {
+        r.icr().write(|w| w.set_pecf(true));
+        Err(Error::Parity)
+    } else if isr.fe() {
+        r.icr().write(|w| w.set_fecf(true));
+        Err(Error::Framing)
+    } else if isr.nf() {
+        r.icr().write(|w| w.set_ncf(true));
+        Err(Error::Noise)
+    } else if isr.ore() {
+        r.icr().write(|w| w.set_orecf(true));
+        Err(Error::Overrun)
+    } else if isr.rxne() {
+        Ok(Some(r.rdr().read().rdr() as u8))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(crate) struct State {
+    rx_waker: AtomicWaker,
+    tx_waker: AtomicWaker,
+    tx_rx_refcount: core::sync::atomic::AtomicU8,
+}
+
+impl State {
+    pub(crate) const fn new() -> Self {
+        Self {
+            rx_waker: AtomicWaker::new(),
+            tx_waker: AtomicWaker::new(),
+            tx_rx_refcount: core::sync::atomic::AtomicU8::new(0),
+        }
+    }
+}
+
+trait SealedInstance: RccGetFreq + Peripheral<P = Self> {
+    fn regs() -> Regs;
+    fn state() -> &'static State;
+    fn enable_and_reset();
+    fn route_signal(signal: Signal) -> crate::gpio::Signal;
+}
+
+/// USART peripheral instance trait.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + 'static {
+    /// Interrupt for this peripheral.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pin_trait!(RxPin, Instance);
+pin_trait!(TxPin, Instance);
+pin_trait!(CtsPin, Instance);
+pin_trait!(RtsPin, Instance);
+
+// TODO: move to _generated.rs
+macro_rules! impl_usart {
+    ($inst:ident, [$tx:ident, $rx:ident, $rts:ident, $cts:ident], $enable_and_reset:expr) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            fn regs() -> Regs {
+                pac::$inst
+            }
+
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
+
+            fn enable_and_reset() {
+                $enable_and_reset
+            }
+
+            fn route_signal(signal: Signal) -> crate::gpio::Signal {
+                match signal {
+                    Signal::Tx => crate::gpio::Signal::$tx,
+                    Signal::Rx => crate::gpio::Signal::$rx,
+                    Signal::Rts => crate::gpio::Signal::$rts,
+                    Signal::Cts => crate::gpio::Signal::$cts,
+                }
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$inst;
+        }
+    };
+}
+
+// USART1 can be reset but can't be disabled, so there is no generated
+// `RccEnableReset` impl for it.
+impl_usart!(USART1, [Usart1Tx, Usart1Rx, Usart1Rts, Usart1Cts], {
+    pac::HPSYS_RCC.rstr1().modify(|w| w.set_usart1(true));
+    while !pac::HPSYS_RCC.rstr1().read().usart1() {}
+    pac::HPSYS_RCC.rstr1().modify(|w| w.set_usart1(false));
+});
+impl_usart!(USART2, [Usart2Tx, Usart2Rx, Usart2Rts, Usart2Cts], crate::rcc::enable_and_reset::<crate::peripherals::USART2>());
+impl_usart!(USART3, [Usart3Tx, Usart3Rx, Usart3Rts, Usart3Cts], crate::rcc::enable_and_reset::<crate::peripherals::USART3>());
+
+// ====================
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match *self {
+            Error::Framing => embedded_io::ErrorKind::InvalidData,
+            Error::Noise => embedded_io::ErrorKind::InvalidData,
+            Error::Overrun => embedded_io::ErrorKind::Other,
+            Error::Parity => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl embedded_hal_nb::serial::Error for Error {
+    fn kind(&self) -> embedded_hal_nb::serial::ErrorKind {
+        match *self {
+            Self::Framing => embedded_hal_nb::serial::ErrorKind::FrameFormat,
+            Self::Noise => embedded_hal_nb::serial::ErrorKind::Noise,
+            Self::Overrun => embedded_hal_nb::serial::ErrorKind::Overrun,
+            Self::Parity => embedded_hal_nb::serial::ErrorKind::Parity,
+        }
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::ErrorType for Uart<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::ErrorType for UartTx<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::ErrorType for UartRx<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::Write for Uart<'d, T, M> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.blocking_write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::Write for UartTx<'d, T, M> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.blocking_write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blocking_flush()
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::Read for UartRx<'d, T, M> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.blocking_read(&mut buf[..1])?;
+        let mut n = 1;
+        while n < buf.len() {
+            match check_rx::<T>()? {
+                Some(b) => {
+                    buf[n] = b;
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_io::Read for Uart<'d, T, M> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        embedded_io::Read::read(&mut self.rx, buf)
+    }
+}
+
+impl<'d, T: Instance> embedded_io_async::Write for Uart<'d, T, Async> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush().await
+    }
+}
+
+impl<'d, T: Instance> embedded_io_async::Write for UartTx<'d, T, Async> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush().await
+    }
+}
+
+impl<'d, T: Instance> embedded_io_async::Read for UartRx<'d, T, Async> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_available(buf).await
+    }
+}
+
+impl<'d, T: Instance> embedded_io_async::Read for Uart<'d, T, Async> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_available(buf).await
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::ErrorType for Uart<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::ErrorType for UartTx<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::ErrorType for UartRx<'d, T, M> {
+    type Error = Error;
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::Read for UartRx<'d, T, M> {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.nb_read()
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::Read for Uart<'d, T, M> {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.nb_read()
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::Write for UartTx<'d, T, M> {
+    fn write(&mut self, char: u8) -> nb::Result<(), Self::Error> {
+        let r = T::regs();
+        if !r.isr().read().txe() {
+            return Err(nb::Error::WouldBlock);
+        }
+        r.tdr().write(|w| w.set_tdr(char as _));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if !T::regs().isr().read().tc() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+}
+
+impl<'d, T: Instance, M: Mode> embedded_hal_nb::serial::Write for Uart<'d, T, M> {
+    fn write(&mut self, char: u8) -> nb::Result<(), Self::Error> {
+        embedded_hal_nb::serial::Write::write(&mut self.tx, char)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        embedded_hal_nb::serial::Write::flush(&mut self.tx)
+    }
+}
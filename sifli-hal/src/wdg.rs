@@ -0,0 +1,358 @@
+//! Watchdog timers (WDT1, IWDT) and reset reason.
+//!
+//! Both watchdogs count `clk_wdt` (LRC10 or LRC32, see
+//! [`rcc::Config::clk_wdt_sel`](crate::rcc::Config)). WDT1 lives in HPSYS and
+//! resets HPSYS; IWDT lives in the always-on domain and resets the whole chip,
+//! it keeps counting in deep sleep.
+//!
+//! A watchdog has two stages. Without early warning, the reset happens when
+//! the first stage expires. With early warning, the first stage raises the
+//! early-warning interrupt instead and the reset happens when the second stage
+//! expires too, unless the watchdog was fed in between.
+//!
+//! The early-warning interrupt of both watchdogs is the NMI. Handle it with
+//! `#[cortex_m_rt::exception] fn NonMaskableInt()` and call
+//! [`take_early_warning`] there.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::pac::wdt::Wdt as Regs;
+use crate::pac::{HPSYS_AON, PMUC};
+use crate::rcc::RccGetFreq;
+use crate::{pac, peripherals};
+
+/// Largest counter reload value.
+const MAX_COUNT: u32 = 0x00FF_FFFF;
+
+// Command register values
+const CMD_START: u32 = 0x76;
+const CMD_STOP: u32 = 0x34;
+const CMD_RELOAD: u32 = 0x51;
+
+// Write protection keys
+const WP_UNLOCK: u32 = 0x51FF_8621;
+const WP_LOCK: u32 = 0x58AB_99FC;
+
+/// Watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wdt {
+    /// WDT1, resets HPSYS.
+    Wdt1,
+    /// IWDT, resets the whole chip.
+    Iwdt,
+}
+
+/// Watchdog error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// `clk_wdt` is not running.
+    ClockDisabled,
+    /// The timeout is zero, or too long for the counter at the current `clk_wdt` frequency.
+    InvalidTimeout,
+}
+
+/// Watchdog config.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Time without feeding until the reset, in microseconds.
+    pub timeout_us: u32,
+    /// Time without feeding until the early-warning interrupt, in microseconds.
+    ///
+    /// Must be shorter than `timeout_us`. `None` disables the early warning.
+    pub early_warning_us: Option<u32>,
+    /// Stop counting while the core is halted by a debugger.
+    pub pause_in_debug: bool,
+    /// Stop counting while the [`low_power`](crate::low_power) executor is in
+    /// light or deep sleep. Counting resumes with a full timeout on wakeup.
+    pub pause_in_sleep: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timeout_us: 1_000_000,
+            early_warning_us: None,
+            pause_in_debug: true,
+            pause_in_sleep: false,
+        }
+    }
+}
+
+/// Watchdogs paused in sleep, bit per [`Wdt`].
+static PAUSE_IN_SLEEP: AtomicU8 = AtomicU8::new(0);
+
+/// Watchdog driver.
+///
+/// Once started, a watchdog can't be stopped by dropping the driver.
+pub struct Watchdog<'d, T: Instance> {
+    _wdt: PeripheralRef<'d, T>,
+}
+
+impl<'d, T: Instance> Watchdog<'d, T> {
+    /// Create a new watchdog driver. The watchdog is not started.
+    pub fn new(wdt: impl Peripheral<P = T> + 'd, config: Config) -> Result<Self, Error> {
+        into_ref!(wdt);
+
+        let this = Self { _wdt: wdt };
+        this.set_config(&config)?;
+        Ok(this)
+    }
+
+    /// Change the configuration. A running watchdog restarts with the new timeouts.
+    pub fn set_config(&self, config: &Config) -> Result<(), Error> {
+        let freq = T::frequency().ok_or(Error::ClockDisabled)?;
+        let ticks = |us: u32| {
+            let ticks = freq.0 as u64 * us as u64 / 1_000_000;
+            if ticks == 0 || ticks > MAX_COUNT as u64 {
+                Err(Error::InvalidTimeout)
+            } else {
+                Ok(ticks as u32)
+            }
+        };
+
+        let (stage1, stage2) = match config.early_warning_us {
+            Some(early) if early >= config.timeout_us => return Err(Error::InvalidTimeout),
+            Some(early) => (ticks(early)?, Some(ticks(config.timeout_us - early)?)),
+            None => (ticks(config.timeout_us)?, None),
+        };
+
+        let bit = 1 << T::WDT as u8;
+        if config.pause_in_sleep {
+            PAUSE_IN_SLEEP.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            PAUSE_IN_SLEEP.fetch_and(!bit, Ordering::Relaxed);
+        }
+
+        unlocked(T::regs(), |r| {
+            r.cvr0().write(|w| w.set_val(stage1));
+            r.cvr1().write(|w| w.set_val(stage2.unwrap_or(0)));
+            r.cr().write(|w| {
+                // Interrupt on the first stage, then reset on the second one.
+                w.set_response_mode(stage2.is_some());
+                w.set_dbg_pause(config.pause_in_debug);
+            });
+            if r.sr().read().wdt_active() {
+                r.ccr().write(|w| w.set_cmd(CMD_RELOAD));
+            }
+        });
+        Ok(())
+    }
+
+    /// Start the watchdog.
+    pub fn start(&mut self) {
+        unlocked(T::regs(), |r| r.ccr().write(|w| w.set_cmd(CMD_START)));
+    }
+
+    /// Stop the watchdog.
+    pub fn stop(&mut self) {
+        unlocked(T::regs(), |r| r.ccr().write(|w| w.set_cmd(CMD_STOP)));
+    }
+
+    /// Check whether the watchdog is running.
+    pub fn is_running(&self) -> bool {
+        T::regs().sr().read().wdt_active()
+    }
+
+    /// Feed the watchdog, restarting the timeout.
+    pub fn pet(&mut self) {
+        unlocked(T::regs(), |r| r.ccr().write(|w| w.set_cmd(CMD_RELOAD)));
+    }
+}
+
+/// Run `f` with the write protection of `r` disabled.
+fn unlocked<R>(r: Regs, f: impl FnOnce(Regs) -> R) -> R {
+    critical_section::with(|_| {
+        r.wp().write(|w| w.set_key(WP_UNLOCK));
+        let result = f(r);
+        r.wp().write(|w| w.set_key(WP_LOCK));
+        result
+    })
+}
+
+/// Get and clear the pending early warning, from the NMI handler.
+///
+/// Feed the watchdog in time to prevent the reset. Returns `None` if no
+/// watchdog raised an early warning.
+pub fn take_early_warning() -> Option<Wdt> {
+    for (r, wdt) in [(pac::WDT1, Wdt::Wdt1), (pac::IWDT, Wdt::Iwdt)] {
+        if r.sr().read().int_assert() {
+            r.icr().write(|w| w.set_int_clr(true));
+            return Some(wdt);
+        }
+    }
+    None
+}
+
+/// Watchdogs stopped by [`pause_for_sleep`], bit per [`Wdt`].
+#[cfg(feature = "low-power")]
+static PAUSED: AtomicU8 = AtomicU8::new(0);
+
+/// Stop the running watchdogs configured with `pause_in_sleep`, before sleeping.
+#[cfg(feature = "low-power")]
+pub(crate) fn pause_for_sleep() {
+    let pause = PAUSE_IN_SLEEP.load(Ordering::Relaxed);
+    let mut paused = 0;
+    for (r, wdt) in [(pac::WDT1, Wdt::Wdt1), (pac::IWDT, Wdt::Iwdt)] {
+        let bit = 1 << wdt as u8;
+        if pause & bit != 0 && r.sr().read().wdt_active() {
+            unlocked(r, |r| r.ccr().write(|w| w.set_cmd(CMD_STOP)));
+            paused |= bit;
+        }
+    }
+    PAUSED.store(paused, Ordering::Relaxed);
+}
+
+/// Restart the watchdogs stopped by [`pause_for_sleep`].
+#[cfg(feature = "low-power")]
+pub(crate) fn resume_after_sleep() {
+    let paused = PAUSED.swap(0, Ordering::Relaxed);
+    for (r, wdt) in [(pac::WDT1, Wdt::Wdt1), (pac::IWDT, Wdt::Iwdt)] {
+        if paused & (1 << wdt as u8) != 0 {
+            unlocked(r, |r| r.ccr().write(|w| w.set_cmd(CMD_START)));
+        }
+    }
+}
+
+/// Cause of the last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetReason {
+    /// Power-on.
+    PowerOn,
+    /// Supply voltage dropped below the brown-out threshold.
+    BrownOut,
+    /// Reset pin.
+    Pin,
+    /// Watchdog timeout.
+    Watchdog(Wdt),
+    /// Software reset, e.g. `cortex_m::peripheral::SCB::sys_reset`.
+    Software,
+    /// Core lockup.
+    Lockup,
+    /// Wakeup from hibernate.
+    Hibernate,
+    /// No status flag set.
+    Unknown,
+}
+
+impl ResetReason {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::PowerOn => 1,
+            Self::BrownOut => 2,
+            Self::Pin => 3,
+            Self::Watchdog(Wdt::Wdt1) => 4,
+            Self::Watchdog(Wdt::Iwdt) => 5,
+            Self::Software => 6,
+            Self::Lockup => 7,
+            Self::Hibernate => 8,
+            Self::Unknown => 9,
+        }
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        Some(match v {
+            1 => Self::PowerOn,
+            2 => Self::BrownOut,
+            3 => Self::Pin,
+            4 => Self::Watchdog(Wdt::Wdt1),
+            5 => Self::Watchdog(Wdt::Iwdt),
+            6 => Self::Software,
+            7 => Self::Lockup,
+            8 => Self::Hibernate,
+            9 => Self::Unknown,
+            _ => return None,
+        })
+    }
+}
+
+/// Reset reason read at boot, 0 until read.
+static RESET_REASON: AtomicU8 = AtomicU8::new(0);
+
+/// Read and clear the reset status flags.
+fn read_reset_reason() -> ResetReason {
+    let rsr = PMUC.rsr().read();
+    let swr = HPSYS_AON.sr().read();
+
+    // Several flags may be set, e.g. power-on together with brown-out; the
+    // order below picks the root cause.
+    let reason = if rsr.por() {
+        ResetReason::PowerOn
+    } else if rsr.bor() {
+        ResetReason::BrownOut
+    } else if rsr.iwdt() {
+        ResetReason::Watchdog(Wdt::Iwdt)
+    } else if rsr.wdt1() {
+        ResetReason::Watchdog(Wdt::Wdt1)
+    } else if rsr.pin() {
+        ResetReason::Pin
+    } else if rsr.hib() {
+        ResetReason::Hibernate
+    } else if swr.lockup() {
+        ResetReason::Lockup
+    } else if swr.sysresetreq() {
+        ResetReason::Software
+    } else {
+        ResetReason::Unknown
+    };
+
+    // Write 1 to clear, so that the next reset reports its own cause.
+    PMUC.rsr().write_value(rsr);
+    HPSYS_AON.sr().write_value(swr);
+    reason
+}
+
+/// Record the reset reason, called by `init`.
+pub(crate) fn init() {
+    let reason = read_reset_reason();
+    RESET_REASON.store(reason.to_u8(), Ordering::Relaxed);
+}
+
+/// Get the cause of the last reset.
+///
+/// The status flags are read and cleared by [`crate::init`], so this keeps
+/// returning the same value until the next reset.
+pub fn reset_reason() -> ResetReason {
+    match ResetReason::from_u8(RESET_REASON.load(Ordering::Relaxed)) {
+        Some(reason) => reason,
+        None => {
+            // Called before `init`.
+            let reason = read_reset_reason();
+            RESET_REASON.store(reason.to_u8(), Ordering::Relaxed);
+            reason
+        }
+    }
+}
+
+trait SealedInstance: RccGetFreq {
+    const WDT: Wdt;
+
+    fn regs() -> Regs;
+}
+
+/// Watchdog instance.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + Peripheral<P = Self> + 'static {}
+
+macro_rules! impl_wdt {
+    ($inst:ident, $wdt:ident) => {
+        impl SealedInstance for peripherals::$inst {
+            const WDT: Wdt = Wdt::$wdt;
+
+            fn regs() -> Regs {
+                pac::$inst
+            }
+        }
+
+        impl Instance for peripherals::$inst {}
+    };
+}
+
+impl_wdt!(WDT1, Wdt1);
+impl_wdt!(IWDT, Iwdt);